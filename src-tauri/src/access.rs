@@ -0,0 +1,287 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hudsucker::hyper::{header, Body, HeaderMap, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProxyCredentials {
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password: String,
+}
+
+/// An allowlist entry, either a single address or a CIDR range.
+#[derive(Clone, Debug)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid IP address: {}", s))?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("Invalid prefix length: {}", s))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // Compare IPv4-mapped IPv6 clients against IPv4 ranges
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Who may use the proxy: a client IP allowlist plus optional basic auth.
+pub struct AccessControl {
+    credentials: Option<ProxyCredentials>,
+    allowlist: Vec<IpRange>,
+    // Client connections with an open, authenticated CONNECT tunnel. Requests
+    // inside the tunnel don't repeat Proxy-Authorization.
+    authorized: Mutex<HashSet<SocketAddr>>,
+}
+
+impl AccessControl {
+    pub fn new(
+        credentials: Option<ProxyCredentials>,
+        allowed_ips: &[String],
+    ) -> Result<Self, String> {
+        let allowlist = allowed_ips
+            .iter()
+            .map(|s| IpRange::parse(s))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            credentials,
+            allowlist,
+            authorized: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Loopback is always allowed, an empty allowlist allows everybody.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        ip.is_loopback()
+            || self.allowlist.is_empty()
+            || self.allowlist.iter().any(|range| range.contains(ip))
+    }
+
+    /// Checks `Proxy-Authorization` for a request coming from `client_addr`.
+    pub fn is_authenticated(&self, client_addr: SocketAddr, headers: &HeaderMap) -> bool {
+        let Some(credentials) = &self.credentials else {
+            return true;
+        };
+        if self.authorized.lock().unwrap().contains(&client_addr) {
+            return true;
+        }
+
+        headers
+            .get(header::PROXY_AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Basic "))
+            .and_then(|v| STANDARD.decode(v.trim()).ok())
+            .and_then(|v| String::from_utf8(v).ok())
+            .and_then(|v| {
+                v.split_once(':')
+                    .map(|(user, pass)| (user.to_string(), pass.to_string()))
+            })
+            .map(|(user, pass)| {
+                // Both are compared so the time taken doesn't tell which one was wrong
+                let user_ok = constant_time_eq(&user, &credentials.username);
+                let pass_ok = constant_time_eq(&pass, &credentials.password);
+                user_ok & pass_ok
+            })
+            .unwrap_or(false)
+    }
+
+    /// Exempts the requests inside a CONNECT tunnel from `client_addr` until
+    /// the returned grant is dropped with the tunnel.
+    pub fn remember_tunnel(self: &Arc<Self>, client_addr: SocketAddr) -> Option<TunnelGrant> {
+        self.credentials.as_ref()?;
+        self.authorized.lock().unwrap().insert(client_addr);
+        Some(TunnelGrant {
+            access: self.clone(),
+            client_addr,
+        })
+    }
+}
+
+/// Keeps a tunnel's exemption alive, the address can be reused by an
+/// unrelated connection once the tunnel is closed.
+pub struct TunnelGrant {
+    access: Arc<AccessControl>,
+    client_addr: SocketAddr,
+}
+
+impl Drop for TunnelGrant {
+    fn drop(&mut self) {
+        self.access
+            .authorized
+            .lock()
+            .unwrap()
+            .remove(&self.client_addr);
+    }
+}
+
+// Hashing first makes the comparison independent of the lengths too
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let a = Sha256::digest(a.as_bytes());
+    let b = Sha256::digest(b.as_bytes());
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+pub fn forbidden() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(Body::from("Client address not allowed"))
+        .expect("Failed to build response")
+}
+
+pub fn proxy_auth_required() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+        .header(header::PROXY_AUTHENTICATE, "Basic realm=\"Yuri\"")
+        .body(Body::empty())
+        .expect("Failed to build response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(range: &str, ip: &str) -> bool {
+        IpRange::parse(range).unwrap().contains(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert!(IpRange::parse("10.0.0.1").is_ok());
+        assert!(IpRange::parse(" 10.0.0.0 / 8 ").is_ok());
+        assert!(IpRange::parse("::1/128").is_ok());
+        assert!(IpRange::parse("10.0.0.0/33").is_err());
+        assert!(IpRange::parse("::/129").is_err());
+        assert!(IpRange::parse("10.0.0.0/x").is_err());
+        assert!(IpRange::parse("example.com").is_err());
+    }
+
+    #[test]
+    fn cidr_masking() {
+        // Host bits in the range address are ignored
+        assert!(contains("192.168.1.77/24", "192.168.1.200"));
+        assert!(!contains("192.168.1.0/24", "192.168.2.1"));
+        assert!(contains("10.0.0.0/8", "10.255.255.255"));
+        assert!(contains("172.16.0.0/12", "172.31.0.1"));
+        assert!(!contains("172.16.0.0/12", "172.32.0.1"));
+        assert!(contains("fd00::/8", "fdab::1"));
+        assert!(!contains("fd00::/8", "fe80::1"));
+    }
+
+    #[test]
+    fn prefix_edges() {
+        assert!(contains("0.0.0.0/0", "203.0.113.9"));
+        assert!(contains("::/0", "2001:db8::1"));
+        assert!(contains("10.0.0.1/32", "10.0.0.1"));
+        assert!(!contains("10.0.0.1/32", "10.0.0.2"));
+        // A bare address is a /32 or /128
+        assert!(contains("10.0.0.1", "10.0.0.1"));
+        assert!(!contains("2001:db8::1", "2001:db8::2"));
+    }
+
+    #[test]
+    fn ipv4_mapped_clients() {
+        assert!(contains("10.0.0.0/8", "::ffff:10.1.2.3"));
+        assert!(!contains("10.0.0.0/8", "::ffff:11.1.2.3"));
+        // Families don't mix otherwise
+        assert!(!contains("::/0", "10.0.0.1"));
+        assert!(!contains("0.0.0.0/0", "2001:db8::1"));
+    }
+
+    #[test]
+    fn allowlist() {
+        let open = AccessControl::new(None, &[]).unwrap();
+        assert!(open.is_allowed("203.0.113.9".parse().unwrap()));
+
+        let access = AccessControl::new(None, &["10.0.0.0/8".to_string()]).unwrap();
+        assert!(access.is_allowed("10.2.3.4".parse().unwrap()));
+        assert!(access.is_allowed("127.0.0.1".parse().unwrap()));
+        assert!(access.is_allowed("::1".parse().unwrap()));
+        assert!(!access.is_allowed("192.168.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn credential_comparison() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+        assert!(!constant_time_eq("", "secret"));
+        assert!(constant_time_eq("", ""));
+    }
+
+    fn basic(user_pass: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = format!("Basic {}", STANDARD.encode(user_pass));
+        headers.insert(header::PROXY_AUTHORIZATION, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn tunnel_grant_lasts_until_dropped() {
+        let access = Arc::new(
+            AccessControl::new(
+                Some(ProxyCredentials {
+                    username: "user".to_string(),
+                    password: "pa:ss".to_string(),
+                }),
+                &[],
+            )
+            .unwrap(),
+        );
+        let client: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+        let other: SocketAddr = "10.0.0.1:50001".parse().unwrap();
+
+        assert!(access.is_authenticated(client, &basic("user:pa:ss")));
+        assert!(!access.is_authenticated(client, &basic("user:wrong")));
+        assert!(!access.is_authenticated(client, &HeaderMap::new()));
+
+        let grant = access.remember_tunnel(client).unwrap();
+        assert!(access.is_authenticated(client, &HeaderMap::new()));
+        assert!(!access.is_authenticated(other, &HeaderMap::new()));
+        drop(grant);
+        assert!(!access.is_authenticated(client, &HeaderMap::new()));
+
+        let open = Arc::new(AccessControl::new(None, &[]).unwrap());
+        assert!(open.remember_tunnel(client).is_none());
+    }
+}
@@ -0,0 +1,707 @@
+use crate::environments::SECRET_MASK;
+use crate::oauth::OAuth2Config;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Request, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Credentials applied by the API client at send time.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer {
+        token: String,
+    },
+    // Sent after the server's 401 challenge
+    Digest {
+        username: String,
+        password: String,
+    },
+    ApiKey {
+        key: String,
+        value: String,
+        #[serde(default)]
+        location: ApiKeyLocation,
+    },
+    AwsSigV4 {
+        access_key_id: String,
+        secret_access_key: String,
+        session_token: Option<String>,
+        region: String,
+        service: String,
+    },
+    // Exchanged for a bearer token by the OAuth manager before sending
+    #[serde(rename = "oauth2")]
+    OAuth2(OAuth2Config),
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+impl Auth {
+    /// Rewrites every credential field, e.g. to interpolate variables.
+    pub fn map_strings(self, mut f: impl FnMut(&str) -> String) -> Self {
+        match self {
+            Auth::Basic { username, password } => Auth::Basic {
+                username: f(&username),
+                password: f(&password),
+            },
+            Auth::Bearer { token } => Auth::Bearer { token: f(&token) },
+            Auth::Digest { username, password } => Auth::Digest {
+                username: f(&username),
+                password: f(&password),
+            },
+            Auth::ApiKey {
+                key,
+                value,
+                location,
+            } => Auth::ApiKey {
+                key: f(&key),
+                value: f(&value),
+                location,
+            },
+            Auth::AwsSigV4 {
+                access_key_id,
+                secret_access_key,
+                session_token,
+                region,
+                service,
+            } => Auth::AwsSigV4 {
+                access_key_id: f(&access_key_id),
+                secret_access_key: f(&secret_access_key),
+                session_token: session_token.map(|t| f(&t)),
+                region: f(&region),
+                service: f(&service),
+            },
+            Auth::OAuth2(config) => Auth::OAuth2(config.map_strings(f)),
+        }
+    }
+
+    /// Static credentials, added once before the first request. Redirects to
+    /// another origin drop them again with `strip`.
+    pub fn prepare(&self, headers: &mut HeaderMap, url: &mut Url) -> Result<(), String> {
+        match self {
+            Auth::Basic { username, password } => {
+                let credentials = STANDARD.encode(format!("{}:{}", username, password));
+                headers.insert(
+                    header::AUTHORIZATION,
+                    header_value(&format!("Basic {}", credentials))?,
+                );
+            }
+            Auth::Bearer { token } => {
+                headers.insert(
+                    header::AUTHORIZATION,
+                    header_value(&format!("Bearer {}", token))?,
+                );
+            }
+            Auth::ApiKey {
+                key,
+                value,
+                location: ApiKeyLocation::Header,
+            } => {
+                let name = HeaderName::from_str(key).map_err(|e| e.to_string())?;
+                headers.insert(name, header_value(value)?);
+            }
+            Auth::ApiKey {
+                key,
+                value,
+                location: ApiKeyLocation::Query,
+            } => {
+                url.query_pairs_mut().append_pair(key, value);
+            }
+            Auth::OAuth2(_) => return Err("OAuth 2.0 token was not acquired".to_string()),
+            Auth::Digest { .. } | Auth::AwsSigV4 { .. } => {}
+        }
+        Ok(())
+    }
+
+    /// Removes the headers added by `prepare`.
+    pub fn strip(&self, headers: &mut HeaderMap) {
+        match self {
+            Auth::ApiKey {
+                key,
+                location: ApiKeyLocation::Header,
+                ..
+            } => {
+                if let Ok(name) = HeaderName::from_str(key) {
+                    headers.remove(name);
+                }
+            }
+            _ => {
+                headers.remove(header::AUTHORIZATION);
+            }
+        }
+    }
+
+    /// Per request credentials that depend on the final request, called on
+    /// every request sent to the original origin.
+    pub fn sign(
+        &self,
+        request: &mut Request,
+        digest: Option<&mut DigestChallenge>,
+    ) -> Result<(), String> {
+        match self {
+            Auth::Digest { username, password } => {
+                if let Some(challenge) = digest {
+                    let value = challenge.authorization(
+                        username,
+                        password,
+                        request.method().as_str(),
+                        request.url(),
+                    );
+                    request
+                        .headers_mut()
+                        .insert(header::AUTHORIZATION, header_value(&value)?);
+                }
+                Ok(())
+            }
+            Auth::AwsSigV4 {
+                access_key_id,
+                secret_access_key,
+                session_token,
+                region,
+                service,
+            } => sign_v4(
+                request,
+                access_key_id,
+                secret_access_key,
+                session_token.as_deref(),
+                region,
+                service,
+                chrono::Utc::now(),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Credential values that must not show up in the history, including
+    /// the encoded form Basic auth puts on the wire.
+    pub fn secrets(&self) -> Vec<String> {
+        let secrets = match self {
+            Auth::Basic { username, password } => vec![
+                password.clone(),
+                STANDARD.encode(format!("{}:{}", username, password)),
+            ],
+            Auth::Bearer { token } => vec![token.clone()],
+            Auth::Digest { password, .. } => vec![password.clone()],
+            Auth::ApiKey { value, .. } => vec![value.clone()],
+            Auth::AwsSigV4 {
+                secret_access_key,
+                session_token,
+                ..
+            } => std::iter::once(secret_access_key.clone())
+                .chain(session_token.clone())
+                .collect(),
+            Auth::OAuth2(config) => config.client_secret.iter().cloned().collect(),
+        };
+        secrets.into_iter().filter(|s| !s.is_empty()).collect()
+    }
+
+    /// The same auth with its `secrets` replaced by `SECRET_MASK`.
+    pub fn masked(self) -> Self {
+        let secrets = self.secrets();
+        self.map_strings(|s| {
+            if secrets.iter().any(|secret| secret == s) {
+                SECRET_MASK.to_string()
+            } else {
+                s.to_string()
+            }
+        })
+    }
+
+    /// The same auth with its credentials replaced by `{{name}}`
+    /// placeholders, for exports shown outside the app.
+    pub fn with_placeholders(self) -> Self {
+        let placeholder = |name: &str| format!("{{{{{}}}}}", name);
+        match self {
+            Auth::Basic { username, .. } => Auth::Basic {
+                username,
+                password: placeholder("password"),
+            },
+            Auth::Bearer { .. } => Auth::Bearer {
+                token: placeholder("token"),
+            },
+            Auth::Digest { username, .. } => Auth::Digest {
+                username,
+                password: placeholder("password"),
+            },
+            Auth::ApiKey { key, location, .. } => Auth::ApiKey {
+                key,
+                value: placeholder("api_key"),
+                location,
+            },
+            Auth::AwsSigV4 {
+                session_token,
+                region,
+                service,
+                ..
+            } => Auth::AwsSigV4 {
+                access_key_id: placeholder("aws_access_key_id"),
+                secret_access_key: placeholder("aws_secret_access_key"),
+                session_token: session_token.map(|_| placeholder("aws_session_token")),
+                region,
+                service,
+            },
+            Auth::OAuth2(mut config) => {
+                config.client_secret = config.client_secret.map(|_| placeholder("client_secret"));
+                config.refresh_token = config.refresh_token.map(|_| placeholder("refresh_token"));
+                Auth::OAuth2(config)
+            }
+        }
+    }
+
+    pub fn is_digest(&self) -> bool {
+        matches!(self, Auth::Digest { .. })
+    }
+}
+
+fn header_value(value: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(value).map_err(|e| e.to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// HTTP Digest (RFC 7616)
+
+#[derive(Clone, Debug)]
+pub struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: String,
+    qop: Option<String>,
+    // Requests sent with this nonce
+    count: u32,
+}
+
+impl DigestChallenge {
+    /// Parses the Digest challenge out of the 401's WWW-Authenticate headers.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(Self::parse)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let (scheme, params) = value.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let params = parse_params(params);
+        let get = |name: &str| {
+            params
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        };
+
+        let algorithm = get("algorithm").unwrap_or_else(|| "MD5".to_string());
+        if !matches!(
+            algorithm.to_ascii_uppercase().as_str(),
+            "MD5" | "MD5-SESS" | "SHA-256" | "SHA-256-SESS"
+        ) {
+            return None;
+        }
+        // Only "auth" is supported, "auth-int" would need the body
+        let qop = get("qop").and_then(|qop| {
+            qop.split(',')
+                .map(str::trim)
+                .find(|q| q.eq_ignore_ascii_case("auth"))
+                .map(String::from)
+        });
+
+        Some(DigestChallenge {
+            realm: get("realm").unwrap_or_default(),
+            nonce: get("nonce")?,
+            opaque: get("opaque"),
+            algorithm,
+            qop,
+            count: 0,
+        })
+    }
+
+    fn hash(&self, data: &str) -> String {
+        if self.algorithm.to_ascii_uppercase().starts_with("SHA-256") {
+            hex(&Sha256::digest(data.as_bytes()))
+        } else {
+            hex(&Md5::digest(data.as_bytes()))
+        }
+    }
+
+    fn authorization(&mut self, username: &str, password: &str, method: &str, url: &Url) -> String {
+        let cnonce = Uuid::new_v4().simple().to_string();
+        self.authorization_with(username, password, method, url, &cnonce)
+    }
+
+    fn authorization_with(
+        &mut self,
+        username: &str,
+        password: &str,
+        method: &str,
+        url: &Url,
+        cnonce: &str,
+    ) -> String {
+        self.count += 1;
+        let nc = format!("{:08x}", self.count);
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+
+        let mut ha1 = self.hash(&format!("{}:{}:{}", username, self.realm, password));
+        if self.algorithm.to_ascii_uppercase().ends_with("-SESS") {
+            ha1 = self.hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = self.hash(&format!("{}:{}", method, uri));
+        let response = match &self.qop {
+            Some(qop) => self.hash(&format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, self.nonce, nc, cnonce, qop, ha2
+            )),
+            None => self.hash(&format!("{}:{}:{}", ha1, self.nonce, ha2)),
+        };
+
+        let mut value = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            username, self.realm, self.nonce, uri, self.algorithm, response
+        );
+        if let Some(qop) = &self.qop {
+            value.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+        }
+        if let Some(opaque) = &self.opaque {
+            value.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        value
+    }
+}
+
+// `key=value, key="quoted, value"` pairs
+fn parse_params(input: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = input.trim();
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        let key = key.trim().trim_start_matches(',').trim().to_string();
+        let after = after.trim_start();
+        let (value, remaining) = if let Some(quoted) = after.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            value.push(escaped);
+                        }
+                    }
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => value.push(c),
+                }
+            }
+            (value, &quoted[end.min(quoted.len())..])
+        } else {
+            let end = after.find(',').unwrap_or(after.len());
+            (after[..end].trim().to_string(), &after[end..])
+        };
+        params.push((key, value));
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+    params
+}
+
+// AWS Signature Version 4
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// RFC 3986 unreserved characters stay, everything else is percent encoded
+fn aws_encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// Other services encode the path a second time, S3 signs it as sent
+fn canonical_uri(url: &Url, service: &str) -> String {
+    if service == "s3" {
+        return url.path().to_string();
+    }
+    url.path()
+        .split('/')
+        .map(aws_encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn sign_v4(
+    request: &mut Request,
+    access_key_id: &str,
+    secret_access_key: &str,
+    session_token: Option<&str>,
+    region: &str,
+    service: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), String> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let url = request.url().clone();
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+    // Streamed bodies can't be hashed up front
+    let payload_hash = match request.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => hex(&Sha256::digest(bytes)),
+            None => "UNSIGNED-PAYLOAD".to_string(),
+        },
+        None => hex(&Sha256::digest(b"")),
+    };
+
+    let headers = request.headers_mut();
+    headers.insert(header::HOST, header_value(&host)?);
+    headers.insert("x-amz-date", header_value(&amz_date)?);
+    // Only S3 wants the payload hash as a header
+    if service == "s3" {
+        headers.insert("x-amz-content-sha256", header_value(&payload_hash)?);
+    }
+    if let Some(token) = session_token {
+        headers.insert("x-amz-security-token", header_value(token)?);
+    }
+
+    // Only headers that reqwest won't touch on the way out are signed
+    let mut signed: Vec<(String, String)> = headers
+        .iter()
+        .filter(|(name, _)| {
+            let name = name.as_str();
+            name == "host" || name == "content-type" || name.starts_with("x-amz-")
+        })
+        .map(|(name, value)| {
+            (
+                name.as_str().to_string(),
+                value.to_str().unwrap_or_default().trim().to_string(),
+            )
+        })
+        .collect();
+    signed.sort();
+    let signed_headers = signed
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = signed
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+
+    let mut query: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(k, v)| (aws_encode(&k), aws_encode(&v)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method().as_str(),
+        canonical_uri(&url, service),
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        payload_hash
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = hmac(format!("AWS4{}", secret_access_key).as_bytes(), &date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    let key = hmac(&key, "aws4_request");
+    let signature = hex(&hmac(&key, &string_to_sign));
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key_id, scope, signed_headers, signature
+    );
+    request
+        .headers_mut()
+        .insert(header::AUTHORIZATION, header_value(&authorization)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    // From the AWS Signature Version 4 test suite
+    fn sign_vector(method: &str, url: &str) -> String {
+        let mut request = Request::new(method.parse().unwrap(), url.parse().unwrap());
+        sign_v4(
+            &mut request,
+            "AKIDEXAMPLE",
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            None,
+            "us-east-1",
+            "service",
+            chrono::Utc
+                .with_ymd_and_hms(2015, 8, 30, 12, 36, 0)
+                .unwrap(),
+        )
+        .unwrap();
+        request.headers()[header::AUTHORIZATION]
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn sigv4_get_vanilla() {
+        assert_eq!(
+            sign_vector("GET", "https://example.amazonaws.com/"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn sigv4_post_vanilla() {
+        assert_eq!(
+            sign_vector("POST", "https://example.amazonaws.com/"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+
+    #[test]
+    fn sigv4_query_order() {
+        assert_eq!(
+            sign_vector(
+                "GET",
+                "https://example.amazonaws.com/?Param2=value2&Param1=value1"
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"
+        );
+    }
+
+    #[test]
+    fn sigv4_canonical_uri() {
+        let url = Url::parse("https://example.amazonaws.com/a b/%C3%A9/").unwrap();
+        assert_eq!(canonical_uri(&url, "service"), "/a%2520b/%25C3%25A9/");
+        assert_eq!(canonical_uri(&url, "s3"), "/a%20b/%C3%A9/");
+    }
+
+    // RFC 7616 section 3.9.1
+    fn rfc7616(algorithm: &str) -> String {
+        let mut challenge = DigestChallenge::parse(&format!(
+            "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+             algorithm={}, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+             opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"",
+            algorithm
+        ))
+        .unwrap();
+        challenge.authorization_with(
+            "Mufasa",
+            "Circle of Life",
+            "GET",
+            &Url::parse("http://www.example.org/dir/index.html").unwrap(),
+            "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ",
+        )
+    }
+
+    #[test]
+    fn digest_md5() {
+        let value = rfc7616("MD5");
+        assert!(value.contains("response=\"8ca523f5e9506fed4657c9700eebdbec\""));
+        assert!(value.contains("qop=auth, nc=00000001"));
+    }
+
+    #[test]
+    fn digest_sha256() {
+        let value = rfc7616("SHA-256");
+        assert!(value.contains(
+            "response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""
+        ));
+    }
+
+    #[test]
+    fn masked_keeps_only_public_fields() {
+        let auth = Auth::Basic {
+            username: "alice".to_string(),
+            password: "hunter2".to_string(),
+        };
+        let json = serde_json::to_string(&auth.masked()).unwrap();
+        assert!(json.contains("alice"));
+        assert!(!json.contains("hunter2"));
+
+        let auth = Auth::AwsSigV4 {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI".to_string(),
+            session_token: Some("session".to_string()),
+            region: "us-east-1".to_string(),
+            service: "s3".to_string(),
+        };
+        let Auth::AwsSigV4 {
+            access_key_id,
+            secret_access_key,
+            session_token,
+            region,
+            ..
+        } = auth.masked()
+        else {
+            unreachable!()
+        };
+        assert_eq!(access_key_id, "AKIDEXAMPLE");
+        assert_eq!(secret_access_key, SECRET_MASK);
+        assert_eq!(session_token.as_deref(), Some(SECRET_MASK));
+        assert_eq!(region, "us-east-1");
+    }
+}
@@ -0,0 +1,256 @@
+use crate::db::block_rules;
+use hudsucker::hyper::{Body, Response, StatusCode};
+use regex::Regex;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, ModelTrait, Set};
+use serde::Deserialize;
+use std::io;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub struct BlockRule {
+    pub id: String,
+    pub name: String,
+    pattern: Regex,
+    // Answer with `status`, or close the connection when None
+    status: Option<StatusCode>,
+    profile: Option<String>,
+}
+
+impl BlockRule {
+    fn applies_to(&self, profile: Option<&str>) -> bool {
+        self.profile.is_none() || self.profile.as_deref() == profile
+    }
+
+    /// Status stored for the blocked request, 0 when the connection is dropped.
+    pub fn status_code(&self) -> i32 {
+        self.status.map(|s| s.as_u16() as i32).unwrap_or(0)
+    }
+
+    pub fn response(&self) -> Response<Body> {
+        let Some(status) = self.status else {
+            // hyper closes the connection when the body fails
+            let body = Body::wrap_stream(futures::stream::once(async {
+                Err::<Vec<u8>, _>(io::Error::new(io::ErrorKind::ConnectionAborted, "blocked"))
+            }));
+            return Response::new(body);
+        };
+
+        Response::builder()
+            .status(status)
+            .header("content-type", "text/plain")
+            .body(Body::from(format!("Blocked by rule '{}'", self.name)))
+            .expect("Failed to build response")
+    }
+}
+
+impl TryFrom<block_rules::Model> for BlockRule {
+    type Error = String;
+
+    fn try_from(model: block_rules::Model) -> Result<Self, Self::Error> {
+        let pattern = Regex::new(&model.match_pattern).map_err(|e| e.to_string())?;
+        let status = match model.action.as_str() {
+            "drop" => None,
+            "status" => Some(
+                StatusCode::from_u16(model.status.unwrap_or(403) as u16)
+                    .map_err(|e| e.to_string())?,
+            ),
+            other => return Err(format!("Unknown block action: {}", other)),
+        };
+        Ok(Self {
+            id: model.id,
+            name: model.name,
+            pattern,
+            status,
+            profile: model.profile,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewBlockRule {
+    pub name: String,
+    pub match_pattern: String,
+    pub action: String,
+    pub status: Option<i32>,
+    pub profile: Option<String>,
+}
+
+pub struct BlockList {
+    rules: RwLock<Vec<BlockRule>>,
+    db: DatabaseConnection,
+}
+
+impl BlockList {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            rules: RwLock::new(Vec::new()),
+            db,
+        }
+    }
+
+    pub async fn load(&self) {
+        if let Ok(models) = block_rules::Entity::find().all(&self.db).await {
+            let mut rules = self.rules.write().unwrap();
+            *rules = models
+                .into_iter()
+                .filter(|m| m.enabled)
+                .filter_map(|m| BlockRule::try_from(m).ok())
+                .collect();
+            println!("Loaded {} block rules", rules.len());
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<block_rules::Model>, String> {
+        block_rules::Entity::find()
+            .all(&self.db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn add(&self, rule: NewBlockRule) -> Result<block_rules::Model, String> {
+        let model = block_rules::Model {
+            id: Uuid::new_v4().to_string(),
+            name: rule.name,
+            enabled: true,
+            match_pattern: rule.match_pattern,
+            action: rule.action,
+            status: rule.status,
+            profile: rule.profile,
+        };
+        // Validate before storing
+        BlockRule::try_from(model.clone())?;
+
+        let model = block_rules::ActiveModel {
+            id: Set(model.id),
+            name: Set(model.name),
+            enabled: Set(model.enabled),
+            match_pattern: Set(model.match_pattern),
+            action: Set(model.action),
+            status: Set(model.status),
+            profile: Set(model.profile),
+        }
+        .insert(&self.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        self.load().await;
+        Ok(model)
+    }
+
+    pub async fn remove(&self, id: String) -> Result<(), String> {
+        let model = block_rules::Entity::find_by_id(id.clone())
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Unknown block rule {}", id))?;
+        model.delete(&self.db).await.map_err(|e| e.to_string())?;
+
+        self.load().await;
+        Ok(())
+    }
+
+    /// First rule whose pattern matches `url`. CONNECT requests are matched
+    /// by their "host:port" target.
+    pub fn find(&self, url: &str, profile: Option<&str>) -> Option<BlockRule> {
+        let rules = self.rules.read().unwrap();
+        rules
+            .iter()
+            .find(|r| r.applies_to(profile) && r.pattern.is_match(url))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hudsucker::hyper;
+
+    fn model(pattern: &str, action: &str, status: Option<i32>) -> block_rules::Model {
+        block_rules::Model {
+            id: pattern.to_string(),
+            name: pattern.to_string(),
+            enabled: true,
+            match_pattern: pattern.to_string(),
+            action: action.to_string(),
+            status,
+            profile: None,
+        }
+    }
+
+    fn list(models: Vec<block_rules::Model>) -> BlockList {
+        let list = BlockList::new(DatabaseConnection::default());
+        *list.rules.write().unwrap() = models
+            .into_iter()
+            .map(|m| BlockRule::try_from(m).unwrap())
+            .collect();
+        list
+    }
+
+    #[test]
+    fn patterns_match_urls_and_connect_targets() {
+        let rules = list(vec![
+            model(r"^https?://ads\.example\.com/", "drop", None),
+            model(r"(?i)tracker", "status", Some(451)),
+            model(r"^[^/]*\.evil\.test:443$", "status", None),
+        ]);
+        let id = |url: &str| rules.find(url, None).map(|r| r.id);
+
+        assert!(id("https://ads.example.com/banner.js").is_some());
+        assert!(id("https://ads.example.com.other.org/").is_none());
+        assert!(id("http://cdn.example.com/TRACKER.js").is_some());
+        assert!(id("www.evil.test:443").is_some());
+        assert!(id("www.evil.test:8443").is_none());
+        assert!(id("https://example.com/").is_none());
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = list(vec![
+            model("example", "status", Some(404)),
+            model("example", "drop", None),
+        ]);
+        let rule = rules.find("https://example.com/", None).unwrap();
+        assert_eq!(rule.status_code(), 404);
+    }
+
+    #[test]
+    fn rules_scoped_to_a_profile() {
+        let mut scoped = model("example", "drop", None);
+        scoped.profile = Some("mobile".to_string());
+        let rules = list(vec![scoped]);
+
+        assert!(rules.find("https://example.com/", Some("mobile")).is_some());
+        assert!(rules
+            .find("https://example.com/", Some("desktop"))
+            .is_none());
+        assert!(rules.find("https://example.com/", None).is_none());
+    }
+
+    #[test]
+    fn invalid_rules_rejected() {
+        assert!(BlockRule::try_from(model("(", "drop", None)).is_err());
+        assert!(BlockRule::try_from(model("x", "redirect", None)).is_err());
+        assert!(BlockRule::try_from(model("x", "status", Some(1000))).is_err());
+    }
+
+    #[tokio::test]
+    async fn status_rules_answer() {
+        let rule = BlockRule::try_from(model("x", "status", None)).unwrap();
+        assert_eq!(rule.status_code(), 403);
+
+        let response = rule.response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"Blocked by rule 'x'");
+    }
+
+    #[tokio::test]
+    async fn drop_rules_abort_the_body() {
+        let rule = BlockRule::try_from(model("x", "drop", None)).unwrap();
+        assert_eq!(rule.status_code(), 0);
+        assert!(hyper::body::to_bytes(rule.response().into_body())
+            .await
+            .is_err());
+    }
+}
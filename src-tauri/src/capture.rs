@@ -0,0 +1,215 @@
+use bytes::Bytes;
+use futures::Stream;
+use hudsucker::hyper::{self, Body, HeaderMap};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// Default cutoff for how much of a single body we keep in the database
+pub const DEFAULT_MAX_CAPTURE_SIZE: usize = 10 * 1024 * 1024;
+
+/// What was kept of a body after it has fully passed through (or was cut off).
+#[derive(Debug, Default)]
+pub struct CapturedBody {
+    pub data: Vec<u8>,
+    pub total_size: u64,
+}
+
+impl CapturedBody {
+    /// Whether more went through than was kept.
+    pub fn truncated(&self) -> bool {
+        (self.data.len() as u64) < self.total_size
+    }
+
+    pub fn into_stored(self) -> Option<Vec<u8>> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(self.data)
+        }
+    }
+}
+
+type ChunkCallback = Box<dyn FnMut(&Bytes) + Send>;
+type CompleteCallback = Box<dyn FnOnce(CapturedBody) + Send>;
+
+/// Forwards a body chunk by chunk while keeping a bounded copy of it.
+///
+/// The completion callback runs once, either when the body ends or when the
+/// stream is dropped early (client hung up), so the stored row is always updated.
+pub struct TeeBody {
+    inner: Body,
+    limit: usize,
+    captured: CapturedBody,
+    on_chunk: Option<ChunkCallback>,
+    on_complete: Option<CompleteCallback>,
+}
+
+impl TeeBody {
+    pub fn new(inner: Body, limit: usize) -> Self {
+        Self {
+            inner,
+            limit,
+            captured: CapturedBody::default(),
+            on_chunk: None,
+            on_complete: None,
+        }
+    }
+
+    pub fn on_chunk(mut self, f: impl FnMut(&Bytes) + Send + 'static) -> Self {
+        self.on_chunk = Some(Box::new(f));
+        self
+    }
+
+    pub fn on_complete(mut self, f: impl FnOnce(CapturedBody) + Send + 'static) -> Self {
+        self.on_complete = Some(Box::new(f));
+        self
+    }
+
+    pub fn into_body(self) -> Body {
+        Body::wrap_stream(self)
+    }
+
+    fn finish(&mut self) {
+        if let Some(f) = self.on_complete.take() {
+            f(std::mem::take(&mut self.captured));
+        }
+    }
+}
+
+impl Stream for TeeBody {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.captured.total_size += chunk.len() as u64;
+                let room = this.limit.saturating_sub(this.captured.data.len());
+                if room > 0 {
+                    let take = room.min(chunk.len());
+                    this.captured.data.extend_from_slice(&chunk[..take]);
+                }
+                if let Some(f) = this.on_chunk.as_mut() {
+                    f(&chunk);
+                }
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready(None) => {
+                this.finish();
+                Poll::Ready(None)
+            }
+            other => other,
+        }
+    }
+}
+
+impl Drop for TeeBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+pub fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+}
+
+pub fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("text/event-stream"))
+        .unwrap_or(false)
+}
+
+/// Keep at most `limit` bytes of a fully buffered body for storage.
+pub fn truncate_for_storage(body: &[u8], limit: usize) -> Option<Vec<u8>> {
+    if body.is_empty() {
+        None
+    } else {
+        Some(body[..body.len().min(limit)].to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn body(chunks: &[&'static [u8]]) -> Body {
+        let chunks: Vec<Result<Bytes, std::io::Error>> =
+            chunks.iter().map(|c| Ok(Bytes::from_static(c))).collect();
+        Body::wrap_stream(futures::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn chunks_pass_through_unchanged() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let chunks = seen.clone();
+        let tee = TeeBody::new(body(&[b"hello ", b"", b"world"]), 100)
+            .on_chunk(move |chunk| chunks.lock().unwrap().push(chunk.clone()));
+
+        let forwarded = hyper::body::to_bytes(tee.into_body()).await.unwrap();
+        assert_eq!(&forwarded[..], b"hello world");
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.concat(), b"hello world");
+    }
+
+    #[tokio::test]
+    async fn capture_stops_at_the_limit() {
+        let captured = Arc::new(Mutex::new(None));
+        let result = captured.clone();
+        let tee = TeeBody::new(body(&[b"abcd", b"efgh"]), 6)
+            .on_complete(move |body| *result.lock().unwrap() = Some(body));
+
+        let forwarded = hyper::body::to_bytes(tee.into_body()).await.unwrap();
+        assert_eq!(&forwarded[..], b"abcdefgh");
+        let captured = captured.lock().unwrap().take().unwrap();
+        assert_eq!(captured.data, b"abcdef");
+        assert_eq!(captured.total_size, 8);
+        assert!(captured.truncated());
+    }
+
+    #[tokio::test]
+    async fn completes_once_at_the_end() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut tee = TeeBody::new(body(&[b"a", b"b"]), 10).on_complete(move |body| {
+            assert_eq!(body.data, b"ab");
+            assert!(!body.truncated());
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        while tee.next().await.is_some() {}
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        drop(tee);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn completes_once_when_dropped_early() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let mut tee = TeeBody::new(body(&[b"a", b"b"]), 10).on_complete(move |body| {
+            assert_eq!(body.data, b"a");
+            assert_eq!(body.total_size, 1);
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        tee.next().await.unwrap().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+        drop(tee);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn truncate_for_storage_limits() {
+        assert_eq!(truncate_for_storage(b"", 10), None);
+        assert_eq!(truncate_for_storage(b"abc", 10), Some(b"abc".to_vec()));
+        assert_eq!(truncate_for_storage(b"abcdef", 4), Some(b"abcd".to_vec()));
+    }
+}
@@ -1,7 +1,14 @@
-use anyhow::{Context, Result};
+use crate::hosts;
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hudsucker::rustls;
 use rcgen::{BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair};
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use uuid::Uuid;
 
 pub struct CaManager {
     pub cert_path: PathBuf,
@@ -45,7 +52,7 @@ impl CaManager {
         }
 
         fs::write(&cert_path, &cert_pem)?;
-        fs::write(&key_path, &key_pem)?;
+        write_private(&key_path, &key_pem)?;
 
         Ok(Self {
             cert_path,
@@ -58,4 +65,302 @@ impl CaManager {
     pub fn get_ca_pem(&self) -> String {
         self.cert_pem.clone()
     }
+
+    /// Leaf certificate for `host` with a deliberate defect, as a PEM chain
+    /// followed by the key. Only the requested defect makes it fail, the
+    /// other variants are signed by this CA.
+    pub fn generate_broken_cert(&self, host: &str, kind: BrokenCertKind) -> Result<String> {
+        let san = match kind {
+            BrokenCertKind::WrongHost => "wrong-host.invalid".to_string(),
+            _ => host.to_string(),
+        };
+        let mut params = CertificateParams::new(vec![san.clone()])?;
+        params.distinguished_name.push(DnType::CommonName, san);
+        if let BrokenCertKind::Expired = kind {
+            params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+            params.not_after = rcgen::date_time_ymd(2001, 1, 1);
+        }
+
+        let key_pair = KeyPair::generate()?;
+        let cert = match kind {
+            BrokenCertKind::SelfSigned => params.self_signed(&key_pair)?,
+            _ => {
+                let ca_key = KeyPair::from_pem(&self.key_pem)?;
+                let ca_cert =
+                    CertificateParams::from_ca_cert_pem(&self.cert_pem)?.self_signed(&ca_key)?;
+                params.signed_by(&key_pair, &ca_cert, &ca_key)?
+            }
+        };
+
+        Ok(format!("{}{}", cert.pem(), key_pair.serialize_pem()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrokenCertKind {
+    Expired,
+    WrongHost,
+    SelfSigned,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HostCert {
+    pub id: String,
+    pub name: String,
+    // "api.internal", "*.corp.example.com" or "*"
+    pub host_pattern: String,
+    pub subject: Option<String>,
+    pub not_after: Option<i64>,
+    pub added_at: i64,
+}
+
+/// How a certificate and its key are handed to us. PKCS#12 data is base64 encoded.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum CertSource {
+    Pem { cert: String, key: String },
+    Pkcs12 { data: String, password: String },
+}
+
+#[derive(Clone)]
+pub struct StoredCert {
+    pub info: HostCert,
+    // Certificate chain followed by the private key
+    pub pem: String,
+}
+
+impl StoredCert {
+    // Chain and key for rustls, as used by hudsucker and hyper-rustls
+    pub fn rustls_identity(&self) -> Result<(Vec<rustls::Certificate>, rustls::PrivateKey)> {
+        parse_identity(&self.pem)
+    }
+}
+
+/// Certificates with their keys assigned to host patterns, kept in a directory
+/// of the app data dir next to the CA. Used for upstream mTLS client
+/// certificates and for overriding the certificates the proxy presents.
+pub struct CertStore {
+    dir: PathBuf,
+    certs: RwLock<Vec<StoredCert>>,
+}
+
+impl CertStore {
+    pub fn empty(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            certs: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn load(dir: PathBuf) -> Result<Self> {
+        let index_path = dir.join("index.json");
+
+        let mut certs = Vec::new();
+        if index_path.exists() {
+            let index = fs::read_to_string(&index_path).context("Failed to read certificates")?;
+            let infos: Vec<HostCert> = serde_json::from_str(&index)?;
+            for info in infos {
+                match fs::read_to_string(dir.join(format!("{}.pem", info.id))) {
+                    Ok(pem) => certs.push(StoredCert { info, pem }),
+                    Err(e) => eprintln!("Skipping certificate {}: {}", info.name, e),
+                }
+            }
+        }
+
+        Ok(Self {
+            dir,
+            certs: RwLock::new(certs),
+        })
+    }
+
+    pub fn list(&self) -> Vec<HostCert> {
+        let certs = self.certs.read().unwrap();
+        certs.iter().map(|c| c.info.clone()).collect()
+    }
+
+    /// The most specific certificate registered for `host`.
+    pub fn find(&self, host: &str) -> Option<StoredCert> {
+        let certs = self.certs.read().unwrap();
+        certs
+            .iter()
+            .filter(|c| hosts::matches(&c.info.host_pattern, host))
+            .max_by_key(|c| c.info.host_pattern.len())
+            .cloned()
+    }
+
+    pub fn add(&self, name: String, host_pattern: String, source: CertSource) -> Result<HostCert> {
+        let pem = match source {
+            CertSource::Pem { cert, key } => format!("{}\n{}\n", cert.trim(), key.trim()),
+            CertSource::Pkcs12 { data, password } => {
+                let der = STANDARD.decode(data.trim())?;
+                pkcs12_to_pem(&der, &password)?
+            }
+        };
+        self.add_pem(name, host_pattern, pem)
+    }
+
+    /// Stores a PEM bundle holding the certificate chain and the private key.
+    pub fn add_pem(&self, name: String, host_pattern: String, pem: String) -> Result<HostCert> {
+        // Reject anything rustls can't use before storing it
+        let (chain, _) = parse_identity(&pem)?;
+        let leaf = crate::upstream::certificate_details(&chain[0].0);
+
+        let info = HostCert {
+            id: Uuid::new_v4().to_string(),
+            name,
+            host_pattern,
+            subject: leaf.as_ref().map(|c| c.subject.clone()),
+            not_after: leaf.as_ref().map(|c| c.not_after),
+            added_at: chrono::Utc::now().timestamp_millis(),
+        };
+
+        fs::create_dir_all(&self.dir)?;
+        write_private(&self.dir.join(format!("{}.pem", info.id)), &pem)?;
+
+        let mut certs = self.certs.write().unwrap();
+        certs.push(StoredCert {
+            info: info.clone(),
+            pem,
+        });
+        self.save_index(&certs)?;
+
+        Ok(info)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let mut certs = self.certs.write().unwrap();
+        let before = certs.len();
+        certs.retain(|c| c.info.id != id);
+        if certs.len() == before {
+            return Err(anyhow!("Unknown certificate {}", id));
+        }
+
+        self.save_index(&certs)?;
+        let _ = fs::remove_file(self.dir.join(format!("{}.pem", id)));
+        Ok(())
+    }
+
+    fn save_index(&self, certs: &[StoredCert]) -> Result<()> {
+        let infos: Vec<&HostCert> = certs.iter().map(|c| &c.info).collect();
+        fs::write(
+            self.dir.join("index.json"),
+            serde_json::to_string_pretty(&infos)?,
+        )?;
+        Ok(())
+    }
+}
+
+// Files holding private keys are only readable by the user
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+pub fn parse_identity(pem: &str) -> Result<(Vec<rustls::Certificate>, rustls::PrivateKey)> {
+    let mut chain = Vec::new();
+    let mut key = None;
+    for item in rustls_pemfile::read_all(&mut pem.as_bytes())? {
+        match item {
+            rustls_pemfile::Item::X509Certificate(der) => chain.push(rustls::Certificate(der)),
+            rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::ECKey(der) => key = key.or(Some(rustls::PrivateKey(der))),
+            _ => {}
+        }
+    }
+
+    if chain.is_empty() {
+        return Err(anyhow!("No certificate found"));
+    }
+    let key = key.ok_or_else(|| anyhow!("No private key found"))?;
+    Ok((chain, key))
+}
+
+fn pkcs12_to_pem(der: &[u8], password: &str) -> Result<String> {
+    let store = p12_keystore::KeyStore::from_pkcs12(der, password, Default::default())
+        .map_err(|e| anyhow!("Failed to read PKCS#12: {}", e))?;
+    let (_, chain) = store
+        .private_key_chain()
+        .ok_or_else(|| anyhow!("PKCS#12 contains no private key"))?;
+
+    let mut pem = String::new();
+    for cert in chain.certs() {
+        pem.push_str(&to_pem("CERTIFICATE", cert.as_der()));
+    }
+    pem.push_str(&to_pem("PRIVATE KEY", chain.key().as_der()));
+    Ok(pem)
+}
+
+fn to_pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(patterns: &[&str]) -> CertStore {
+        let store = CertStore::empty(PathBuf::new());
+        *store.certs.write().unwrap() = patterns
+            .iter()
+            .map(|pattern| StoredCert {
+                info: HostCert {
+                    id: pattern.to_string(),
+                    name: pattern.to_string(),
+                    host_pattern: pattern.to_string(),
+                    subject: None,
+                    not_after: None,
+                    added_at: 0,
+                },
+                pem: String::new(),
+            })
+            .collect();
+        store
+    }
+
+    fn found(store: &CertStore, host: &str) -> Option<String> {
+        store.find(host).map(|c| c.info.host_pattern)
+    }
+
+    #[test]
+    fn most_specific_certificate_wins() {
+        let store = store(&["*", "*.example.com", "api.example.com", "*.api.example.com"]);
+        assert_eq!(
+            found(&store, "api.example.com").as_deref(),
+            Some("api.example.com")
+        );
+        assert_eq!(
+            found(&store, "v1.api.example.com").as_deref(),
+            Some("*.api.example.com")
+        );
+        assert_eq!(
+            found(&store, "www.example.com").as_deref(),
+            Some("*.example.com")
+        );
+        assert_eq!(found(&store, "example.org").as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn certificates_selected_by_case_and_port_free_host() {
+        let store = store(&["Api.Example.com"]);
+        assert!(found(&store, "api.example.com:443").is_some());
+        assert!(found(&store, "API.EXAMPLE.COM").is_some());
+        assert!(found(&store, "www.example.com").is_none());
+    }
 }
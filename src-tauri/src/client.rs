@@ -1,36 +1,496 @@
+use crate::auth::{Auth, DigestChallenge};
+use crate::capture;
+use crate::certs::CertStore;
+use crate::cookies::CookieJar;
+use crate::db::requests;
+use crate::dns::{DnsOverrides, OverrideResolver};
+use crate::environments::Variables;
+use crate::{AppState, ProxyEventPayload};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use reqwest::{
-    header::{HeaderMap, HeaderName, HeaderValue},
-    Client, Method,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    multipart, redirect, Client, Identity, Method, RequestBuilder, StatusCode, Url,
 };
+use sea_orm::{ActiveModelTrait, Set};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
 use std::str::FromStr;
-use std::time::Instant;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    // HTTP/2 when the server offers it through ALPN, HTTP/1.1 otherwise
+    #[default]
+    Auto,
+    Http1,
+    // Only HTTP/2, over TLS it must be negotiated through ALPN
+    Http2,
+    // HTTP/2 over plain TCP without upgrade (prior knowledge)
+    H2c,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClientOptions {
+    pub http_version: HttpVersion,
+    // Off by default, dev servers often use self-signed certificates
+    pub verify_tls: bool,
+    pub follow_redirects: bool,
+    pub max_redirects: usize,
+    pub connect_timeout_ms: Option<u64>,
+    // Whole exchange including redirects and reading the body
+    pub timeout_ms: Option<u64>,
+    // Keep idle connections open for later requests with the same options
+    pub reuse_connections: bool,
+    // Send cookies from the shared jar and store the ones servers set
+    pub use_cookie_jar: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            http_version: HttpVersion::Auto,
+            verify_tls: false,
+            follow_redirects: true,
+            max_redirects: 10,
+            connect_timeout_ms: None,
+            timeout_ms: None,
+            reuse_connections: true,
+            use_cookie_jar: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RequestBody {
+    Text { content: String },
+    Base64 { data: String },
+    // Read from disk when the request is sent
+    File { path: String },
+    // application/x-www-form-urlencoded
+    Form { fields: Vec<FormField> },
+    Multipart { parts: Vec<MultipartPart> },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FormField {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MultipartPart {
+    pub name: String,
+    // Either a text value or a file to upload
+    pub value: Option<String>,
+    pub file_path: Option<String>,
+    // Defaults to the file's name
+    pub file_name: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl RequestBody {
+    async fn apply(&self, builder: RequestBuilder) -> Result<RequestBuilder, String> {
+        Ok(match self {
+            RequestBody::Text { content } => builder.body(content.clone()),
+            RequestBody::Base64 { data } => {
+                builder.body(STANDARD.decode(data.trim()).map_err(|e| e.to_string())?)
+            }
+            RequestBody::File { path } => builder.body(read_file(path).await?),
+            RequestBody::Form { fields } => {
+                let pairs: Vec<(&str, &str)> = fields
+                    .iter()
+                    .map(|f| (f.name.as_str(), f.value.as_str()))
+                    .collect();
+                builder.form(&pairs)
+            }
+            RequestBody::Multipart { parts } => {
+                let mut form = multipart::Form::new();
+                for part in parts {
+                    form = form.part(part.name.clone(), part.to_part().await?);
+                }
+                builder.multipart(form)
+            }
+        })
+    }
+}
+
+impl MultipartPart {
+    async fn to_part(&self) -> Result<multipart::Part, String> {
+        let mut part = match (&self.file_path, &self.value) {
+            (Some(path), _) => {
+                let file_name = self.file_name.clone().or_else(|| {
+                    Path::new(path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                });
+                let mut part = multipart::Part::bytes(read_file(path).await?);
+                if let Some(file_name) = file_name {
+                    part = part.file_name(file_name);
+                }
+                part
+            }
+            (None, Some(value)) => multipart::Part::text(value.clone()),
+            (None, None) => {
+                return Err(format!(
+                    "Multipart field '{}' needs a value or a file",
+                    self.name
+                ))
+            }
+        };
+        if let Some(content_type) = &self.content_type {
+            part = part.mime_str(content_type).map_err(|e| e.to_string())?;
+        }
+        Ok(part)
+    }
+}
+
+async fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ClientRequest {
     pub method: String,
     pub url: String,
     pub headers: std::collections::HashMap<String, String>,
-    pub body: Option<String>, // Base64 or Text? For MVP text/json.
+    pub body: Option<RequestBody>,
+    #[serde(default)]
+    pub options: ClientOptions,
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    // Variable scopes for {{name}} placeholders, globals always apply
+    #[serde(default)]
+    pub collection_id: Option<String>,
+    #[serde(default)]
+    pub environment_id: Option<String>,
+}
+
+impl ClientRequest {
+    /// Rebuilds a captured request for export, without the headers that the
+    /// HTTP stack sets itself.
+    pub fn from_record(record: &requests::Model) -> Self {
+        let headers: HashMap<String, String> =
+            serde_json::from_str(&record.request_headers).unwrap_or_default();
+        let host = Url::parse(&record.url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_ascii_lowercase()));
+
+        let headers = headers
+            .into_iter()
+            .filter(|(name, value)| {
+                let name = name.to_ascii_lowercase();
+                !name.starts_with(':')
+                    && name != "content-length"
+                    && name != "transfer-encoding"
+                    && !(name == "host"
+                        && host
+                            .as_deref()
+                            .is_some_and(|h| value.to_ascii_lowercase().starts_with(h)))
+            })
+            .collect();
+
+        let body =
+            record
+                .request_body
+                .as_ref()
+                .map(|bytes| match String::from_utf8(bytes.clone()) {
+                    Ok(content) => RequestBody::Text { content },
+                    Err(_) => RequestBody::Base64 {
+                        data: STANDARD.encode(bytes),
+                    },
+                });
+
+        ClientRequest {
+            method: record.method.clone(),
+            url: record.url.clone(),
+            headers,
+            body,
+            // Replays what was seen, a single exchange with checked certificates
+            options: ClientOptions {
+                verify_tls: true,
+                follow_redirects: false,
+                ..Default::default()
+            },
+            auth: None,
+            collection_id: None,
+            environment_id: None,
+        }
+    }
+}
+
+/// Whether the stored request body is shorter than what was sent.
+pub fn record_body_truncated(record: &requests::Model) -> bool {
+    match (&record.request_body, record.request_body_size) {
+        (Some(body), Some(size)) => (body.len() as i64) < size,
+        (None, Some(size)) => size > 0,
+        _ => false,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RedirectHop {
+    pub url: String,
+    pub status: u16,
+    pub location: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ClientResponse {
     pub status: u16,
     pub headers: std::collections::HashMap<String, String>,
-    pub body: String, // Text content
+    pub body: String, // Base64 of the raw bytes
+    // Set when the body is valid UTF-8
+    pub body_text: Option<String>,
+    // From Content-Type, or guessed from the bytes when missing
+    pub content_type: Option<String>,
+    pub size: usize,
     pub duration_ms: u64,
+    // URL of the final response, differs from the request after redirects
+    pub url: String,
+    pub http_version: String,
+    pub redirects: Vec<RedirectHop>,
+    // Row in the shared requests history
+    pub request_id: String,
+    #[serde(skip)]
+    raw_body: Vec<u8>,
+}
+
+// Everything a reqwest::Client is built from
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ClientKey {
+    http_version: HttpVersion,
+    verify_tls: bool,
+    connect_timeout_ms: Option<u64>,
+    reuse_connections: bool,
+    client_cert: Option<String>,
+}
+
+/// reqwest clients shared between API client requests, one per distinct set
+/// of connection options, so connections can be reused.
+pub struct ClientPool {
+    clients: Mutex<HashMap<ClientKey, Client>>,
+    dns: Arc<DnsOverrides>,
+    client_certs: Arc<CertStore>,
+}
+
+impl ClientPool {
+    pub fn new(dns: Arc<DnsOverrides>, client_certs: Arc<CertStore>) -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            dns,
+            client_certs,
+        }
+    }
+
+    pub(crate) fn get(&self, options: &ClientOptions, url: &Url) -> Result<Client, String> {
+        if options.http_version == HttpVersion::H2c && url.scheme() != "http" {
+            return Err("h2c is only possible with http:// URLs".to_string());
+        }
+
+        let cert = url.host_str().and_then(|host| self.client_certs.find(host));
+        let key = ClientKey {
+            http_version: options.http_version,
+            verify_tls: options.verify_tls,
+            connect_timeout_ms: options.connect_timeout_ms,
+            reuse_connections: options.reuse_connections,
+            client_cert: cert.as_ref().map(|c| c.info.id.clone()),
+        };
+
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+
+        // Redirects are followed by send_request so every hop is recorded
+        let mut builder = Client::builder()
+            .danger_accept_invalid_certs(!options.verify_tls)
+            .redirect(redirect::Policy::none())
+            .dns_resolver(Arc::new(OverrideResolver::new(self.dns.clone())));
+
+        builder = match options.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 | HttpVersion::H2c => builder.http2_prior_knowledge(),
+        };
+        if let Some(ms) = options.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(ms));
+        }
+        if !options.reuse_connections {
+            builder = builder.pool_max_idle_per_host(0);
+        }
+        // Present the client certificate registered for this host, if any
+        if let Some(cert) = cert {
+            let identity = Identity::from_pem(cert.pem.as_bytes()).map_err(|e| e.to_string())?;
+            builder = builder.identity(identity);
+        }
+
+        let client = builder.build().map_err(|e| e.to_string())?;
+        if options.reuse_connections {
+            clients.insert(key, client.clone());
+        }
+        Ok(client)
+    }
+}
+
+/// Interpolates variables and swaps OAuth 2.0 for its bearer token, giving
+/// the request exactly as it will be sent.
+pub async fn resolve_request(
+    state: &AppState,
+    req: ClientRequest,
+) -> Result<(ClientRequest, Variables), String> {
+    let mut variables = Variables::resolve(
+        &state.db,
+        req.collection_id.as_deref(),
+        req.environment_id.as_deref(),
+    )
+    .await?;
+    let mut req = variables.apply(req)?;
+    req.auth = state
+        .oauth
+        .resolve(req.auth.take(), req.environment_id.as_deref())
+        .await?;
+    // Credentials typed in directly and fetched tokens are masked like secrets
+    for secret in req.auth.iter().flat_map(Auth::secrets) {
+        variables.add_secret(secret);
+    }
+    Ok((req, variables))
+}
+
+/// Interpolates variables for exports served over the REST API. Secret
+/// variables and auth credentials stay placeholders and OAuth 2.0 is not
+/// exchanged for a token.
+pub async fn resolve_for_export(
+    state: &AppState,
+    req: ClientRequest,
+) -> Result<ClientRequest, String> {
+    let mut req = Variables::resolve(
+        &state.db,
+        req.collection_id.as_deref(),
+        req.environment_id.as_deref(),
+    )
+    .await?
+    .without_secrets()
+    .apply(req)?;
+    req.auth = req.auth.map(Auth::with_placeholders);
+    Ok(req)
 }
 
 #[tauri::command]
-pub async fn send_request(req: ClientRequest) -> Result<ClientResponse, String> {
-    let client = Client::builder()
-        .danger_accept_invalid_certs(true) // For dev tools usually expected
-        .http2_prior_knowledge() // Optional, maybe auto?
-        .build()
-        .map_err(|e| e.to_string())?;
+pub async fn send_request(
+    state: State<'_, Arc<AppState>>,
+    req: ClientRequest,
+) -> Result<ClientResponse, String> {
+    let (req, variables) = resolve_request(&state, req).await?;
 
-    let method = Method::from_str(&req.method).map_err(|e| e.to_string())?;
+    let id = Uuid::new_v4().to_string();
+    let method = req.method.clone();
+    // Secrets don't end up in events or the history
+    let url = variables.mask(&req.url);
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    let start = Instant::now();
+
+    let _ = state.proxy_event_tx.send(ProxyEventPayload {
+        id: id.clone(),
+        method: method.clone(),
+        url: url.clone(),
+        status: None,
+        phase: "request".to_string(),
+        ..Default::default()
+    });
+
+    let timeout = req.options.timeout_ms.map(Duration::from_millis);
+    let mut sent = SentRequest::default();
+    let exchange = send(&state.http_clients, &state.cookies, req, &mut sent);
+    let result = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, exchange)
+            .await
+            .unwrap_or_else(|_| {
+                Err(format!(
+                    "Request timed out after {} ms",
+                    timeout.as_millis()
+                ))
+            }),
+        None => exchange.await,
+    };
+    let duration = start.elapsed().as_millis() as u64;
+
+    // Failed sends are kept too, with status 0 and the error as body
+    let (status, response_headers, response_body) = match &result {
+        Ok(res) => (
+            res.status as i32,
+            serde_json::to_string(&res.headers).unwrap_or_default(),
+            res.raw_body.clone(),
+        ),
+        Err(e) => (0, "".to_string(), variables.mask(e).into_bytes()),
+    };
+    let request_body_size = sent.body.as_ref().map(|b| b.len() as i64);
+    let request_body = sent.body.map(|b| variables.mask_bytes(b));
+    let record = requests::ActiveModel {
+        id: Set(id.clone()),
+        method: Set(method.clone()),
+        url: Set(url.clone()),
+        protocol: Set("http".to_string()),
+        request_headers: Set(variables.mask(&sent.headers)),
+        request_body_size: Set(request_body_size),
+        request_body: Set(request_body
+            .and_then(|b| capture::truncate_for_storage(&b, capture::DEFAULT_MAX_CAPTURE_SIZE))),
+        response_status: Set(status),
+        response_headers: Set(response_headers),
+        response_body_size: Set(Some(response_body.len() as i64)),
+        response_body: Set(capture::truncate_for_storage(
+            &response_body,
+            capture::DEFAULT_MAX_CAPTURE_SIZE,
+        )),
+        duration: Set(duration as i64),
+        timestamp: Set(timestamp),
+        source: Set(Some("client".to_string())),
+        ..Default::default()
+    };
+    if let Err(e) = record.insert(&state.db).await {
+        eprintln!("Failed to store client request: {}", e);
+    }
+
+    let _ = state.proxy_event_tx.send(ProxyEventPayload {
+        id: id.clone(),
+        method,
+        url,
+        status: Some(status),
+        phase: "response".to_string(),
+        ..Default::default()
+    });
+
+    let mut response = result?;
+    response.request_id = id;
+    response.duration_ms = duration;
+    Ok(response)
+}
+
+// What actually went out for the first hop, for the history
+#[derive(Default)]
+struct SentRequest {
+    headers: String, // JSON
+    // None for streamed bodies such as multipart
+    body: Option<Vec<u8>>,
+}
+
+async fn send(
+    pool: &ClientPool,
+    jar: &CookieJar,
+    req: ClientRequest,
+    sent: &mut SentRequest,
+) -> Result<ClientResponse, String> {
+    let mut method = Method::from_str(&req.method).map_err(|e| e.to_string())?;
+    let mut url = Url::parse(&req.url).map_err(|e| e.to_string())?;
+    let mut body = req.body;
 
     let mut headers = HeaderMap::new();
     for (k, v) in req.headers {
@@ -39,18 +499,113 @@ pub async fn send_request(req: ClientRequest) -> Result<ClientResponse, String>
         }
     }
 
-    let start = Instant::now();
+    if let Some(auth) = &req.auth {
+        auth.prepare(&mut headers, &mut url)?;
+    }
+    // Signed and digest credentials only go to the origin they were meant for
+    let auth_origin = url.origin();
+    let mut digest: Option<DigestChallenge> = None;
 
-    let mut request_builder = client.request(method, &req.url).headers(headers);
+    let mut redirects = Vec::new();
+    let response = loop {
+        let client = pool.get(&req.options, &url)?;
+        let mut request_builder = client
+            .request(method.clone(), url.clone())
+            .headers(headers.clone());
+        if let Some(body) = &body {
+            request_builder = body.apply(request_builder).await?;
+        }
+        // A Cookie header set by hand replaces the jar
+        if req.options.use_cookie_jar && !headers.contains_key(header::COOKIE) {
+            if let Some(cookies) = jar.header_for(&url) {
+                request_builder = request_builder.header(header::COOKIE, cookies);
+            }
+        }
 
-    if let Some(body) = req.body {
-        request_builder = request_builder.body(body);
-    }
+        let mut request = request_builder.build().map_err(|e| e.to_string())?;
+        if let Some(auth) = req.auth.as_ref().filter(|_| url.origin() == auth_origin) {
+            auth.sign(&mut request, digest.as_mut())?;
+        }
+        if redirects.is_empty() {
+            let headers_map: HashMap<String, String> = request
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+                .collect();
+            sent.headers = serde_json::to_string(&headers_map).unwrap_or_default();
+            sent.body = request
+                .body()
+                .and_then(|b| b.as_bytes())
+                .map(|b| b.to_vec());
+        }
 
-    let response = request_builder.send().await.map_err(|e| e.to_string())?;
+        let response = client.execute(request).await.map_err(|e| e.to_string())?;
+        if req.options.use_cookie_jar {
+            jar.store_response(&url, response.headers()).await;
+        }
+
+        // Answer the first Digest challenge by sending the request again
+        if response.status() == StatusCode::UNAUTHORIZED
+            && digest.is_none()
+            && url.origin() == auth_origin
+            && req.auth.as_ref().is_some_and(Auth::is_digest)
+        {
+            if let Some(challenge) = DigestChallenge::from_headers(response.headers()) {
+                digest = Some(challenge);
+                continue;
+            }
+        }
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        let (Some(location), true) = (
+            location,
+            req.options.follow_redirects && response.status().is_redirection(),
+        ) else {
+            break response;
+        };
+        if redirects.len() >= req.options.max_redirects {
+            return Err(format!(
+                "Too many redirects (max {})",
+                req.options.max_redirects
+            ));
+        }
+
+        let next = url.join(&location).map_err(|e| e.to_string())?;
+        redirects.push(RedirectHop {
+            url: url.to_string(),
+            status: response.status().as_u16(),
+            location,
+        });
+
+        // Like browsers: 303 always, and 301/302 after POST, continue as GET
+        let status = response.status();
+        if status == StatusCode::SEE_OTHER
+            || (method == Method::POST
+                && (status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::FOUND))
+        {
+            method = Method::GET;
+            body = None;
+            headers.remove(header::CONTENT_TYPE);
+            headers.remove(header::CONTENT_LENGTH);
+        }
+        // Don't hand credentials to another origin
+        if next.origin() != url.origin() {
+            headers.remove(header::AUTHORIZATION);
+            headers.remove(header::COOKIE);
+            if let Some(auth) = &req.auth {
+                auth.strip(&mut headers);
+            }
+        }
+        url = next;
+    };
 
-    let duration = start.elapsed().as_millis() as u64;
     let status = response.status().as_u16();
+    let final_url = response.url().to_string();
+    let http_version = format!("{:?}", response.version());
 
     let res_headers: std::collections::HashMap<String, String> = response
         .headers()
@@ -58,13 +613,66 @@ pub async fn send_request(req: ClientRequest) -> Result<ClientResponse, String>
         .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
         .collect();
 
-    // Read body (text)
-    let body_text = response.text().await.map_err(|e| e.to_string())?;
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+    let content_type = content_type.or_else(|| sniff_content_type(&bytes).map(String::from));
 
     Ok(ClientResponse {
         status,
         headers: res_headers,
-        body: body_text,
-        duration_ms: duration,
+        body: STANDARD.encode(&bytes),
+        body_text: String::from_utf8(bytes.to_vec()).ok(),
+        content_type,
+        size: bytes.len(),
+        duration_ms: 0,
+        url: final_url,
+        http_version,
+        redirects,
+        request_id: String::new(),
+        raw_body: bytes.to_vec(),
     })
 }
+
+// Recognizes common formats by their magic bytes
+fn sniff_content_type(bytes: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x00asm", "application/wasm"),
+    ];
+
+    if bytes.is_empty() {
+        return None;
+    }
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+        return Some(mime);
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return Some("image/webp");
+    }
+
+    let text = std::str::from_utf8(bytes).ok()?;
+    let trimmed = text.trim_start();
+    if serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        Some("application/json")
+    } else if trimmed.starts_with('<') {
+        if trimmed.to_ascii_lowercase().starts_with("<!doctype html")
+            || trimmed.to_ascii_lowercase().starts_with("<html")
+        {
+            Some("text/html")
+        } else {
+            Some("application/xml")
+        }
+    } else {
+        Some("text/plain")
+    }
+}
@@ -0,0 +1,1081 @@
+use crate::auth::{ApiKeyLocation, Auth};
+use crate::client::{self, ClientOptions, ClientRequest, RequestBody};
+use crate::curl::{self, shell_quote};
+use crate::db::requests;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    Curl,
+    RustReqwest,
+    PythonRequests,
+    JavascriptFetch,
+    GoNetHttp,
+    Httpie,
+    Wget,
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(serde_json::Value::String(s.to_string()))
+            .map_err(|_| format!("Unknown code target '{}'", s))
+    }
+}
+
+/// Generates code sending the request. Variables and OAuth tokens should be
+/// resolved beforehand.
+pub fn generate(req: &ClientRequest, target: Target) -> String {
+    let prepared = Prepared::new(req);
+    match target {
+        Target::Curl => curl::to_curl(req),
+        Target::RustReqwest => rust_reqwest(&prepared),
+        Target::PythonRequests => python_requests(&prepared),
+        Target::JavascriptFetch => javascript_fetch(&prepared),
+        Target::GoNetHttp => go_net_http(&prepared),
+        Target::Httpie => httpie(&prepared),
+        Target::Wget => wget(&prepared),
+    }
+}
+
+/// Generates code replaying a captured request.
+pub fn generate_for_record(record: &requests::Model, target: Target) -> String {
+    let code = generate(&ClientRequest::from_record(record), target);
+    if !client::record_body_truncated(record) {
+        return code;
+    }
+    let comment = match target {
+        Target::RustReqwest | Target::JavascriptFetch | Target::GoNetHttp => "//",
+        _ => "#",
+    };
+    format!(
+        "{} The captured body was cut at the capture limit, this sends only the stored part\n{}",
+        comment, code
+    )
+}
+
+// The request with auth turned into headers where every target does the same
+struct Prepared<'a> {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    basic: Option<(&'a str, &'a str)>,
+    digest: Option<(&'a str, &'a str)>,
+    // Schemes a target may not support, explained in a comment
+    unsupported_auth: Option<&'static str>,
+    body: Option<&'a RequestBody>,
+    options: &'a ClientOptions,
+}
+
+impl<'a> Prepared<'a> {
+    fn new(req: &'a ClientRequest) -> Self {
+        let mut headers: Vec<(String, String)> = req
+            .headers
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        headers.sort();
+
+        let mut url = req.url.clone();
+        let mut basic = None;
+        let mut digest = None;
+        let mut unsupported_auth = None;
+        match &req.auth {
+            Some(Auth::Basic { username, password }) => {
+                basic = Some((username.as_str(), password.as_str()))
+            }
+            Some(Auth::Digest { username, password }) => {
+                digest = Some((username.as_str(), password.as_str()))
+            }
+            Some(Auth::Bearer { token }) => {
+                headers.push(("Authorization".to_string(), format!("Bearer {}", token)))
+            }
+            Some(Auth::ApiKey {
+                key,
+                value,
+                location: ApiKeyLocation::Header,
+            }) => headers.push((key.clone(), value.clone())),
+            Some(Auth::ApiKey {
+                key,
+                value,
+                location: ApiKeyLocation::Query,
+            }) => {
+                if let Ok(mut parsed) = Url::parse(&url) {
+                    parsed.query_pairs_mut().append_pair(key, value);
+                    url = parsed.to_string();
+                }
+            }
+            Some(Auth::AwsSigV4 { .. }) => {
+                unsupported_auth =
+                    Some("AWS SigV4 signing is not generated, sign the request with the AWS SDK")
+            }
+            Some(Auth::OAuth2(_)) => headers.push((
+                "Authorization".to_string(),
+                "Bearer <access token>".to_string(),
+            )),
+            None => {}
+        }
+
+        Prepared {
+            method: req.method.to_uppercase(),
+            url,
+            headers,
+            basic,
+            digest,
+            unsupported_auth,
+            body: req.body.as_ref(),
+            options: &req.options,
+        }
+    }
+
+    fn timeout_secs(&self) -> Option<f64> {
+        self.options.timeout_ms.map(|ms| ms as f64 / 1000.0)
+    }
+
+    fn decoded_base64(data: &str) -> Vec<u8> {
+        STANDARD.decode(data.trim()).unwrap_or_default()
+    }
+}
+
+// A double quoted string literal, valid in Rust, Python, JavaScript and Go
+fn quoted(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\x{:02x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Backslashes and quotes escaped for a quoted-string in a MIME header, as
+// Go's mime/multipart does for CreateFormFile
+fn mime_quoted(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn file_name(path: &str) -> String {
+    std::path::Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+// Rust, reqwest with tokio
+
+fn rust_reqwest(req: &Prepared) -> String {
+    let mut code = String::new();
+    code.push_str("#[tokio::main]\nasync fn main() -> Result<(), Box<dyn std::error::Error>> {\n");
+    code.push_str("    let client = reqwest::Client::builder()\n");
+    if !req.options.verify_tls {
+        code.push_str("        .danger_accept_invalid_certs(true)\n");
+    }
+    if req.options.follow_redirects {
+        let _ = writeln!(
+            code,
+            "        .redirect(reqwest::redirect::Policy::limited({}))",
+            req.options.max_redirects
+        );
+    } else {
+        code.push_str("        .redirect(reqwest::redirect::Policy::none())\n");
+    }
+    if let Some(ms) = req.options.timeout_ms {
+        let _ = writeln!(
+            code,
+            "        .timeout(std::time::Duration::from_millis({}))",
+            ms
+        );
+    }
+    code.push_str("        .build()?;\n\n");
+
+    if let Some(note) = req.unsupported_auth {
+        let _ = writeln!(code, "    // {}", note);
+    }
+    if req.digest.is_some() {
+        code.push_str("    // reqwest has no Digest auth, answer the 401 challenge with a crate such as diqwest\n");
+    }
+
+    let method = match req.method.as_str() {
+        "GET" | "POST" | "PUT" | "DELETE" | "HEAD" | "OPTIONS" | "PATCH" => {
+            format!("reqwest::Method::{}", req.method)
+        }
+        other => format!("reqwest::Method::from_bytes(b{})?", quoted(other)),
+    };
+    let _ = writeln!(code, "    let response = client");
+    let _ = writeln!(code, "        .request({}, {})", method, quoted(&req.url));
+    for (name, value) in &req.headers {
+        let _ = writeln!(code, "        .header({}, {})", quoted(name), quoted(value));
+    }
+    if let Some((username, password)) = req.basic {
+        let _ = writeln!(
+            code,
+            "        .basic_auth({}, Some({}))",
+            quoted(username),
+            quoted(password)
+        );
+    }
+    match req.body {
+        Some(RequestBody::Text { content }) => {
+            let _ = writeln!(code, "        .body({})", quoted(content));
+        }
+        Some(RequestBody::Base64 { data }) => {
+            let bytes: Vec<String> = Prepared::decoded_base64(data)
+                .iter()
+                .map(|b| format!("0x{:02x}", b))
+                .collect();
+            let _ = writeln!(code, "        .body(vec![{}])", bytes.join(", "));
+        }
+        Some(RequestBody::File { path }) => {
+            let _ = writeln!(code, "        .body(std::fs::read({})?)", quoted(path));
+        }
+        Some(RequestBody::Form { fields }) => {
+            let pairs: Vec<String> = fields
+                .iter()
+                .map(|f| format!("({}, {})", quoted(&f.name), quoted(&f.value)))
+                .collect();
+            let _ = writeln!(code, "        .form(&[{}])", pairs.join(", "));
+        }
+        Some(RequestBody::Multipart { parts }) => {
+            code.push_str("        .multipart(\n            reqwest::multipart::Form::new()\n");
+            for part in parts {
+                match (&part.file_path, &part.value) {
+                    (Some(path), _) => {
+                        let mut expr = format!(
+                            "reqwest::multipart::Part::bytes(std::fs::read({})?).file_name({})",
+                            quoted(path),
+                            quoted(&part.file_name.clone().unwrap_or_else(|| file_name(path)))
+                        );
+                        if let Some(content_type) = &part.content_type {
+                            let _ = write!(expr, ".mime_str({})?", quoted(content_type));
+                        }
+                        let _ = writeln!(
+                            code,
+                            "                .part({}, {})",
+                            quoted(&part.name),
+                            expr
+                        );
+                    }
+                    (None, value) => {
+                        let _ = writeln!(
+                            code,
+                            "                .text({}, {})",
+                            quoted(&part.name),
+                            quoted(value.as_deref().unwrap_or_default())
+                        );
+                    }
+                }
+            }
+            code.push_str("        )\n");
+        }
+        None => {}
+    }
+    code.push_str("        .send()\n        .await?;\n\n");
+    code.push_str("    println!(\"{}\", response.status());\n");
+    code.push_str("    println!(\"{}\", response.text().await?);\n");
+    code.push_str("    Ok(())\n}\n");
+    code
+}
+
+// Python, requests
+
+fn python_requests(req: &Prepared) -> String {
+    let mut imports = vec!["import requests".to_string()];
+    let mut setup = String::new();
+    let mut args = vec![quoted(&req.method), quoted(&req.url)];
+
+    if !req.headers.is_empty() {
+        let headers: Vec<String> = req
+            .headers
+            .iter()
+            .map(|(k, v)| format!("        {}: {},", quoted(k), quoted(v)))
+            .collect();
+        args.push(format!("headers={{\n{}\n    }}", headers.join("\n")));
+    }
+    if let Some((username, password)) = req.basic {
+        args.push(format!("auth=({}, {})", quoted(username), quoted(password)));
+    }
+    if let Some((username, password)) = req.digest {
+        imports.push("from requests.auth import HTTPDigestAuth".to_string());
+        args.push(format!(
+            "auth=HTTPDigestAuth({}, {})",
+            quoted(username),
+            quoted(password)
+        ));
+    }
+
+    match req.body {
+        Some(RequestBody::Text { content }) => args.push(format!("data={}", quoted(content))),
+        Some(RequestBody::Base64 { data }) => {
+            imports.push("import base64".to_string());
+            args.push(format!("data=base64.b64decode({})", quoted(data.trim())));
+        }
+        Some(RequestBody::File { path }) => {
+            let _ = writeln!(setup, "body = open({}, \"rb\")", quoted(path));
+            args.push("data=body".to_string());
+        }
+        Some(RequestBody::Form { fields }) => {
+            // A list keeps repeated names
+            let pairs: Vec<String> = fields
+                .iter()
+                .map(|f| format!("({}, {})", quoted(&f.name), quoted(&f.value)))
+                .collect();
+            args.push(format!("data=[{}]", pairs.join(", ")));
+        }
+        Some(RequestBody::Multipart { parts }) => {
+            let files: Vec<String> = parts
+                .iter()
+                .map(|part| match (&part.file_path, &part.value) {
+                    (Some(path), _) => {
+                        let name = part.file_name.clone().unwrap_or_else(|| file_name(path));
+                        match &part.content_type {
+                            Some(content_type) => format!(
+                                "        ({}, ({}, open({}, \"rb\"), {})),",
+                                quoted(&part.name),
+                                quoted(&name),
+                                quoted(path),
+                                quoted(content_type)
+                            ),
+                            None => format!(
+                                "        ({}, ({}, open({}, \"rb\"))),",
+                                quoted(&part.name),
+                                quoted(&name),
+                                quoted(path)
+                            ),
+                        }
+                    }
+                    // A None file name makes requests send a plain form field
+                    (None, value) => format!(
+                        "        ({}, (None, {})),",
+                        quoted(&part.name),
+                        quoted(value.as_deref().unwrap_or_default())
+                    ),
+                })
+                .collect();
+            args.push(format!("files=[\n{}\n    ]", files.join("\n")));
+        }
+        None => {}
+    }
+
+    if !req.options.verify_tls {
+        args.push("verify=False".to_string());
+    }
+    args.push(format!(
+        "allow_redirects={}",
+        if req.options.follow_redirects {
+            "True"
+        } else {
+            "False"
+        }
+    ));
+    if let Some(timeout) = req.timeout_secs() {
+        args.push(format!("timeout={}", timeout));
+    }
+
+    let mut code = imports.join("\n");
+    code.push_str("\n\n");
+    if let Some(note) = req.unsupported_auth {
+        let _ = writeln!(code, "# {}", note);
+    }
+    code.push_str(&setup);
+    code.push_str("response = requests.request(\n");
+    for arg in args {
+        let _ = writeln!(code, "    {},", arg);
+    }
+    code.push_str(")\n\nprint(response.status_code)\nprint(response.text)\n");
+    code
+}
+
+// JavaScript, fetch as in browsers and Node 18+
+
+fn javascript_fetch(req: &Prepared) -> String {
+    let mut code = String::new();
+    // Header values as JavaScript expressions, Basic credentials stay readable
+    let mut headers: Vec<(String, String)> = req
+        .headers
+        .iter()
+        .map(|(name, value)| (name.clone(), quoted(value)))
+        .collect();
+    if let Some((username, password)) = req.basic {
+        headers.push((
+            "Authorization".to_string(),
+            format!(
+                "\"Basic \" + btoa({})",
+                quoted(&format!("{}:{}", username, password))
+            ),
+        ));
+    }
+
+    if let Some(note) = req.unsupported_auth {
+        let _ = writeln!(code, "// {}", note);
+    }
+    if req.digest.is_some() {
+        code.push_str("// fetch has no Digest auth, answer the 401 challenge by hand\n");
+    }
+    if !req.options.verify_tls {
+        code.push_str(
+            "// fetch always checks certificates, in Node set NODE_TLS_REJECT_UNAUTHORIZED=0\n",
+        );
+    }
+
+    let needs_fs = matches!(req.body, Some(RequestBody::File { .. }))
+        || matches!(req.body, Some(RequestBody::Multipart { parts }) if parts.iter().any(|p| p.file_path.is_some()));
+    if needs_fs {
+        code.push_str("import { readFile } from \"node:fs/promises\";\n\n");
+    }
+
+    let body = match req.body {
+        Some(RequestBody::Text { content }) => Some(quoted(content)),
+        Some(RequestBody::Base64 { data }) => Some(format!(
+            "Uint8Array.from(atob({}), (c) => c.charCodeAt(0))",
+            quoted(data.trim())
+        )),
+        Some(RequestBody::File { path }) => Some(format!("await readFile({})", quoted(path))),
+        Some(RequestBody::Form { fields }) => {
+            let pairs: Vec<String> = fields
+                .iter()
+                .map(|f| format!("[{}, {}]", quoted(&f.name), quoted(&f.value)))
+                .collect();
+            Some(format!("new URLSearchParams([{}])", pairs.join(", ")))
+        }
+        Some(RequestBody::Multipart { parts }) => {
+            code.push_str("const form = new FormData();\n");
+            for part in parts {
+                match (&part.file_path, &part.value) {
+                    (Some(path), _) => {
+                        let name = part.file_name.clone().unwrap_or_else(|| file_name(path));
+                        let blob = match &part.content_type {
+                            Some(content_type) => format!(
+                                "new Blob([await readFile({})], {{ type: {} }})",
+                                quoted(path),
+                                quoted(content_type)
+                            ),
+                            None => format!("new Blob([await readFile({})])", quoted(path)),
+                        };
+                        let _ = writeln!(
+                            code,
+                            "form.append({}, {}, {});",
+                            quoted(&part.name),
+                            blob,
+                            quoted(&name)
+                        );
+                    }
+                    (None, value) => {
+                        let _ = writeln!(
+                            code,
+                            "form.append({}, {});",
+                            quoted(&part.name),
+                            quoted(value.as_deref().unwrap_or_default())
+                        );
+                    }
+                }
+            }
+            code.push('\n');
+            Some("form".to_string())
+        }
+        None => None,
+    };
+
+    let _ = writeln!(
+        code,
+        "const response = await fetch({}, {{",
+        quoted(&req.url)
+    );
+    let _ = writeln!(code, "  method: {},", quoted(&req.method));
+    if !headers.is_empty() {
+        code.push_str("  headers: {\n");
+        for (name, value) in &headers {
+            let _ = writeln!(code, "    {}: {},", quoted(name), value);
+        }
+        code.push_str("  },\n");
+    }
+    if let Some(body) = body {
+        let _ = writeln!(code, "  body: {},", body);
+    }
+    let _ = writeln!(
+        code,
+        "  redirect: {},",
+        quoted(if req.options.follow_redirects {
+            "follow"
+        } else {
+            "manual"
+        })
+    );
+    if let Some(ms) = req.options.timeout_ms {
+        let _ = writeln!(code, "  signal: AbortSignal.timeout({}),", ms);
+    }
+    code.push_str("});\n\nconsole.log(response.status);\nconsole.log(await response.text());\n");
+    code
+}
+
+// Go, net/http
+
+fn go_net_http(req: &Prepared) -> String {
+    let mut imports = vec!["fmt", "io", "net/http"];
+    let mut body_setup = String::new();
+    let mut content_type = None;
+
+    let body_expr = match req.body {
+        Some(RequestBody::Text { content }) => {
+            imports.push("strings");
+            format!("strings.NewReader({})", quoted(content))
+        }
+        Some(RequestBody::Base64 { data }) => {
+            imports.push("bytes");
+            imports.push("encoding/base64");
+            let _ = writeln!(
+                body_setup,
+                "\tdata, err := base64.StdEncoding.DecodeString({})\n\tif err != nil {{\n\t\tpanic(err)\n\t}}",
+                quoted(data.trim())
+            );
+            "bytes.NewReader(data)".to_string()
+        }
+        Some(RequestBody::File { path }) => {
+            imports.push("os");
+            let _ = writeln!(
+                body_setup,
+                "\tfile, err := os.Open({})\n\tif err != nil {{\n\t\tpanic(err)\n\t}}\n\tdefer file.Close()",
+                quoted(path)
+            );
+            "file".to_string()
+        }
+        Some(RequestBody::Form { fields }) => {
+            imports.push("net/url");
+            imports.push("strings");
+            body_setup.push_str("\tform := url.Values{}\n");
+            for field in fields {
+                let _ = writeln!(
+                    body_setup,
+                    "\tform.Add({}, {})",
+                    quoted(&field.name),
+                    quoted(&field.value)
+                );
+            }
+            "strings.NewReader(form.Encode())".to_string()
+        }
+        Some(RequestBody::Multipart { parts }) => {
+            imports.push("bytes");
+            imports.push("mime/multipart");
+            body_setup
+                .push_str("\tvar body bytes.Buffer\n\twriter := multipart.NewWriter(&body)\n");
+            for part in parts {
+                match (&part.file_path, &part.value) {
+                    (Some(path), _) => {
+                        if !imports.contains(&"os") {
+                            imports.push("os");
+                        }
+                        let name = part.file_name.clone().unwrap_or_else(|| file_name(path));
+                        // CreateFormFile always sends application/octet-stream
+                        if part.content_type.is_some() {
+                            imports.push("net/textproto");
+                        }
+                        let create = match &part.content_type {
+                            Some(content_type) => format!(
+                                "writer.CreatePart(textproto.MIMEHeader{{\n\t\t\t\"Content-Disposition\": {{{}}},\n\t\t\t\"Content-Type\":        {{{}}},\n\t\t}})",
+                                quoted(&format!(
+                                    "form-data; name=\"{}\"; filename=\"{}\"",
+                                    mime_quoted(&part.name),
+                                    mime_quoted(&name)
+                                )),
+                                quoted(content_type)
+                            ),
+                            None => format!(
+                                "writer.CreateFormFile({}, {})",
+                                quoted(&part.name),
+                                quoted(&name)
+                            ),
+                        };
+                        let _ = writeln!(
+                            body_setup,
+                            "\t{{\n\t\tcontent, err := os.ReadFile({})\n\t\tif err != nil {{\n\t\t\tpanic(err)\n\t\t}}\n\t\tpart, err := {}\n\t\tif err != nil {{\n\t\t\tpanic(err)\n\t\t}}\n\t\tpart.Write(content)\n\t}}",
+                            quoted(path),
+                            create
+                        );
+                    }
+                    (None, value) => {
+                        let _ = writeln!(
+                            body_setup,
+                            "\twriter.WriteField({}, {})",
+                            quoted(&part.name),
+                            quoted(value.as_deref().unwrap_or_default())
+                        );
+                    }
+                }
+            }
+            body_setup.push_str("\twriter.Close()\n");
+            content_type = Some("writer.FormDataContentType()");
+            "&body".to_string()
+        }
+        None => "nil".to_string(),
+    };
+
+    let mut client_fields = Vec::new();
+    if !req.options.verify_tls {
+        imports.push("crypto/tls");
+        client_fields.push(
+            "\t\tTransport: &http.Transport{\n\t\t\tTLSClientConfig: &tls.Config{InsecureSkipVerify: true},\n\t\t},"
+                .to_string(),
+        );
+    }
+    if !req.options.follow_redirects {
+        client_fields.push(
+            "\t\tCheckRedirect: func(req *http.Request, via []*http.Request) error {\n\t\t\treturn http.ErrUseLastResponse\n\t\t},"
+                .to_string(),
+        );
+    }
+    if let Some(ms) = req.options.timeout_ms {
+        imports.push("time");
+        client_fields.push(format!("\t\tTimeout: {} * time.Millisecond,", ms));
+    }
+
+    imports.sort();
+    imports.dedup();
+    let mut code = String::from("package main\n\nimport (\n");
+    for import in &imports {
+        let _ = writeln!(code, "\t\"{}\"", import);
+    }
+    code.push_str(")\n\nfunc main() {\n");
+    if let Some(note) = req.unsupported_auth {
+        let _ = writeln!(code, "\t// {}", note);
+    }
+    if req.digest.is_some() {
+        code.push_str("\t// net/http has no Digest auth, answer the 401 challenge by hand\n");
+    }
+    code.push_str(&body_setup);
+    if client_fields.is_empty() {
+        code.push_str("\tclient := &http.Client{}\n");
+    } else {
+        let _ = writeln!(
+            code,
+            "\tclient := &http.Client{{\n{}\n\t}}",
+            client_fields.join("\n")
+        );
+    }
+    let _ = writeln!(
+        code,
+        "\treq, err := http.NewRequest({}, {}, {})\n\tif err != nil {{\n\t\tpanic(err)\n\t}}",
+        quoted(&req.method),
+        quoted(&req.url),
+        body_expr
+    );
+    for (name, value) in &req.headers {
+        let _ = writeln!(
+            code,
+            "\treq.Header.Set({}, {})",
+            quoted(name),
+            quoted(value)
+        );
+    }
+    if let Some(content_type) = content_type {
+        let _ = writeln!(code, "\treq.Header.Set(\"Content-Type\", {})", content_type);
+    }
+    if let Some((username, password)) = req.basic {
+        let _ = writeln!(
+            code,
+            "\treq.SetBasicAuth({}, {})",
+            quoted(username),
+            quoted(password)
+        );
+    }
+    code.push_str(
+        "\n\tresp, err := client.Do(req)\n\tif err != nil {\n\t\tpanic(err)\n\t}\n\tdefer resp.Body.Close()\n\n\trespBody, err := io.ReadAll(resp.Body)\n\tif err != nil {\n\t\tpanic(err)\n\t}\n\tfmt.Println(resp.Status)\n\tfmt.Println(string(respBody))\n}\n",
+    );
+    code
+}
+
+// HTTPie
+
+fn httpie(req: &Prepared) -> String {
+    let mut args: Vec<String> = vec!["http".to_string()];
+    let mut prefix = None;
+    let mut stdin_file = None;
+
+    match req.body {
+        Some(RequestBody::Form { .. }) => args.push("--form".to_string()),
+        Some(RequestBody::Multipart { .. }) => args.push("--multipart".to_string()),
+        _ => {}
+    }
+    if let Some((username, password)) = req.basic {
+        args.push("--auth".to_string());
+        args.push(shell_quote(&format!("{}:{}", username, password)));
+    }
+    if let Some((username, password)) = req.digest {
+        args.push("--auth-type=digest".to_string());
+        args.push("--auth".to_string());
+        args.push(shell_quote(&format!("{}:{}", username, password)));
+    }
+    if !req.options.verify_tls {
+        args.push("--verify=no".to_string());
+    }
+    if req.options.follow_redirects {
+        args.push("--follow".to_string());
+        args.push(format!("--max-redirects={}", req.options.max_redirects));
+    }
+    if let Some(timeout) = req.timeout_secs() {
+        args.push(format!("--timeout={}", timeout));
+    }
+    match req.body {
+        Some(RequestBody::Text { content }) => {
+            args.push("--raw".to_string());
+            args.push(shell_quote(content));
+        }
+        Some(RequestBody::Base64 { data }) => {
+            prefix = Some(format!("echo {} | base64 -d |", shell_quote(data.trim())));
+        }
+        Some(RequestBody::File { path }) => stdin_file = Some(path.clone()),
+        _ => {}
+    }
+
+    args.push(shell_quote(&req.method));
+    args.push(shell_quote(&req.url));
+    for (name, value) in &req.headers {
+        args.push(shell_quote(&if value.is_empty() {
+            format!("{};", name)
+        } else {
+            format!("{}:{}", name, value)
+        }));
+    }
+    match req.body {
+        Some(RequestBody::Form { fields }) => {
+            for field in fields {
+                args.push(shell_quote(&format!("{}={}", field.name, field.value)));
+            }
+        }
+        Some(RequestBody::Multipart { parts }) => {
+            for part in parts {
+                match (&part.file_path, &part.value) {
+                    (Some(path), _) => {
+                        let mut item = format!("{}@{}", part.name, path);
+                        if let Some(content_type) = &part.content_type {
+                            let _ = write!(item, ";type={}", content_type);
+                        }
+                        args.push(shell_quote(&item));
+                    }
+                    (None, value) => args.push(shell_quote(&format!(
+                        "{}={}",
+                        part.name,
+                        value.as_deref().unwrap_or_default()
+                    ))),
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut command = args.join(" ");
+    if let Some(path) = stdin_file {
+        let _ = write!(command, " < {}", shell_quote(&path));
+    }
+    let command = match prefix {
+        Some(prefix) => format!("{} {}", prefix, command),
+        None => command,
+    };
+    match req.unsupported_auth {
+        Some(note) => format!("# {}\n{}", note, command),
+        None => command,
+    }
+}
+
+// wget
+
+fn wget(req: &Prepared) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    if let Some(note) = req.unsupported_auth {
+        lines.push(format!("# {}", note));
+    }
+
+    let mut args: Vec<String> = vec!["wget".to_string()];
+    args.push(format!("--method={}", shell_quote(&req.method)));
+    for (name, value) in &req.headers {
+        args.push(format!(
+            "--header={}",
+            shell_quote(&format!("{}: {}", name, value))
+        ));
+    }
+    if let Some((username, password)) = req.basic {
+        args.push(format!("--user={}", shell_quote(username)));
+        args.push(format!("--password={}", shell_quote(password)));
+        args.push("--auth-no-challenge".to_string());
+    }
+    // wget answers Digest challenges on its own
+    if let Some((username, password)) = req.digest {
+        args.push(format!("--user={}", shell_quote(username)));
+        args.push(format!("--password={}", shell_quote(password)));
+    }
+
+    match req.body {
+        Some(RequestBody::Text { content }) => {
+            args.push(format!("--body-data={}", shell_quote(content)));
+        }
+        Some(RequestBody::Base64 { data }) => {
+            lines.push(format!(
+                "echo {} | base64 -d > body.bin",
+                shell_quote(data.trim())
+            ));
+            args.push("--body-file=body.bin".to_string());
+        }
+        Some(RequestBody::File { path }) => {
+            args.push(format!("--body-file={}", shell_quote(path)));
+        }
+        Some(RequestBody::Form { fields }) => {
+            let mut form = Url::parse("http://localhost/").expect("static URL");
+            for field in fields {
+                form.query_pairs_mut()
+                    .append_pair(&field.name, &field.value);
+            }
+            if !req
+                .headers
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+            {
+                args.push(format!(
+                    "--header={}",
+                    shell_quote("Content-Type: application/x-www-form-urlencoded")
+                ));
+            }
+            args.push(format!(
+                "--body-data={}",
+                shell_quote(form.query().unwrap_or_default())
+            ));
+        }
+        Some(RequestBody::Multipart { .. }) => {
+            lines
+                .push("# wget can't send multipart bodies, use curl or HTTPie instead".to_string());
+        }
+        None => {}
+    }
+
+    if !req.options.verify_tls {
+        args.push("--no-check-certificate".to_string());
+    }
+    if req.options.follow_redirects {
+        args.push(format!("--max-redirect={}", req.options.max_redirects));
+    } else {
+        args.push("--max-redirect=0".to_string());
+    }
+    if let Some(timeout) = req.timeout_secs() {
+        args.push(format!("--timeout={}", timeout));
+    }
+    args.push("--output-document=-".to_string());
+    args.push(shell_quote(&req.url));
+
+    lines.push(args.join(" \\\n  "));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::MultipartPart;
+
+    // POST with a JSON body, Basic auth from an export and a timeout
+    fn request() -> ClientRequest {
+        ClientRequest {
+            method: "post".to_string(),
+            url: "https://api.example.com/items".to_string(),
+            headers: [("Accept".to_string(), "application/json".to_string())].into(),
+            body: Some(RequestBody::Text {
+                content: "{\"name\":\"it's\"}".to_string(),
+            }),
+            options: ClientOptions {
+                timeout_ms: Some(5000),
+                ..Default::default()
+            },
+            auth: Some(
+                Auth::Basic {
+                    username: "alice".to_string(),
+                    password: "s3cr3t".to_string(),
+                }
+                .with_placeholders(),
+            ),
+            collection_id: None,
+            environment_id: None,
+        }
+    }
+
+    #[test]
+    fn golden_curl() {
+        let expected = r#"curl https://api.example.com/items \
+  -H 'Accept: application/json' \
+  -u 'alice:{{password}}' \
+  --data-raw '{"name":"it'\''s"}' \
+  -k \
+  -L \
+  --max-redirs 10 \
+  --max-time 5"#;
+        assert_eq!(generate(&request(), Target::Curl), expected);
+    }
+
+    #[test]
+    fn golden_rust_reqwest() {
+        let expected = r#"#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .redirect(reqwest::redirect::Policy::limited(10))
+        .timeout(std::time::Duration::from_millis(5000))
+        .build()?;
+
+    let response = client
+        .request(reqwest::Method::POST, "https://api.example.com/items")
+        .header("Accept", "application/json")
+        .basic_auth("alice", Some("{{password}}"))
+        .body("{\"name\":\"it's\"}")
+        .send()
+        .await?;
+
+    println!("{}", response.status());
+    println!("{}", response.text().await?);
+    Ok(())
+}
+"#;
+        assert_eq!(generate(&request(), Target::RustReqwest), expected);
+    }
+
+    #[test]
+    fn golden_python_requests() {
+        let expected = r#"import requests
+
+response = requests.request(
+    "POST",
+    "https://api.example.com/items",
+    headers={
+        "Accept": "application/json",
+    },
+    auth=("alice", "{{password}}"),
+    data="{\"name\":\"it's\"}",
+    verify=False,
+    allow_redirects=True,
+    timeout=5,
+)
+
+print(response.status_code)
+print(response.text)
+"#;
+        assert_eq!(generate(&request(), Target::PythonRequests), expected);
+    }
+
+    #[test]
+    fn golden_javascript_fetch() {
+        let expected = r#"// fetch always checks certificates, in Node set NODE_TLS_REJECT_UNAUTHORIZED=0
+const response = await fetch("https://api.example.com/items", {
+  method: "POST",
+  headers: {
+    "Accept": "application/json",
+    "Authorization": "Basic " + btoa("alice:{{password}}"),
+  },
+  body: "{\"name\":\"it's\"}",
+  redirect: "follow",
+  signal: AbortSignal.timeout(5000),
+});
+
+console.log(response.status);
+console.log(await response.text());
+"#;
+        assert_eq!(generate(&request(), Target::JavascriptFetch), expected);
+    }
+
+    #[test]
+    fn golden_go_net_http() {
+        let expected = r#"package main
+
+import (
+	"crypto/tls"
+	"fmt"
+	"io"
+	"net/http"
+	"strings"
+	"time"
+)
+
+func main() {
+	client := &http.Client{
+		Transport: &http.Transport{
+			TLSClientConfig: &tls.Config{InsecureSkipVerify: true},
+		},
+		Timeout: 5000 * time.Millisecond,
+	}
+	req, err := http.NewRequest("POST", "https://api.example.com/items", strings.NewReader("{\"name\":\"it's\"}"))
+	if err != nil {
+		panic(err)
+	}
+	req.Header.Set("Accept", "application/json")
+	req.SetBasicAuth("alice", "{{password}}")
+
+	resp, err := client.Do(req)
+	if err != nil {
+		panic(err)
+	}
+	defer resp.Body.Close()
+
+	respBody, err := io.ReadAll(resp.Body)
+	if err != nil {
+		panic(err)
+	}
+	fmt.Println(resp.Status)
+	fmt.Println(string(respBody))
+}
+"#;
+        assert_eq!(generate(&request(), Target::GoNetHttp), expected);
+    }
+
+    #[test]
+    fn golden_httpie() {
+        let expected = r#"http --auth 'alice:{{password}}' --verify=no --follow --max-redirects=10 --timeout=5 --raw '{"name":"it'\''s"}' POST https://api.example.com/items Accept:application/json"#;
+        assert_eq!(generate(&request(), Target::Httpie), expected);
+    }
+
+    #[test]
+    fn golden_wget() {
+        let expected = r#"wget \
+  --method=POST \
+  --header='Accept: application/json' \
+  --user=alice \
+  --password='{{password}}' \
+  --auth-no-challenge \
+  --body-data='{"name":"it'\''s"}' \
+  --no-check-certificate \
+  --max-redirect=10 \
+  --timeout=5 \
+  --output-document=- \
+  https://api.example.com/items"#;
+        assert_eq!(generate(&request(), Target::Wget), expected);
+    }
+
+    #[test]
+    fn go_multipart_names_are_escaped() {
+        let mut req = request();
+        req.auth = None;
+        req.body = Some(RequestBody::Multipart {
+            parts: vec![MultipartPart {
+                name: "a\"b".to_string(),
+                value: None,
+                file_path: Some("/tmp/x`y.txt".to_string()),
+                file_name: None,
+                content_type: Some("text/plain".to_string()),
+            }],
+        });
+        let code = generate(&req, Target::GoNetHttp);
+        assert!(
+            code.contains(
+                r#""Content-Disposition": {"form-data; name=\"a\\\"b\"; filename=\"x`y.txt\""},"#
+            ),
+            "{}",
+            code
+        );
+    }
+}
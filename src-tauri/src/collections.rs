@@ -0,0 +1,758 @@
+use crate::auth::Auth;
+use crate::client::{ClientRequest, RequestBody};
+use crate::db::{collections, folders, saved_requests};
+use crate::environments::{self, VariableScope};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CollectionInput {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FolderInput {
+    pub collection_id: String,
+    pub parent_id: Option<String>,
+    pub name: String,
+}
+
+/// Contents of a saved request, its location is handled by create and move.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SavedRequestInput {
+    pub name: String,
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub body: Option<RequestBody>,
+    pub auth: Option<Auth>,
+    pub pre_request_script: Option<String>,
+    pub test_script: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewSavedRequest {
+    pub collection_id: String,
+    pub folder_id: Option<String>,
+    #[serde(flatten)]
+    pub request: SavedRequestInput,
+}
+
+/// Where to move a folder or request. `folder_id` is the new parent, None for
+/// the collection root. Without a position the item goes last.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MoveTarget {
+    pub collection_id: String,
+    pub folder_id: Option<String>,
+    pub position: Option<usize>,
+}
+
+/// A collection with all its folders and requests, each list ordered by
+/// `sort_order`. Nesting follows `parent_id` and `folder_id`.
+#[derive(Clone, Debug, Serialize)]
+pub struct CollectionTree {
+    pub collection: collections::Model,
+    pub folders: Vec<folders::Model>,
+    pub requests: Vec<saved_requests::Model>,
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn new_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+fn copy_name(name: &str) -> String {
+    format!("{} Copy", name)
+}
+
+// Collections
+
+pub async fn list_collections<C: ConnectionTrait>(
+    db: &C,
+) -> Result<Vec<collections::Model>, String> {
+    collections::Entity::find()
+        .order_by_asc(collections::Column::SortOrder)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+async fn find_collection<C: ConnectionTrait>(
+    db: &C,
+    id: &str,
+) -> Result<collections::Model, String> {
+    collections::Entity::find_by_id(id.to_string())
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown collection {}", id))
+}
+
+pub async fn get_collection_tree<C: ConnectionTrait>(
+    db: &C,
+    id: &str,
+) -> Result<CollectionTree, String> {
+    let collection = find_collection(db, id).await?;
+    let folders = folders::Entity::find()
+        .filter(folders::Column::CollectionId.eq(id))
+        .order_by_asc(folders::Column::SortOrder)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    let requests = saved_requests::Entity::find()
+        .filter(saved_requests::Column::CollectionId.eq(id))
+        .order_by_asc(saved_requests::Column::SortOrder)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(CollectionTree {
+        collection,
+        folders,
+        requests,
+    })
+}
+
+pub async fn create_collection<C: ConnectionTrait>(
+    db: &C,
+    input: CollectionInput,
+) -> Result<collections::Model, String> {
+    let count = list_collections(db).await?.len() as i64;
+    collections::ActiveModel {
+        id: Set(new_id()),
+        name: Set(input.name),
+        description: Set(input.description),
+        sort_order: Set(count),
+        created_at: Set(now()),
+        updated_at: Set(now()),
+    }
+    .insert(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn update_collection(
+    db: &DatabaseConnection,
+    id: &str,
+    input: CollectionInput,
+) -> Result<collections::Model, String> {
+    find_collection(db, id).await?;
+    collections::ActiveModel {
+        id: Set(id.to_string()),
+        name: Set(input.name),
+        description: Set(input.description),
+        updated_at: Set(now()),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn delete_collection(db: &DatabaseConnection, id: &str) -> Result<(), String> {
+    find_collection(db, id).await?;
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    environments::delete_scope(&txn, &VariableScope::Collection(id.to_string())).await?;
+    saved_requests::Entity::delete_many()
+        .filter(saved_requests::Column::CollectionId.eq(id))
+        .exec(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+    folders::Entity::delete_many()
+        .filter(folders::Column::CollectionId.eq(id))
+        .exec(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+    collections::Entity::delete_by_id(id.to_string())
+        .exec(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+    txn.commit().await.map_err(|e| e.to_string())
+}
+
+/// Moves a collection to `position` in the collection list.
+pub async fn reorder_collection(
+    db: &DatabaseConnection,
+    id: &str,
+    position: usize,
+) -> Result<Vec<collections::Model>, String> {
+    let mut ids: Vec<String> = list_collections(db)
+        .await?
+        .into_iter()
+        .map(|c| c.id)
+        .filter(|c| c != id)
+        .collect();
+    ids.insert(position.min(ids.len()), id.to_string());
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    for (order, id) in ids.into_iter().enumerate() {
+        collections::ActiveModel {
+            id: Set(id),
+            sort_order: Set(order as i64),
+            ..Default::default()
+        }
+        .update(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    txn.commit().await.map_err(|e| e.to_string())?;
+    list_collections(db).await
+}
+
+pub async fn duplicate_collection(
+    db: &DatabaseConnection,
+    id: &str,
+) -> Result<CollectionTree, String> {
+    let tree = get_collection_tree(db, id).await?;
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    let copy = create_collection(
+        &txn,
+        CollectionInput {
+            name: copy_name(&tree.collection.name),
+            description: tree.collection.description.clone(),
+        },
+    )
+    .await?;
+
+    // Parents have to be copied before their children
+    let folder_ids: Vec<String> = tree
+        .folders
+        .iter()
+        .filter(|f| f.parent_id.is_none())
+        .flat_map(|f| subtree(&tree.folders, &f.id))
+        .collect();
+    copy_folders(&txn, &tree, &folder_ids, &copy.id, None).await?;
+    txn.commit().await.map_err(|e| e.to_string())?;
+    get_collection_tree(db, &copy.id).await
+}
+
+// Folders
+
+async fn find_folder<C: ConnectionTrait>(db: &C, id: &str) -> Result<folders::Model, String> {
+    folders::Entity::find_by_id(id.to_string())
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown folder {}", id))
+}
+
+// The folder a new item goes into must exist in the same collection
+async fn check_location<C: ConnectionTrait>(
+    db: &C,
+    collection_id: &str,
+    folder_id: Option<&str>,
+) -> Result<(), String> {
+    find_collection(db, collection_id).await?;
+    if let Some(folder_id) = folder_id {
+        let folder = find_folder(db, folder_id).await?;
+        if folder.collection_id != collection_id {
+            return Err(format!(
+                "Folder {} is not part of collection {}",
+                folder_id, collection_id
+            ));
+        }
+    }
+    Ok(())
+}
+
+async fn sibling_folders<C: ConnectionTrait>(
+    db: &C,
+    collection_id: &str,
+    parent_id: Option<&str>,
+) -> Result<Vec<folders::Model>, String> {
+    let parent = match parent_id {
+        Some(parent_id) => folders::Column::ParentId.eq(parent_id),
+        None => folders::Column::ParentId.is_null(),
+    };
+    folders::Entity::find()
+        .filter(folders::Column::CollectionId.eq(collection_id))
+        .filter(parent)
+        .order_by_asc(folders::Column::SortOrder)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// `root` and every folder below it
+fn subtree(folders: &[folders::Model], root: &str) -> Vec<String> {
+    let mut ids = vec![root.to_string()];
+    let mut i = 0;
+    while i < ids.len() {
+        let parent = ids[i].clone();
+        ids.extend(
+            folders
+                .iter()
+                .filter(|f| f.parent_id.as_deref() == Some(parent.as_str()))
+                .map(|f| f.id.clone()),
+        );
+        i += 1;
+    }
+    ids
+}
+
+pub async fn create_folder(
+    db: &DatabaseConnection,
+    input: FolderInput,
+) -> Result<folders::Model, String> {
+    check_location(db, &input.collection_id, input.parent_id.as_deref()).await?;
+    let count = sibling_folders(db, &input.collection_id, input.parent_id.as_deref())
+        .await?
+        .len() as i64;
+
+    folders::ActiveModel {
+        id: Set(new_id()),
+        collection_id: Set(input.collection_id),
+        parent_id: Set(input.parent_id),
+        name: Set(input.name),
+        sort_order: Set(count),
+        created_at: Set(now()),
+        updated_at: Set(now()),
+    }
+    .insert(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn rename_folder(
+    db: &DatabaseConnection,
+    id: &str,
+    name: String,
+) -> Result<folders::Model, String> {
+    find_folder(db, id).await?;
+    folders::ActiveModel {
+        id: Set(id.to_string()),
+        name: Set(name),
+        updated_at: Set(now()),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Deletes the folder with its subfolders and requests.
+pub async fn delete_folder(db: &DatabaseConnection, id: &str) -> Result<(), String> {
+    let folder = find_folder(db, id).await?;
+    let tree = get_collection_tree(db, &folder.collection_id).await?;
+    let ids = subtree(&tree.folders, id);
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    saved_requests::Entity::delete_many()
+        .filter(saved_requests::Column::FolderId.is_in(ids.clone()))
+        .exec(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+    folders::Entity::delete_many()
+        .filter(folders::Column::Id.is_in(ids))
+        .exec(&txn)
+        .await
+        .map_err(|e| e.to_string())?;
+    txn.commit().await.map_err(|e| e.to_string())
+}
+
+pub async fn move_folder(
+    db: &DatabaseConnection,
+    id: &str,
+    target: MoveTarget,
+) -> Result<folders::Model, String> {
+    let folder = find_folder(db, id).await?;
+    check_location(db, &target.collection_id, target.folder_id.as_deref()).await?;
+
+    let tree = get_collection_tree(db, &folder.collection_id).await?;
+    let ids = subtree(&tree.folders, id);
+    if let Some(parent) = &target.folder_id {
+        if ids.contains(parent) {
+            return Err("A folder can't be moved into itself".to_string());
+        }
+    }
+
+    let mut siblings: Vec<String> =
+        sibling_folders(db, &target.collection_id, target.folder_id.as_deref())
+            .await?
+            .into_iter()
+            .map(|f| f.id)
+            .filter(|f| f != id)
+            .collect();
+    let position = target
+        .position
+        .unwrap_or(siblings.len())
+        .min(siblings.len());
+    siblings.insert(position, id.to_string());
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    // Everything below follows the folder into the other collection
+    if target.collection_id != folder.collection_id {
+        for folder_id in &ids {
+            folders::ActiveModel {
+                id: Set(folder_id.clone()),
+                collection_id: Set(target.collection_id.clone()),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+        for request in tree
+            .requests
+            .iter()
+            .filter(|r| r.folder_id.as_ref().is_some_and(|f| ids.contains(f)))
+        {
+            saved_requests::ActiveModel {
+                id: Set(request.id.clone()),
+                collection_id: Set(target.collection_id.clone()),
+                ..Default::default()
+            }
+            .update(&txn)
+            .await
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    for (order, sibling) in siblings.into_iter().enumerate() {
+        let mut model = folders::ActiveModel {
+            id: Set(sibling.clone()),
+            sort_order: Set(order as i64),
+            ..Default::default()
+        };
+        if sibling == id {
+            model.parent_id = Set(target.folder_id.clone());
+            model.updated_at = Set(now());
+        }
+        model.update(&txn).await.map_err(|e| e.to_string())?;
+    }
+    txn.commit().await.map_err(|e| e.to_string())?;
+
+    find_folder(db, id).await
+}
+
+/// Copies the folder with its content next to the original.
+pub async fn duplicate_folder(db: &DatabaseConnection, id: &str) -> Result<folders::Model, String> {
+    let folder = find_folder(db, id).await?;
+    let tree = get_collection_tree(db, &folder.collection_id).await?;
+    let ids = subtree(&tree.folders, id);
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    let copies = copy_folders(&txn, &tree, &ids, &folder.collection_id, Some(id)).await?;
+    txn.commit().await.map_err(|e| e.to_string())?;
+    let copy_id = copies
+        .get(id)
+        .cloned()
+        .ok_or_else(|| "Failed to copy folder".to_string())?;
+    find_folder(db, &copy_id).await
+}
+
+// Copies the given folders (parents before children) and their requests into
+// `collection_id`. `renamed` gets a "Copy" suffix and is placed last among its
+// siblings. Returns the new id of every copied folder.
+async fn copy_folders<C: ConnectionTrait>(
+    db: &C,
+    tree: &CollectionTree,
+    ids: &[String],
+    collection_id: &str,
+    renamed: Option<&str>,
+) -> Result<HashMap<String, String>, String> {
+    let selected: HashSet<&String> = ids.iter().collect();
+    let mut copies: HashMap<String, String> = HashMap::new();
+
+    for folder_id in ids {
+        let Some(folder) = tree.folders.iter().find(|f| &f.id == folder_id) else {
+            continue;
+        };
+        let new_id = new_id();
+        // Parents outside the copied set stay as they are
+        let parent_id = folder
+            .parent_id
+            .as_ref()
+            .map(|p| copies.get(p).cloned().unwrap_or_else(|| p.clone()));
+        let is_renamed = renamed == Some(folder.id.as_str());
+        let sort_order = if is_renamed {
+            sibling_folders(db, collection_id, parent_id.as_deref())
+                .await?
+                .len() as i64
+        } else {
+            folder.sort_order
+        };
+
+        folders::ActiveModel {
+            id: Set(new_id.clone()),
+            collection_id: Set(collection_id.to_string()),
+            parent_id: Set(parent_id),
+            name: Set(if is_renamed {
+                copy_name(&folder.name)
+            } else {
+                folder.name.clone()
+            }),
+            sort_order: Set(sort_order),
+            created_at: Set(now()),
+            updated_at: Set(now()),
+        }
+        .insert(db)
+        .await
+        .map_err(|e| e.to_string())?;
+        copies.insert(folder.id.clone(), new_id);
+    }
+
+    // Whole collection copies also take the requests at the root
+    let whole_collection = renamed.is_none();
+    for request in &tree.requests {
+        let folder_id = match &request.folder_id {
+            Some(f) if selected.contains(f) => copies.get(f).cloned(),
+            None if whole_collection => None,
+            _ => continue,
+        };
+        insert_request_copy(db, request, collection_id, folder_id, request.name.clone()).await?;
+    }
+
+    Ok(copies)
+}
+
+// Saved requests
+
+async fn find_request<C: ConnectionTrait>(
+    db: &C,
+    id: &str,
+) -> Result<saved_requests::Model, String> {
+    saved_requests::Entity::find_by_id(id.to_string())
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown saved request {}", id))
+}
+
+async fn sibling_requests<C: ConnectionTrait>(
+    db: &C,
+    collection_id: &str,
+    folder_id: Option<&str>,
+) -> Result<Vec<saved_requests::Model>, String> {
+    let folder = match folder_id {
+        Some(folder_id) => saved_requests::Column::FolderId.eq(folder_id),
+        None => saved_requests::Column::FolderId.is_null(),
+    };
+    saved_requests::Entity::find()
+        .filter(saved_requests::Column::CollectionId.eq(collection_id))
+        .filter(folder)
+        .order_by_asc(saved_requests::Column::SortOrder)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn to_json<T: Serialize>(value: &Option<T>) -> Option<String> {
+    value.as_ref().and_then(|v| serde_json::to_string(v).ok())
+}
+
+pub async fn get_saved_request(
+    db: &DatabaseConnection,
+    id: &str,
+) -> Result<saved_requests::Model, String> {
+    find_request(db, id).await
+}
+
+/// The saved request with the secret parts of its auth masked, for callers
+/// outside the app.
+pub fn mask_auth(mut request: saved_requests::Model) -> saved_requests::Model {
+    request.auth = request
+        .auth
+        .as_deref()
+        .and_then(|auth| serde_json::from_str::<Auth>(auth).ok())
+        .and_then(|auth| serde_json::to_string(&auth.masked()).ok());
+    request
+}
+
+/// The saved request as something the API client can send.
+pub fn to_client_request(
+    request: &saved_requests::Model,
+    environment_id: Option<String>,
+) -> Result<ClientRequest, String> {
+    let parse_error = |e: serde_json::Error| format!("Corrupt saved request {}: {}", request.id, e);
+    Ok(ClientRequest {
+        method: request.method.clone(),
+        url: request.url.clone(),
+        headers: serde_json::from_str(&request.headers).map_err(parse_error)?,
+        body: request
+            .body
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(parse_error)?,
+        options: Default::default(),
+        auth: request
+            .auth
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(parse_error)?,
+        collection_id: Some(request.collection_id.clone()),
+        environment_id,
+    })
+}
+
+pub async fn create_saved_request(
+    db: &DatabaseConnection,
+    new: NewSavedRequest,
+) -> Result<saved_requests::Model, String> {
+    check_location(db, &new.collection_id, new.folder_id.as_deref()).await?;
+    let count = sibling_requests(db, &new.collection_id, new.folder_id.as_deref())
+        .await?
+        .len() as i64;
+    let input = new.request;
+
+    saved_requests::ActiveModel {
+        id: Set(new_id()),
+        collection_id: Set(new.collection_id),
+        folder_id: Set(new.folder_id),
+        name: Set(input.name),
+        method: Set(input.method),
+        url: Set(input.url),
+        headers: Set(serde_json::to_string(&input.headers).unwrap_or_default()),
+        body: Set(to_json(&input.body)),
+        auth: Set(to_json(&input.auth)),
+        pre_request_script: Set(input.pre_request_script),
+        test_script: Set(input.test_script),
+        sort_order: Set(count),
+        created_at: Set(now()),
+        updated_at: Set(now()),
+    }
+    .insert(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn update_saved_request(
+    db: &DatabaseConnection,
+    id: &str,
+    input: SavedRequestInput,
+) -> Result<saved_requests::Model, String> {
+    find_request(db, id).await?;
+    saved_requests::ActiveModel {
+        id: Set(id.to_string()),
+        name: Set(input.name),
+        method: Set(input.method),
+        url: Set(input.url),
+        headers: Set(serde_json::to_string(&input.headers).unwrap_or_default()),
+        body: Set(to_json(&input.body)),
+        auth: Set(to_json(&input.auth)),
+        pre_request_script: Set(input.pre_request_script),
+        test_script: Set(input.test_script),
+        updated_at: Set(now()),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn delete_saved_request(db: &DatabaseConnection, id: &str) -> Result<(), String> {
+    find_request(db, id).await?;
+    saved_requests::Entity::delete_by_id(id.to_string())
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn move_saved_request(
+    db: &DatabaseConnection,
+    id: &str,
+    target: MoveTarget,
+) -> Result<saved_requests::Model, String> {
+    find_request(db, id).await?;
+    check_location(db, &target.collection_id, target.folder_id.as_deref()).await?;
+
+    let mut siblings: Vec<String> =
+        sibling_requests(db, &target.collection_id, target.folder_id.as_deref())
+            .await?
+            .into_iter()
+            .map(|r| r.id)
+            .filter(|r| r != id)
+            .collect();
+    let position = target
+        .position
+        .unwrap_or(siblings.len())
+        .min(siblings.len());
+    siblings.insert(position, id.to_string());
+
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    for (order, sibling) in siblings.into_iter().enumerate() {
+        let mut model = saved_requests::ActiveModel {
+            id: Set(sibling.clone()),
+            sort_order: Set(order as i64),
+            ..Default::default()
+        };
+        if sibling == id {
+            model.collection_id = Set(target.collection_id.clone());
+            model.folder_id = Set(target.folder_id.clone());
+            model.updated_at = Set(now());
+        }
+        model.update(&txn).await.map_err(|e| e.to_string())?;
+    }
+    txn.commit().await.map_err(|e| e.to_string())?;
+
+    find_request(db, id).await
+}
+
+pub async fn duplicate_saved_request(
+    db: &DatabaseConnection,
+    id: &str,
+) -> Result<saved_requests::Model, String> {
+    let request = find_request(db, id).await?;
+    let txn = db.begin().await.map_err(|e| e.to_string())?;
+    let copy = insert_request_copy(
+        &txn,
+        &request,
+        &request.collection_id,
+        request.folder_id.clone(),
+        copy_name(&request.name),
+    )
+    .await?;
+    txn.commit().await.map_err(|e| e.to_string())?;
+    Ok(copy)
+}
+
+// Copies go last in their folder, except within collection copies where the
+// original order is kept
+async fn insert_request_copy<C: ConnectionTrait>(
+    db: &C,
+    request: &saved_requests::Model,
+    collection_id: &str,
+    folder_id: Option<String>,
+    name: String,
+) -> Result<saved_requests::Model, String> {
+    let sort_order = if collection_id == request.collection_id {
+        sibling_requests(db, collection_id, folder_id.as_deref())
+            .await?
+            .len() as i64
+    } else {
+        request.sort_order
+    };
+
+    saved_requests::ActiveModel {
+        id: Set(new_id()),
+        collection_id: Set(collection_id.to_string()),
+        folder_id: Set(folder_id),
+        name: Set(name),
+        method: Set(request.method.clone()),
+        url: Set(request.url.clone()),
+        headers: Set(request.headers.clone()),
+        body: Set(request.body.clone()),
+        auth: Set(request.auth.clone()),
+        pre_request_script: Set(request.pre_request_script.clone()),
+        test_script: Set(request.test_script.clone()),
+        sort_order: Set(sort_order),
+        created_at: Set(now()),
+        updated_at: Set(now()),
+    }
+    .insert(db)
+    .await
+    .map_err(|e| e.to_string())
+}
@@ -0,0 +1,584 @@
+use crate::db::cookies;
+use reqwest::header::{HeaderMap, SET_COOKIE};
+use reqwest::Url;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use serde::Deserialize;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+/// Cookie fields as edited by the user.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CookieInput {
+    pub domain: String,
+    #[serde(default)]
+    pub host_only: bool,
+    #[serde(default = "default_path")]
+    pub path: String,
+    pub name: String,
+    pub value: String,
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    pub same_site: Option<String>,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+/// The API client's cookie jar, kept in memory and mirrored to SQLite.
+pub struct CookieJar {
+    cookies: RwLock<Vec<cookies::Model>>,
+    db: DatabaseConnection,
+}
+
+impl CookieJar {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            cookies: RwLock::new(Vec::new()),
+            db,
+        }
+    }
+
+    pub async fn load(&self) {
+        if let Ok(models) = cookies::Entity::find().all(&self.db).await {
+            let mut cookies = self.cookies.write().unwrap();
+            *cookies = models;
+            println!("Loaded {} cookies", cookies.len());
+        }
+    }
+
+    /// Cookies for one domain and its subdomains, or all of them.
+    pub fn list(&self, domain: Option<&str>) -> Vec<cookies::Model> {
+        let domain = domain.map(normalize_domain);
+        let mut cookies: Vec<cookies::Model> = self
+            .cookies
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|c| match &domain {
+                Some(domain) => domain_matches(&c.domain, domain),
+                None => true,
+            })
+            .cloned()
+            .collect();
+        cookies.sort_by(|a, b| (&a.domain, &a.path, &a.name).cmp(&(&b.domain, &b.path, &b.name)));
+        cookies
+    }
+
+    /// The Cookie header value for a request to `url`.
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let secure = url.scheme() == "https";
+        let now = now();
+
+        let cookies = self.cookies.read().unwrap();
+        let mut matching: Vec<&cookies::Model> = cookies
+            .iter()
+            .filter(|c| c.expires_at.is_none_or(|expires| expires > now))
+            .filter(|c| secure || !c.secure)
+            .filter(|c| {
+                if c.host_only {
+                    c.domain == host
+                } else {
+                    domain_matches(&host, &c.domain)
+                }
+            })
+            .filter(|c| path_matches(url.path(), &c.path))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        // Longer paths first, then older cookies, as RFC 6265 suggests
+        matching.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then(a.created_at.cmp(&b.created_at))
+        });
+        Some(
+            matching
+                .iter()
+                .map(|c| format!("{}={}", c.name, c.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Applies the Set-Cookie headers of a response received from `url`.
+    pub async fn store_response(&self, url: &Url, headers: &HeaderMap) {
+        for value in headers.get_all(SET_COOKIE) {
+            let Ok(value) = value.to_str() else {
+                continue;
+            };
+            let Some(cookie) = parse_set_cookie(url, value) else {
+                continue;
+            };
+            if url.scheme() != "https" && self.shadows_secure(&cookie) {
+                continue;
+            }
+            let result = match cookie.expires_at {
+                Some(expires) if expires <= now() => self.delete_matching(&cookie).await,
+                _ => self.set(cookie).await.map(|_| ()),
+            };
+            if let Err(e) = result {
+                eprintln!("Failed to store cookie: {}", e);
+            }
+        }
+    }
+
+    /// Adds or replaces the cookie with the same domain, path and name.
+    pub async fn set(&self, input: CookieInput) -> Result<cookies::Model, String> {
+        let domain = normalize_domain(&input.domain);
+        if domain.is_empty() || input.name.is_empty() {
+            return Err("A cookie needs a domain and a name".to_string());
+        }
+
+        let existing = self.find(&domain, input.host_only, &input.path, &input.name);
+        let model = cookies::ActiveModel {
+            id: Set(existing
+                .as_ref()
+                .map(|c| c.id.clone())
+                .unwrap_or_else(|| Uuid::new_v4().to_string())),
+            domain: Set(domain),
+            host_only: Set(input.host_only),
+            path: Set(input.path),
+            name: Set(input.name),
+            value: Set(input.value),
+            expires_at: Set(input.expires_at),
+            secure: Set(input.secure),
+            http_only: Set(input.http_only),
+            same_site: Set(input.same_site),
+            // Replacing keeps the original creation time
+            created_at: Set(existing.as_ref().map(|c| c.created_at).unwrap_or_else(now)),
+        };
+        let model = match existing {
+            Some(_) => model.update(&self.db).await,
+            None => model.insert(&self.db).await,
+        }
+        .map_err(|e| e.to_string())?;
+
+        let mut cookies = self.cookies.write().unwrap();
+        cookies.retain(|c| c.id != model.id);
+        cookies.push(model.clone());
+        Ok(model)
+    }
+
+    pub async fn remove(&self, id: String) -> Result<(), String> {
+        cookies::Entity::delete_by_id(id.clone())
+            .exec(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.cookies.write().unwrap().retain(|c| c.id != id);
+        Ok(())
+    }
+
+    /// Clears one domain with its subdomains, or the whole jar.
+    pub async fn clear(&self, domain: Option<String>) -> Result<(), String> {
+        let ids: Vec<String> = self
+            .list(domain.as_deref())
+            .into_iter()
+            .map(|c| c.id)
+            .collect();
+        cookies::Entity::delete_many()
+            .filter(cookies::Column::Id.is_in(ids.clone()))
+            .exec(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        self.cookies
+            .write()
+            .unwrap()
+            .retain(|c| !ids.contains(&c.id));
+        Ok(())
+    }
+
+    // A host-only cookie and a Domain cookie for the same name are separate,
+    // the first one is never sent to subdomains
+    fn find(
+        &self,
+        domain: &str,
+        host_only: bool,
+        path: &str,
+        name: &str,
+    ) -> Option<cookies::Model> {
+        self.cookies
+            .read()
+            .unwrap()
+            .iter()
+            .find(|c| {
+                c.domain == domain && c.host_only == host_only && c.path == path && c.name == name
+            })
+            .cloned()
+    }
+
+    async fn delete_matching(&self, cookie: &CookieInput) -> Result<(), String> {
+        let domain = normalize_domain(&cookie.domain);
+        match self.find(&domain, cookie.host_only, &cookie.path, &cookie.name) {
+            Some(cookie) => self.remove(cookie.id).await,
+            None => Ok(()),
+        }
+    }
+
+    // RFC 6265bis section 5.7: plain http responses can't overwrite or shadow
+    // a secure cookie of the same name
+    fn shadows_secure(&self, cookie: &CookieInput) -> bool {
+        self.cookies.read().unwrap().iter().any(|c| {
+            c.secure
+                && c.name == cookie.name
+                && (domain_matches(&c.domain, &cookie.domain)
+                    || domain_matches(&cookie.domain, &c.domain))
+                && path_matches(&cookie.path, &c.path)
+        })
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_start_matches('.').to_ascii_lowercase()
+}
+
+// `host` is `domain` or one of its subdomains
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+// Multi-label suffixes under which anyone can register a domain. Not the
+// full public suffix list, but the ones cookies get abused with most.
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "co.uk",
+    "org.uk",
+    "ac.uk",
+    "gov.uk",
+    "me.uk",
+    "ltd.uk",
+    "plc.uk",
+    "com.au",
+    "net.au",
+    "org.au",
+    "edu.au",
+    "gov.au",
+    "co.nz",
+    "org.nz",
+    "co.jp",
+    "ne.jp",
+    "or.jp",
+    "ac.jp",
+    "co.kr",
+    "or.kr",
+    "com.cn",
+    "net.cn",
+    "org.cn",
+    "com.br",
+    "net.br",
+    "org.br",
+    "co.in",
+    "net.in",
+    "org.in",
+    "com.mx",
+    "com.tw",
+    "com.hk",
+    "com.sg",
+    "co.za",
+    "com.tr",
+    "com.ar",
+    "co.il",
+    "com.ua",
+    "com.pl",
+    "github.io",
+    "gitlab.io",
+    "herokuapp.com",
+    "vercel.app",
+    "netlify.app",
+    "pages.dev",
+    "workers.dev",
+    "web.app",
+    "firebaseapp.com",
+    "appspot.com",
+    "azurewebsites.net",
+    "cloudfront.net",
+    "blogspot.com",
+    "fly.dev",
+    "onrender.com",
+];
+
+fn is_public_suffix(domain: &str) -> bool {
+    !domain.contains('.') || PUBLIC_SUFFIXES.contains(&domain)
+}
+
+// RFC 6265 section 5.1.1, which accepts RFC 1123, RFC 850 and asctime
+// dates alike by picking the fields out of the tokens
+fn parse_cookie_date(value: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let (mut time, mut day, mut month, mut year) = (None, None, None, None);
+
+    let is_delimiter = |c: char| !(c.is_ascii_alphanumeric() || c == ':');
+    for token in value.split(is_delimiter).filter(|t| !t.is_empty()) {
+        let digits = token.chars().take_while(char::is_ascii_digit).count();
+        if time.is_none() && token.contains(':') {
+            let fields: Vec<Option<u32>> = token
+                .splitn(3, ':')
+                .map(|f| {
+                    let f: String = f.chars().take_while(char::is_ascii_digit).collect();
+                    (1..=2).contains(&f.len()).then(|| f.parse().ok()).flatten()
+                })
+                .collect();
+            if let [Some(h), Some(m), Some(s)] = fields[..] {
+                time = Some((h, m, s));
+                continue;
+            }
+        }
+        if day.is_none() && (1..=2).contains(&digits) {
+            day = token[..digits].parse::<u32>().ok();
+            continue;
+        }
+        if month.is_none() && token.len() >= 3 {
+            let prefix = token[..3].to_ascii_lowercase();
+            if let Some(i) = MONTHS.iter().position(|m| *m == prefix) {
+                month = Some(i as u32 + 1);
+                continue;
+            }
+        }
+        if year.is_none() && (2..=4).contains(&digits) {
+            year = token[..digits].parse::<i32>().ok();
+        }
+    }
+
+    let year = match year? {
+        y @ 70..=99 => y + 1900,
+        y @ 0..=69 => y + 2000,
+        y => y,
+    };
+    let (h, m, s) = time?;
+    if year < 1601 || h > 23 || m > 59 || s > 59 {
+        return None;
+    }
+    chrono::NaiveDate::from_ymd_opt(year, month?, day?)?
+        .and_hms_opt(h, m, s)
+        .map(|d| d.and_utc().timestamp_millis())
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+// RFC 6265 default path: the request path up to its last slash
+fn default_cookie_path(url: &Url) -> String {
+    let path = url.path();
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+fn parse_set_cookie(url: &Url, header: &str) -> Option<CookieInput> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let is_ip = host
+        .trim_matches(['[', ']'])
+        .parse::<std::net::IpAddr>()
+        .is_ok();
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = CookieInput {
+        domain: host.clone(),
+        host_only: true,
+        path: default_cookie_path(url),
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        expires_at: None,
+        secure: false,
+        http_only: false,
+        same_site: None,
+    };
+    let mut max_age = None;
+
+    for attribute in parts {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "domain" if !value.is_empty() => {
+                let domain = normalize_domain(value);
+                if is_ip {
+                    // IP addresses have no subdomains to share the cookie with
+                    if domain.trim_matches(['[', ']']) != host.trim_matches(['[', ']']) {
+                        return None;
+                    }
+                    continue;
+                }
+                // Servers can't set cookies for unrelated domains
+                if !domain_matches(&host, &domain) {
+                    return None;
+                }
+                // Nor for a whole TLD or registry, unless that is the host itself
+                if is_public_suffix(&domain) {
+                    if domain != host {
+                        return None;
+                    }
+                    continue;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "expires" => {
+                cookie.expires_at = parse_cookie_date(value);
+            }
+            "max-age" => max_age = value.parse::<i64>().ok(),
+            // Only secure origins may set secure cookies
+            "secure" if url.scheme() != "https" => return None,
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            "samesite" if !value.is_empty() => cookie.same_site = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    // Max-Age wins over Expires
+    if let Some(max_age) = max_age {
+        cookie.expires_at = Some(if max_age <= 0 {
+            0
+        } else {
+            now() + max_age * 1000
+        });
+    }
+
+    Some(cookie)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn parse(url: &str, header: &str) -> Option<CookieInput> {
+        parse_set_cookie(&Url::parse(url).unwrap(), header)
+    }
+
+    #[test]
+    fn domain_attribute() {
+        let cookie = parse("https://a.example.com/", "a=1; Domain=.example.com").unwrap();
+        assert_eq!(cookie.domain, "example.com");
+        assert!(!cookie.host_only);
+
+        assert!(parse("https://a.example.com/", "a=1; Domain=other.com").is_none());
+        assert!(parse("https://a.example.com/", "a=1; Domain=com").is_none());
+        assert!(parse("https://shop.example.co.uk/", "a=1; Domain=co.uk").is_none());
+        assert!(parse("https://me.github.io/", "a=1; Domain=github.io").is_none());
+
+        // A single label host may still set a cookie for itself
+        let cookie = parse("http://localhost/", "a=1; Domain=localhost").unwrap();
+        assert!(cookie.host_only);
+    }
+
+    #[test]
+    fn ip_hosts_need_an_exact_domain() {
+        let cookie = parse("http://10.0.0.1/", "a=1; Domain=10.0.0.1").unwrap();
+        assert_eq!(cookie.domain, "10.0.0.1");
+        assert!(cookie.host_only);
+        assert!(parse("http://10.0.0.1/", "a=1; Domain=0.0.1").is_none());
+        assert!(parse("http://[::1]/", "a=1; Domain=::1").is_some());
+    }
+
+    fn jar(cookies: Vec<CookieInput>) -> CookieJar {
+        let jar = CookieJar::new(DatabaseConnection::default());
+        *jar.cookies.write().unwrap() = cookies
+            .into_iter()
+            .enumerate()
+            .map(|(i, c)| cookies::Model {
+                id: i.to_string(),
+                domain: c.domain,
+                host_only: c.host_only,
+                path: c.path,
+                name: c.name,
+                value: c.value,
+                expires_at: c.expires_at,
+                secure: c.secure,
+                http_only: c.http_only,
+                same_site: c.same_site,
+                created_at: i as i64,
+            })
+            .collect();
+        jar
+    }
+
+    fn header(jar: &CookieJar, url: &str) -> Option<String> {
+        jar.header_for(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn host_only_cookies_need_the_exact_host() {
+        let jar = jar(vec![
+            parse("https://example.com/", "host=1").unwrap(),
+            parse("https://example.com/", "domain=2; Domain=example.com").unwrap(),
+        ]);
+        assert_eq!(
+            header(&jar, "https://example.com/").as_deref(),
+            Some("host=1; domain=2")
+        );
+        assert_eq!(
+            header(&jar, "https://EXAMPLE.com/").as_deref(),
+            Some("host=1; domain=2")
+        );
+        assert_eq!(
+            header(&jar, "https://www.example.com/").as_deref(),
+            Some("domain=2")
+        );
+        assert_eq!(header(&jar, "https://notexample.com/"), None);
+    }
+
+    #[test]
+    fn host_only_and_domain_cookies_are_separate() {
+        let jar = jar(vec![parse("https://example.com/", "a=1").unwrap()]);
+        assert!(jar.find("example.com", true, "/", "a").is_some());
+        assert!(jar.find("example.com", false, "/", "a").is_none());
+    }
+
+    #[test]
+    fn secure_cookies_need_https() {
+        let cookie = parse("https://example.com/", "a=1; Secure").unwrap();
+        assert!(cookie.secure);
+        assert!(parse("http://example.com/", "a=1; Secure").is_none());
+
+        let jar = jar(vec![cookie]);
+        assert_eq!(header(&jar, "http://example.com/"), None);
+        assert_eq!(header(&jar, "https://example.com/").as_deref(), Some("a=1"));
+
+        // Nor can plain http shadow them with a cookie of the same name
+        let shadow = parse("http://www.example.com/", "a=2; Domain=example.com").unwrap();
+        assert!(jar.shadows_secure(&shadow));
+        let other = parse("http://example.com/", "b=2").unwrap();
+        assert!(!jar.shadows_secure(&other));
+    }
+
+    #[test]
+    fn cookie_dates() {
+        let expected = chrono::Utc
+            .with_ymd_and_hms(2026, 10, 21, 7, 28, 0)
+            .unwrap()
+            .timestamp_millis();
+        for date in [
+            "Wed, 21 Oct 2026 07:28:00 GMT",
+            "Wed, 21-Oct-2026 07:28:00 GMT",
+            "Wednesday, 21-Oct-26 07:28:00 GMT",
+            "Wed Oct 21 07:28:00 2026",
+        ] {
+            assert_eq!(parse_cookie_date(date), Some(expected), "{}", date);
+        }
+        assert_eq!(parse_cookie_date("Wed, 32 Oct 2026 07:28:00 GMT"), None);
+        assert_eq!(parse_cookie_date("tomorrow"), None);
+    }
+}
@@ -0,0 +1,1012 @@
+use crate::auth::{ApiKeyLocation, Auth};
+use crate::client::{
+    self, ClientOptions, ClientRequest, FormField, HttpVersion, MultipartPart, RequestBody,
+};
+use crate::db::requests;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::Url;
+use std::collections::HashMap;
+
+// Short options that take a value, as in curl's man page
+const SHORT_WITH_VALUE: &str = "XHdFuAebomwxTErUCKcDyYzQt";
+
+// Long options we don't use but whose value must be skipped
+const LONG_IGNORED_WITH_VALUE: &[&str] = &[
+    "output",
+    "cookie-jar",
+    "write-out",
+    "proxy",
+    "proxy-user",
+    "resolve",
+    "connect-to",
+    "cacert",
+    "capath",
+    "cert",
+    "cert-type",
+    "key",
+    "key-type",
+    "ciphers",
+    "retry",
+    "retry-delay",
+    "retry-max-time",
+    "limit-rate",
+    "interface",
+    "dump-header",
+    "config",
+    "range",
+    "trace",
+    "trace-ascii",
+    "stderr",
+    "local-port",
+    "unix-socket",
+    "abstract-unix-socket",
+    "pass",
+    "keepalive-time",
+    "expect100-timeout",
+    "dns-servers",
+    "speed-limit",
+    "speed-time",
+    "time-cond",
+    "continue-at",
+];
+
+// Long options without a value that don't change the request
+const LONG_IGNORED: &[&str] = &[
+    "silent",
+    "show-error",
+    "verbose",
+    "include",
+    "fail",
+    "fail-with-body",
+    "fail-early",
+    "progress-bar",
+    "no-progress-meter",
+    "globoff",
+    "no-buffer",
+    "no-keepalive",
+    "no-sessionid",
+    "path-as-is",
+    "raw",
+    "ipv4",
+    "ipv6",
+    "tcp-nodelay",
+    "tcp-fastopen",
+    "basic",
+    "anyauth",
+    "ssl",
+    "ssl-reqd",
+    "tlsv1",
+    "tlsv1.0",
+    "tlsv1.1",
+    "tlsv1.2",
+    "tlsv1.3",
+    "create-dirs",
+    "remote-name",
+    "remote-name-all",
+    "remote-header-name",
+    "location-trusted",
+    "post301",
+    "post302",
+    "post303",
+    "no-alpn",
+    "no-npn",
+    "disable",
+    "netrc",
+    "netrc-optional",
+    "junk-session-cookies",
+    "styled-output",
+    "no-styled-output",
+    "proxy-insecure",
+];
+
+enum Data {
+    Text(String),
+    File(String),
+}
+
+/// Turns a pasted curl command into a client request.
+pub fn parse_curl(command: &str) -> Result<ClientRequest, String> {
+    let args = split_args(command)?;
+    let mut args = args.into_iter().peekable();
+    if args
+        .peek()
+        .is_some_and(|a| a == "curl" || a.ends_with("/curl") || a == "curl.exe")
+    {
+        args.next();
+    }
+
+    let mut url = None;
+    let mut method = None;
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut data: Vec<Data> = Vec::new();
+    let mut parts: Vec<MultipartPart> = Vec::new();
+    let mut user = None;
+    let mut digest = false;
+    let mut aws_sigv4 = None;
+    let mut bearer = None;
+    let mut head = false;
+    let mut get = false;
+    let mut json = false;
+    let mut upload = None;
+    let mut request_target = None;
+    let mut options = ClientOptions {
+        // curl checks certificates and stays on the first response unless told otherwise
+        verify_tls: true,
+        follow_redirects: false,
+        ..Default::default()
+    };
+
+    // (option, value) pairs with short clusters like -sSLk expanded
+    let mut pairs: Vec<(String, Option<String>)> = Vec::new();
+    while let Some(arg) = args.next() {
+        if let Some(long) = arg.strip_prefix("--") {
+            let takes_value = long_takes_value(long);
+            // Anything else might take a value and shift the URL
+            if !takes_value && !long_is_flag(long) {
+                return Err(format!("Unsupported curl option --{}", long));
+            }
+            let value = if takes_value { args.next() } else { None };
+            if takes_value && value.is_none() {
+                return Err(format!("--{} needs a value", long));
+            }
+            pairs.push((long.to_string(), value));
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            let flags: Vec<char> = arg[1..].chars().collect();
+            for (i, flag) in flags.iter().enumerate() {
+                if SHORT_WITH_VALUE.contains(*flag) {
+                    let rest: String = flags[i + 1..].iter().collect();
+                    let value = if rest.is_empty() {
+                        args.next()
+                    } else {
+                        Some(rest)
+                    };
+                    if value.is_none() {
+                        return Err(format!("-{} needs a value", flag));
+                    }
+                    pairs.push((flag.to_string(), value));
+                    break;
+                }
+                pairs.push((flag.to_string(), None));
+            }
+        } else if url.is_none() {
+            url = Some(arg);
+        }
+    }
+
+    for (option, value) in pairs {
+        let value = value.unwrap_or_default();
+        match option.as_str() {
+            "X" | "request" => method = Some(value.to_uppercase()),
+            "H" | "header" => {
+                if let Some((name, v)) = value.split_once(':') {
+                    headers.push((name.trim().to_string(), v.trim().to_string()));
+                } else if let Some(name) = value.strip_suffix(';') {
+                    // "-H 'Name;'" sends the header with an empty value
+                    headers.push((name.trim().to_string(), String::new()));
+                }
+            }
+            "d" | "data" | "data-ascii" => data.push(match value.strip_prefix('@') {
+                Some(path) => Data::File(path.to_string()),
+                None => Data::Text(value),
+            }),
+            "data-raw" => data.push(Data::Text(value)),
+            "data-binary" => data.push(match value.strip_prefix('@') {
+                Some(path) => Data::File(path.to_string()),
+                None => Data::Text(value),
+            }),
+            "data-urlencode" => data.push(Data::Text(urlencode_data(&value)?)),
+            "json" => {
+                json = true;
+                data.push(match value.strip_prefix('@') {
+                    Some(path) => Data::File(path.to_string()),
+                    None => Data::Text(value),
+                });
+            }
+            "T" | "upload-file" => upload = Some(value),
+            "request-target" => request_target = Some(value),
+            "F" | "form" => parts.push(parse_form_part(&value)?),
+            "form-string" => {
+                let (name, v) = value.split_once('=').unwrap_or((value.as_str(), ""));
+                parts.push(MultipartPart {
+                    name: name.to_string(),
+                    value: Some(v.to_string()),
+                    file_path: None,
+                    file_name: None,
+                    content_type: None,
+                });
+            }
+            "u" | "user" => user = Some(value),
+            "digest" => digest = true,
+            "aws-sigv4" => aws_sigv4 = Some(value),
+            "oauth2-bearer" => bearer = Some(value),
+            "A" | "user-agent" => headers.push(("User-Agent".to_string(), value)),
+            "e" | "referer" => headers.push(("Referer".to_string(), value)),
+            // Without '=' the value names a cookie file
+            "b" | "cookie" if value.contains('=') => headers.push(("Cookie".to_string(), value)),
+            "url" => url = Some(value),
+            "I" | "head" => head = true,
+            "G" | "get" => get = true,
+            "k" | "insecure" => options.verify_tls = false,
+            "L" | "location" => options.follow_redirects = true,
+            "max-redirs" => {
+                options.max_redirects = value
+                    .parse()
+                    .map_err(|_| format!("Invalid --max-redirs '{}'", value))?
+            }
+            "m" | "max-time" => options.timeout_ms = Some(seconds_to_ms(&value)?),
+            "connect-timeout" => options.connect_timeout_ms = Some(seconds_to_ms(&value)?),
+            "0" | "http1.0" | "http1.1" => options.http_version = HttpVersion::Http1,
+            // curl negotiates HTTP/2 through ALPN and falls back, like our auto mode
+            "http2" => options.http_version = HttpVersion::Auto,
+            "http2-prior-knowledge" => options.http_version = HttpVersion::H2c,
+            // The client doesn't decompress, so not asking for compression
+            // gets the same body
+            "compressed" => {}
+            _ => {}
+        }
+    }
+
+    let url = url.ok_or_else(|| "The curl command has no URL".to_string())?;
+    let mut url = if url.contains("://") {
+        url
+    } else {
+        format!("http://{}", url)
+    };
+    if let Some(target) = request_target {
+        if !target.starts_with('/') {
+            return Err(format!("--request-target '{}' is not supported", target));
+        }
+        let mut parsed = Url::parse(&url).map_err(|e| e.to_string())?;
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (target.as_str(), None),
+        };
+        parsed.set_path(path);
+        parsed.set_query(query);
+        url = parsed.to_string();
+    }
+
+    let uploading = upload.is_some();
+    let body = if let Some(path) = upload {
+        if !data.is_empty() || !parts.is_empty() {
+            return Err("curl can't combine -T with -d or -F".to_string());
+        }
+        if path == "-" || path == "." {
+            return Err("Uploading from stdin is not supported".to_string());
+        }
+        // Like curl, a URL ending in a slash gets the file name appended
+        if url.ends_with('/') {
+            let name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+            url.push_str(name);
+        }
+        Some(RequestBody::File { path })
+    } else if !parts.is_empty() {
+        if !data.is_empty() {
+            return Err("curl can't combine -d and -F".to_string());
+        }
+        Some(RequestBody::Multipart { parts })
+    } else if data.is_empty() {
+        None
+    } else if get {
+        // -G moves the data into the query string
+        let query = data_text(data)?;
+        let separator = if url.contains('?') { '&' } else { '?' };
+        url = format!("{}{}{}", url, separator, query);
+        None
+    } else {
+        let body = match (data.len(), data.first()) {
+            (1, Some(Data::File(path))) => RequestBody::File { path: path.clone() },
+            _ => RequestBody::Text {
+                content: data_text(data)?,
+            },
+        };
+        let has_header = |headers: &[(String, String)], name: &str| {
+            headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+        };
+        if !has_header(&headers, "content-type") {
+            let content_type = if json {
+                "application/json"
+            } else {
+                "application/x-www-form-urlencoded"
+            };
+            headers.push(("Content-Type".to_string(), content_type.to_string()));
+        }
+        if json && !has_header(&headers, "accept") {
+            headers.push(("Accept".to_string(), "application/json".to_string()));
+        }
+        Some(body)
+    };
+
+    let method = method.unwrap_or_else(|| {
+        if head {
+            "HEAD"
+        } else if uploading {
+            "PUT"
+        } else if body.is_some() {
+            "POST"
+        } else {
+            "GET"
+        }
+        .to_string()
+    });
+
+    let auth = match (user, aws_sigv4, bearer) {
+        (Some(user), Some(provider), _) => {
+            // aws:amz:region:service
+            let fields: Vec<&str> = provider.split(':').collect();
+            let (access_key_id, secret_access_key) =
+                user.split_once(':').unwrap_or((user.as_str(), ""));
+            Some(Auth::AwsSigV4 {
+                access_key_id: access_key_id.to_string(),
+                secret_access_key: secret_access_key.to_string(),
+                session_token: None,
+                region: fields.get(2).unwrap_or(&"us-east-1").to_string(),
+                service: fields.get(3).unwrap_or(&"execute-api").to_string(),
+            })
+        }
+        (Some(user), None, _) => {
+            let (username, password) = user.split_once(':').unwrap_or((user.as_str(), ""));
+            let (username, password) = (username.to_string(), password.to_string());
+            Some(if digest {
+                Auth::Digest { username, password }
+            } else {
+                Auth::Basic { username, password }
+            })
+        }
+        (None, _, Some(token)) => Some(Auth::Bearer { token }),
+        _ => None,
+    };
+
+    // Repeated headers are folded into one
+    let mut header_map: HashMap<String, String> = HashMap::new();
+    for (name, value) in headers {
+        let existing = header_map
+            .keys()
+            .find(|k| k.eq_ignore_ascii_case(&name))
+            .cloned();
+        match existing {
+            Some(key) => {
+                let separator = if name.eq_ignore_ascii_case("cookie") {
+                    "; "
+                } else {
+                    ", "
+                };
+                let joined = format!("{}{}{}", header_map[&key], separator, value);
+                header_map.insert(key, joined);
+            }
+            None => {
+                header_map.insert(name, value);
+            }
+        }
+    }
+
+    Ok(ClientRequest {
+        method,
+        url,
+        headers: header_map,
+        body,
+        options,
+        auth,
+        collection_id: None,
+        environment_id: None,
+    })
+}
+
+fn long_takes_value(option: &str) -> bool {
+    matches!(
+        option,
+        "request"
+            | "header"
+            | "data"
+            | "data-ascii"
+            | "data-raw"
+            | "data-binary"
+            | "data-urlencode"
+            | "form"
+            | "form-string"
+            | "user"
+            | "user-agent"
+            | "referer"
+            | "cookie"
+            | "url"
+            | "max-time"
+            | "connect-timeout"
+            | "max-redirs"
+            | "aws-sigv4"
+            | "oauth2-bearer"
+            | "json"
+            | "upload-file"
+            | "request-target"
+    ) || LONG_IGNORED_WITH_VALUE.contains(&option)
+}
+
+fn long_is_flag(option: &str) -> bool {
+    let option = option.strip_prefix("no-").unwrap_or(option);
+    matches!(
+        option,
+        "digest"
+            | "head"
+            | "get"
+            | "insecure"
+            | "location"
+            | "http1.0"
+            | "http1.1"
+            | "http2"
+            | "http2-prior-knowledge"
+            | "compressed"
+    ) || LONG_IGNORED.contains(&option)
+}
+
+fn seconds_to_ms(value: &str) -> Result<u64, String> {
+    value
+        .parse::<f64>()
+        .map(|secs| (secs * 1000.0) as u64)
+        .map_err(|_| format!("Invalid timeout '{}'", value))
+}
+
+fn data_text(data: Vec<Data>) -> Result<String, String> {
+    data.into_iter()
+        .map(|d| match d {
+            Data::Text(text) => Ok(text),
+            Data::File(_) => Err("@file data can only be used on its own".to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|pieces| pieces.join("&"))
+}
+
+// --data-urlencode: "content", "=content" or "name=content"
+fn urlencode_data(value: &str) -> Result<String, String> {
+    let encode = form_encode;
+    if value.contains('@') && !value.contains('=') {
+        return Err("--data-urlencode with @file is not supported".to_string());
+    }
+    Ok(match value.split_once('=') {
+        Some(("", content)) => encode(content),
+        Some((name, content)) => format!("{}={}", name, encode(content)),
+        None => encode(value),
+    })
+}
+
+// application/x-www-form-urlencoded escaping, as curl does it
+fn form_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'*' => {
+                (b as char).to_string()
+            }
+            b' ' => "+".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// name=value, name=@file;type=...;filename=... or name=<file
+fn parse_form_part(value: &str) -> Result<MultipartPart, String> {
+    let (name, content) = value
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid -F value '{}'", value))?;
+    let mut part = MultipartPart {
+        name: name.to_string(),
+        value: None,
+        file_path: None,
+        file_name: None,
+        content_type: None,
+    };
+
+    let file = content
+        .strip_prefix('@')
+        .or_else(|| content.strip_prefix('<'));
+    let Some(file) = file else {
+        part.value = Some(content.to_string());
+        return Ok(part);
+    };
+
+    let (path, attributes) = match file.strip_prefix('"') {
+        Some(quoted) => {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| format!("Unterminated quote in -F value '{}'", value))?;
+            (quoted[..end].to_string(), &quoted[end + 1..])
+        }
+        None => match file.split_once(';') {
+            Some((path, rest)) => (path.to_string(), rest),
+            None => (file.to_string(), ""),
+        },
+    };
+    part.file_path = Some(path);
+    for attribute in attributes.split(';').filter(|a| !a.is_empty()) {
+        match attribute.split_once('=') {
+            Some(("type", v)) => part.content_type = Some(v.to_string()),
+            Some(("filename", v)) => part.file_name = Some(v.trim_matches('"').to_string()),
+            _ => {}
+        }
+    }
+    Ok(part)
+}
+
+// POSIX shell word splitting: quotes, backslashes, line continuations and
+// bash's $'...' strings
+fn split_args(command: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(next) => {
+                    current.push(next);
+                    in_word = true;
+                }
+                None => {}
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err("Unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => return Err("Unterminated double quote".to_string()),
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err("Unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                // \xNN escapes are raw bytes, which only make text together
+                let mut bytes = Vec::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => bytes.push(b'\n'),
+                            Some('t') => bytes.push(b'\t'),
+                            Some('r') => bytes.push(b'\r'),
+                            Some('x') => {
+                                let hex: String = (0..2).filter_map(|_| chars.next()).collect();
+                                let byte = u8::from_str_radix(&hex, 16)
+                                    .map_err(|_| format!("Invalid escape \\x{}", hex))?;
+                                bytes.push(byte);
+                            }
+                            Some(c) => push_char(&mut bytes, c),
+                            None => return Err("Unterminated $'' string".to_string()),
+                        },
+                        Some(c) => push_char(&mut bytes, c),
+                        None => return Err("Unterminated $'' string".to_string()),
+                    }
+                }
+                let text = String::from_utf8(bytes)
+                    .map_err(|_| "$'' string is not valid UTF-8".to_string())?;
+                current.push_str(&text);
+            }
+            c => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+fn push_char(bytes: &mut Vec<u8>, c: char) {
+    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+/// Quotes a word for POSIX shells, leaving simple words as they are.
+pub fn shell_quote(word: &str) -> String {
+    let safe = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@,+%".contains(c));
+    if safe {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Renders a client request as a curl command. Variables and OAuth tokens
+/// should be resolved beforehand.
+pub fn to_curl(req: &ClientRequest) -> String {
+    let mut args: Vec<String> = vec!["curl".to_string()];
+    // Binary bodies come from stdin
+    let mut stdin = None;
+
+    let method = req.method.to_uppercase();
+    let sends_body = req.body.is_some();
+    match method.as_str() {
+        "HEAD" if !sends_body => args.push("--head".to_string()),
+        "GET" if !sends_body => {}
+        "POST" if sends_body => {}
+        _ => {
+            args.push("-X".to_string());
+            args.push(shell_quote(&method));
+        }
+    }
+
+    let mut url = req.url.clone();
+    if let Some(Auth::ApiKey {
+        key,
+        value,
+        location: ApiKeyLocation::Query,
+    }) = &req.auth
+    {
+        if let Ok(mut parsed) = Url::parse(&url) {
+            parsed.query_pairs_mut().append_pair(key, value);
+            url = parsed.to_string();
+        }
+    }
+    args.push(shell_quote(&url));
+
+    let mut headers: Vec<(&String, &String)> = req.headers.iter().collect();
+    headers.sort();
+    for (name, value) in headers {
+        args.push("-H".to_string());
+        args.push(shell_quote(&if value.is_empty() {
+            format!("{};", name)
+        } else {
+            format!("{}: {}", name, value)
+        }));
+    }
+
+    match &req.auth {
+        Some(Auth::Basic { username, password }) => {
+            args.push("-u".to_string());
+            args.push(shell_quote(&format!("{}:{}", username, password)));
+        }
+        Some(Auth::Digest { username, password }) => {
+            args.push("--digest".to_string());
+            args.push("-u".to_string());
+            args.push(shell_quote(&format!("{}:{}", username, password)));
+        }
+        Some(Auth::Bearer { token }) => {
+            args.push("-H".to_string());
+            args.push(shell_quote(&format!("Authorization: Bearer {}", token)));
+        }
+        Some(Auth::ApiKey {
+            key,
+            value,
+            location: ApiKeyLocation::Header,
+        }) => {
+            args.push("-H".to_string());
+            args.push(shell_quote(&format!("{}: {}", key, value)));
+        }
+        Some(Auth::AwsSigV4 {
+            access_key_id,
+            secret_access_key,
+            session_token,
+            region,
+            service,
+        }) => {
+            args.push("--aws-sigv4".to_string());
+            args.push(shell_quote(&format!("aws:amz:{}:{}", region, service)));
+            args.push("-u".to_string());
+            args.push(shell_quote(&format!(
+                "{}:{}",
+                access_key_id, secret_access_key
+            )));
+            if let Some(token) = session_token {
+                args.push("-H".to_string());
+                args.push(shell_quote(&format!("x-amz-security-token: {}", token)));
+            }
+        }
+        Some(Auth::OAuth2(_)) => {
+            args.push("-H".to_string());
+            args.push(shell_quote("Authorization: Bearer <access token>"));
+        }
+        Some(Auth::ApiKey { .. }) | None => {}
+    }
+
+    match &req.body {
+        Some(RequestBody::Text { content }) => {
+            args.push("--data-raw".to_string());
+            args.push(shell_quote(content));
+        }
+        Some(RequestBody::Base64 { data }) => match STANDARD
+            .decode(data.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        {
+            Some(text) => {
+                args.push("--data-raw".to_string());
+                args.push(shell_quote(&text));
+            }
+            None => {
+                stdin = Some(data.trim().to_string());
+                args.push("--data-binary".to_string());
+                args.push("@-".to_string());
+            }
+        },
+        Some(RequestBody::File { path }) => {
+            args.push("--data-binary".to_string());
+            args.push(shell_quote(&format!("@{}", path)));
+        }
+        Some(RequestBody::Form { fields }) => {
+            for FormField { name, value } in fields {
+                args.push("--data-urlencode".to_string());
+                args.push(shell_quote(&format!("{}={}", name, value)));
+            }
+        }
+        Some(RequestBody::Multipart { parts }) => {
+            for part in parts {
+                match (&part.file_path, &part.value) {
+                    (Some(path), _) => {
+                        let mut spec = format!("{}=@\"{}\"", part.name, path.replace('"', "\\\""));
+                        if let Some(file_name) = &part.file_name {
+                            spec.push_str(&format!(";filename=\"{}\"", file_name));
+                        }
+                        if let Some(content_type) = &part.content_type {
+                            spec.push_str(&format!(";type={}", content_type));
+                        }
+                        args.push("-F".to_string());
+                        args.push(shell_quote(&spec));
+                    }
+                    (None, value) => {
+                        // --form-string never reads files, whatever the value starts with
+                        args.push("--form-string".to_string());
+                        args.push(shell_quote(&format!(
+                            "{}={}",
+                            part.name,
+                            value.as_deref().unwrap_or_default()
+                        )));
+                    }
+                }
+            }
+        }
+        None => {}
+    }
+
+    let options = &req.options;
+    if !options.verify_tls {
+        args.push("-k".to_string());
+    }
+    if options.follow_redirects {
+        args.push("-L".to_string());
+        args.push("--max-redirs".to_string());
+        args.push(options.max_redirects.to_string());
+    }
+    match options.http_version {
+        HttpVersion::Auto => {}
+        HttpVersion::Http1 => args.push("--http1.1".to_string()),
+        HttpVersion::Http2 => args.push("--http2".to_string()),
+        HttpVersion::H2c => args.push("--http2-prior-knowledge".to_string()),
+    }
+    if let Some(timeout) = options.connect_timeout_ms {
+        args.push("--connect-timeout".to_string());
+        args.push(format!("{}", timeout as f64 / 1000.0));
+    }
+    if let Some(timeout) = options.timeout_ms {
+        args.push("--max-time".to_string());
+        args.push(format!("{}", timeout as f64 / 1000.0));
+    }
+
+    // One option per line, with its value
+    let mut lines: Vec<String> = Vec::new();
+    for arg in args {
+        match lines.last_mut() {
+            Some(line) if !arg.starts_with('-') => {
+                line.push(' ');
+                line.push_str(&arg);
+            }
+            _ => lines.push(arg),
+        }
+    }
+    let command = lines.join(" \\\n  ");
+    match stdin {
+        Some(data) => format!("echo {} | base64 -d | {}", shell_quote(&data), command),
+        None => command,
+    }
+}
+
+/// Renders a captured request as a curl command that replays it as seen.
+pub fn record_to_curl(record: &requests::Model) -> String {
+    let command = to_curl(&ClientRequest::from_record(record));
+    if client::record_body_truncated(record) {
+        format!(
+            "# The captured body was cut at the capture limit, this sends only the stored part\n{}",
+            command
+        )
+    } else {
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_args_table() {
+        let cases: &[(&str, &[&str])] = &[
+            ("curl https://x", &["curl", "https://x"]),
+            ("a  'b c'  \"d e\"", &["a", "b c", "d e"]),
+            ("a\\ b c", &["a b", "c"]),
+            ("a \\\n  b", &["a", "b"]),
+            ("'it'\\''s'", &["it's"]),
+            ("\"a\\\"b\\$c\\n\"", &["a\"b$c\\n"]),
+            ("$'a\\tb\\n'", &["a\tb\n"]),
+            ("$'\\xc3\\xa9'", &["é"]),
+            ("x''", &["x"]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(split_args(input).unwrap(), *expected, "{}", input);
+        }
+        assert!(split_args("'open").is_err());
+        assert!(split_args("$'\\xff'").is_err());
+    }
+
+    #[test]
+    fn parse_curl_table() {
+        // (command, method, url, body)
+        let cases: &[(&str, &str, &str, Option<&str>)] = &[
+            ("curl example.com", "GET", "http://example.com", None),
+            (
+                "curl -sSL -X delete https://x/a",
+                "DELETE",
+                "https://x/a",
+                None,
+            ),
+            (
+                "curl -d a=1 -d b=2 https://x",
+                "POST",
+                "https://x",
+                Some("a=1&b=2"),
+            ),
+            (
+                "curl -G -d a=1 https://x?b=2",
+                "GET",
+                "https://x?b=2&a=1",
+                None,
+            ),
+            (
+                "curl --json '{\"a\":1}' https://x",
+                "POST",
+                "https://x",
+                Some("{\"a\":1}"),
+            ),
+            (
+                "curl --request-target '/b?c=1' https://x/a",
+                "GET",
+                "https://x/b?c=1",
+                None,
+            ),
+            ("curl -I --compressed https://x", "HEAD", "https://x", None),
+        ];
+        for (command, method, url, body) in cases {
+            let req = parse_curl(command).unwrap();
+            assert_eq!(req.method, *method, "{}", command);
+            assert_eq!(req.url, *url, "{}", command);
+            let content = match &req.body {
+                Some(RequestBody::Text { content }) => Some(content.as_str()),
+                _ => None,
+            };
+            assert_eq!(content, *body, "{}", command);
+        }
+    }
+
+    #[test]
+    fn parse_curl_json_headers() {
+        let req = parse_curl("curl --json @body.json https://x").unwrap();
+        assert_eq!(req.headers["Content-Type"], "application/json");
+        assert_eq!(req.headers["Accept"], "application/json");
+        assert!(matches!(req.body, Some(RequestBody::File { ref path }) if path == "body.json"));
+    }
+
+    #[test]
+    fn parse_curl_upload_file() {
+        let req = parse_curl("curl -T dir/report.txt https://x/files/").unwrap();
+        assert_eq!(req.method, "PUT");
+        assert_eq!(req.url, "https://x/files/report.txt");
+        assert!(
+            matches!(req.body, Some(RequestBody::File { ref path }) if path == "dir/report.txt")
+        );
+    }
+
+    #[test]
+    fn parse_curl_rejects_unknown_long_options() {
+        let err = parse_curl("curl --frobnicate value https://x").unwrap_err();
+        assert!(err.contains("--frobnicate"), "{}", err);
+        assert!(parse_curl("curl --no-location --silent https://x").is_ok());
+    }
+
+    #[test]
+    fn to_curl_round_trip() {
+        let commands = [
+            "curl https://x/a?b=1",
+            "curl -X PUT https://x -H 'Content-Type: text/plain' --data-raw 'it'\\''s'",
+            "curl https://x -u user:pass -k -L --max-redirs 3 --max-time 2.5",
+            "curl https://x -H 'X-Empty;' -H 'Accept: */*'",
+            "curl https://x -F a=1 -F 'f=@\"/tmp/a b.txt\";type=text/plain'",
+        ];
+        for command in commands {
+            let req = parse_curl(command).unwrap();
+            let again = parse_curl(&to_curl(&req)).unwrap();
+            assert_eq!(
+                serde_json::to_value(&req).unwrap(),
+                serde_json::to_value(&again).unwrap(),
+                "{}",
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn exported_credentials_are_placeholders() {
+        let secret = "s3cr3t-value".to_string();
+        let auths = [
+            Auth::Basic {
+                username: "alice".to_string(),
+                password: secret.clone(),
+            },
+            Auth::Digest {
+                username: "alice".to_string(),
+                password: secret.clone(),
+            },
+            Auth::Bearer {
+                token: secret.clone(),
+            },
+            Auth::ApiKey {
+                key: "X-Api-Key".to_string(),
+                value: secret.clone(),
+                location: ApiKeyLocation::Header,
+            },
+            Auth::ApiKey {
+                key: "api_key".to_string(),
+                value: secret.clone(),
+                location: ApiKeyLocation::Query,
+            },
+            Auth::AwsSigV4 {
+                access_key_id: secret.clone(),
+                secret_access_key: secret.clone(),
+                session_token: Some(secret.clone()),
+                region: "us-east-1".to_string(),
+                service: "s3".to_string(),
+            },
+        ];
+        for auth in auths {
+            let mut req = parse_curl("curl https://x/").unwrap();
+            req.auth = Some(auth.with_placeholders());
+            let command = to_curl(&req);
+            assert!(!command.contains(&secret), "{}", command);
+            assert!(
+                command.contains("%7B%7B") || command.contains("{{"),
+                "{}",
+                command
+            );
+        }
+    }
+}
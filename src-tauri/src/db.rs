@@ -18,8 +18,25 @@ pub mod requests {
         pub request_headers: String, // JSON
         pub request_body: Option<Vec<u8>>,
         pub response_status: i32,
+        // Calls from the gRPC client, the status from the trailers
+        pub grpc_status: Option<i32>,
         pub response_headers: String, // JSON
         pub response_body: Option<Vec<u8>>,
+        // Full body sizes, the stored bodies may be cut at the capture limit
+        pub request_body_size: Option<i64>,
+        pub response_body_size: Option<i64>,
+        // Proxy listener that captured the request
+        pub listener_id: Option<String>,
+        // Local client process, only resolved on Linux
+        pub process_name: Option<String>,
+        pub process_id: Option<i64>,
+        pub process_exe: Option<String>,
+        // Upstream TLS handshake and certificate chain, JSON
+        pub tls_info: Option<String>,
+        // Block rule that answered the request instead of upstream
+        pub blocked_by: Option<String>,
+        // "proxy" or "client" (sent from the API client)
+        pub source: Option<String>,
         pub duration: i64,
         pub timestamp: i64,
     }
@@ -27,6 +44,28 @@ pub mod requests {
     impl ActiveModelBehavior for ActiveModel {}
 }
 
+pub mod sse_events {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "sse_events")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub request_id: String,
+        pub seq: i64, // Position within the stream
+        pub event_id: Option<String>,
+        pub event_type: Option<String>,
+        pub data: String,
+        pub retry: Option<i64>,
+        pub timestamp: i64,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
 pub mod proto_files {
     use sea_orm::entity::prelude::*;
     use serde::{Deserialize, Serialize};
@@ -63,6 +102,204 @@ pub mod rewrites {
         pub replace_with: String,
         pub location: String, // e.g., "request" or "response"
         pub action: String,   // "replace", "delete", "add"
+        // Only applied by proxies using this rules profile, None applies everywhere
+        pub profile: Option<String>,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod dns_overrides {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "dns_overrides")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub host_pattern: String, // "api.example.com" or "*.example.com"
+        pub ip: String,
+        pub enabled: bool,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod block_rules {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "block_rules")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub name: String,
+        pub enabled: bool,
+        pub match_pattern: String, // Regex on the URL
+        pub action: String,        // "status" or "drop"
+        pub status: Option<i32>,   // For "status", defaults to 403
+        pub profile: Option<String>,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod collections {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "collections")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub name: String,
+        pub description: Option<String>,
+        pub sort_order: i64,
+        pub created_at: i64,
+        pub updated_at: i64,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod folders {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "folders")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub collection_id: String,
+        pub parent_id: Option<String>, // None for top level folders
+        pub name: String,
+        pub sort_order: i64, // Among folders with the same parent
+        pub created_at: i64,
+        pub updated_at: i64,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod saved_requests {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "saved_requests")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub collection_id: String,
+        pub folder_id: Option<String>, // None at the collection root
+        pub name: String,
+        pub method: String,
+        pub url: String,
+        pub headers: String,      // JSON
+        pub body: Option<String>, // JSON, client::RequestBody
+        pub auth: Option<String>, // JSON
+        pub pre_request_script: Option<String>,
+        pub test_script: Option<String>,
+        pub sort_order: i64, // Among requests in the same folder
+        pub created_at: i64,
+        pub updated_at: i64,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod environments {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "environments")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub name: String,
+        pub created_at: i64,
+        pub updated_at: i64,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod variables {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "variables")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub scope: String,            // "global", "collection" or "environment"
+        pub scope_id: Option<String>, // Collection or environment id, None for globals
+        pub key: String,
+        pub value: String,
+        pub secret: bool,
+        pub enabled: bool,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod oauth_tokens {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "oauth_tokens")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String, // Derived from environment, token URL, client id and scope
+        pub environment_id: Option<String>,
+        pub token_url: String,
+        pub client_id: String,
+        pub scope: Option<String>,
+        pub access_token: String,
+        pub token_type: String,
+        pub refresh_token: Option<String>,
+        pub expires_at: Option<i64>, // Unix millis
+        pub created_at: i64,
+    }
+
+    impl ActiveModelBehavior for ActiveModel {}
+}
+
+pub mod cookies {
+    use sea_orm::entity::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[sea_orm::model]
+    #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+    #[sea_orm(table_name = "cookies")]
+    pub struct Model {
+        #[sea_orm(primary_key, auto_increment = false)]
+        pub id: String,
+        pub domain: String,  // Lowercase, without a leading dot
+        pub host_only: bool, // Set without a Domain attribute, exact host only
+        pub path: String,
+        pub name: String,
+        pub value: String,
+        pub expires_at: Option<i64>, // Unix millis, None for session cookies
+        pub secure: bool,
+        pub http_only: bool,
+        pub same_site: Option<String>,
+        pub created_at: i64,
     }
 
     impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,207 @@
+use crate::db::dns_overrides;
+use crate::hosts;
+use hudsucker::hyper::{client::connect::dns::Name, service::Service};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, ModelTrait, Set};
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+struct Override {
+    host_pattern: String,
+    ip: IpAddr,
+}
+
+/// Hosts-file style overrides used by the proxy upstream and the API client.
+pub struct DnsOverrides {
+    entries: RwLock<Vec<Override>>,
+    db: DatabaseConnection,
+}
+
+impl DnsOverrides {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self {
+            entries: RwLock::new(Vec::new()),
+            db,
+        }
+    }
+
+    pub async fn load(&self) {
+        if let Ok(models) = dns_overrides::Entity::find().all(&self.db).await {
+            let mut entries = self.entries.write().unwrap();
+            *entries = models
+                .into_iter()
+                .filter(|m| m.enabled)
+                .filter_map(|m| {
+                    Some(Override {
+                        ip: m.ip.parse().ok()?,
+                        host_pattern: m.host_pattern,
+                    })
+                })
+                .collect();
+            println!("Loaded {} DNS overrides", entries.len());
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<dns_overrides::Model>, String> {
+        dns_overrides::Entity::find()
+            .all(&self.db)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn add(
+        &self,
+        host_pattern: String,
+        ip: String,
+    ) -> Result<dns_overrides::Model, String> {
+        ip.parse::<IpAddr>()
+            .map_err(|_| format!("Invalid IP address: {}", ip))?;
+
+        let model = dns_overrides::ActiveModel {
+            id: Set(Uuid::new_v4().to_string()),
+            host_pattern: Set(host_pattern),
+            ip: Set(ip),
+            enabled: Set(true),
+        }
+        .insert(&self.db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        self.load().await;
+        Ok(model)
+    }
+
+    pub async fn remove(&self, id: String) -> Result<(), String> {
+        let model = dns_overrides::Entity::find_by_id(id.clone())
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Unknown DNS override {}", id))?;
+        model.delete(&self.db).await.map_err(|e| e.to_string())?;
+
+        self.load().await;
+        Ok(())
+    }
+
+    /// The most specific override for `host`.
+    pub fn lookup(&self, host: &str) -> Option<IpAddr> {
+        let entries = self.entries.read().unwrap();
+        entries
+            .iter()
+            .filter(|e| hosts::matches(&e.host_pattern, host))
+            .max_by_key(|e| e.host_pattern.len())
+            .map(|e| e.ip)
+    }
+
+    // Port 0, connectors fill in the port of the request
+    async fn resolve(&self, host: &str) -> io::Result<Vec<SocketAddr>> {
+        if let Some(ip) = self.lookup(host) {
+            return Ok(vec![SocketAddr::new(ip, 0)]);
+        }
+        Ok(tokio::net::lookup_host((host, 0)).await?.collect())
+    }
+}
+
+/// Resolver for hyper and reqwest applying the overrides before falling back to the system.
+#[derive(Clone)]
+pub struct OverrideResolver {
+    overrides: Arc<DnsOverrides>,
+}
+
+impl OverrideResolver {
+    pub fn new(overrides: Arc<DnsOverrides>) -> Self {
+        Self { overrides }
+    }
+}
+
+impl Service<Name> for OverrideResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<Self::Response>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let overrides = self.overrides.clone();
+        Box::pin(async move { Ok(overrides.resolve(name.as_str()).await?.into_iter()) })
+    }
+}
+
+impl reqwest::dns::Resolve for OverrideResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let overrides = self.overrides.clone();
+        Box::pin(async move {
+            let addrs = overrides.resolve(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(entries: &[(&str, &str)]) -> DnsOverrides {
+        let overrides = DnsOverrides::new(DatabaseConnection::default());
+        *overrides.entries.write().unwrap() = entries
+            .iter()
+            .map(|(pattern, ip)| Override {
+                host_pattern: pattern.to_string(),
+                ip: ip.parse().unwrap(),
+            })
+            .collect();
+        overrides
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn exact_and_wildcard() {
+        let dns = overrides(&[
+            ("api.example.com", "10.0.0.1"),
+            ("*.example.com", "10.0.0.2"),
+        ]);
+        assert_eq!(dns.lookup("api.example.com"), ip("10.0.0.1"));
+        assert_eq!(dns.lookup("www.example.com"), ip("10.0.0.2"));
+        assert_eq!(dns.lookup("a.b.example.com"), ip("10.0.0.2"));
+        // The wildcard only covers subdomains
+        assert_eq!(dns.lookup("example.com"), None);
+        assert_eq!(dns.lookup("notexample.com"), None);
+    }
+
+    #[test]
+    fn most_specific_wins() {
+        let dns = overrides(&[
+            ("*", "10.0.0.9"),
+            ("*.example.com", "10.0.0.2"),
+            ("*.api.example.com", "10.0.0.3"),
+        ]);
+        assert_eq!(dns.lookup("v1.api.example.com"), ip("10.0.0.3"));
+        assert_eq!(dns.lookup("www.example.com"), ip("10.0.0.2"));
+        assert_eq!(dns.lookup("other.org"), ip("10.0.0.9"));
+    }
+
+    #[test]
+    fn case_and_port() {
+        let dns = overrides(&[("API.Example.com", "::1")]);
+        assert_eq!(dns.lookup("api.example.COM"), ip("::1"));
+        assert_eq!(dns.lookup("api.example.com:8443"), ip("::1"));
+        assert_eq!(dns.lookup("api.example.com."), ip("::1"));
+    }
+
+    #[tokio::test]
+    async fn overrides_resolve_without_port() {
+        let dns = overrides(&[("db.internal", "192.168.1.20")]);
+        let addrs = dns.resolve("db.internal").await.unwrap();
+        assert_eq!(addrs, vec!["192.168.1.20:0".parse().unwrap()]);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::client::{ClientRequest, RequestBody};
+use crate::db::{environments, variables};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Shown instead of secret values in listings and the request history
+pub const SECRET_MASK: &str = "********";
+
+/// Where a variable is defined. Environment values win over collection values,
+/// which win over globals.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "scope", content = "id", rename_all = "lowercase")]
+pub enum VariableScope {
+    Global,
+    Collection(String),
+    Environment(String),
+}
+
+impl VariableScope {
+    /// Builds a scope from its name and id, e.g. from query parameters.
+    pub fn from_parts(scope: &str, id: Option<String>) -> Result<Self, String> {
+        match (scope, id) {
+            ("global", _) => Ok(VariableScope::Global),
+            ("collection", Some(id)) => Ok(VariableScope::Collection(id)),
+            ("environment", Some(id)) => Ok(VariableScope::Environment(id)),
+            (scope, _) => Err(format!("Unknown variable scope '{}' or missing id", scope)),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            VariableScope::Global => "global",
+            VariableScope::Collection(_) => "collection",
+            VariableScope::Environment(_) => "environment",
+        }
+    }
+
+    fn id(&self) -> Option<String> {
+        match self {
+            VariableScope::Global => None,
+            VariableScope::Collection(id) | VariableScope::Environment(id) => Some(id.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct VariableInput {
+    pub key: String,
+    pub value: String,
+    #[serde(default)]
+    pub secret: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+// Environments
+
+pub async fn list_environments(
+    db: &DatabaseConnection,
+) -> Result<Vec<environments::Model>, String> {
+    environments::Entity::find()
+        .order_by_asc(environments::Column::Name)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn create_environment(
+    db: &DatabaseConnection,
+    name: String,
+) -> Result<environments::Model, String> {
+    environments::ActiveModel {
+        id: Set(Uuid::new_v4().to_string()),
+        name: Set(name),
+        created_at: Set(now()),
+        updated_at: Set(now()),
+    }
+    .insert(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn rename_environment(
+    db: &DatabaseConnection,
+    id: &str,
+    name: String,
+) -> Result<environments::Model, String> {
+    environments::ActiveModel {
+        id: Set(id.to_string()),
+        name: Set(name),
+        updated_at: Set(now()),
+        ..Default::default()
+    }
+    .update(db)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+pub async fn delete_environment(db: &DatabaseConnection, id: &str) -> Result<(), String> {
+    delete_scope(db, &VariableScope::Environment(id.to_string())).await?;
+    environments::Entity::delete_by_id(id.to_string())
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Variables
+
+fn scope_filter(scope: &VariableScope) -> sea_orm::Condition {
+    let condition = sea_orm::Condition::all().add(variables::Column::Scope.eq(scope.name()));
+    match scope.id() {
+        Some(id) => condition.add(variables::Column::ScopeId.eq(id)),
+        None => condition.add(variables::Column::ScopeId.is_null()),
+    }
+}
+
+/// Variables of one scope, secret values masked.
+pub async fn list_variables(
+    db: &DatabaseConnection,
+    scope: &VariableScope,
+) -> Result<Vec<variables::Model>, String> {
+    let variables = variables::Entity::find()
+        .filter(scope_filter(scope))
+        .order_by_asc(variables::Column::Key)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(variables
+        .into_iter()
+        .map(|mut v| {
+            if v.secret {
+                v.value = SECRET_MASK.to_string();
+            }
+            v
+        })
+        .collect())
+}
+
+/// Creates or replaces the variable with the same key in `scope`.
+pub async fn set_variable(
+    db: &DatabaseConnection,
+    scope: VariableScope,
+    input: VariableInput,
+) -> Result<variables::Model, String> {
+    if input.key.is_empty() || input.key.contains(['{', '}']) {
+        return Err(format!("Invalid variable name '{}'", input.key));
+    }
+
+    let existing = variables::Entity::find()
+        .filter(scope_filter(&scope))
+        .filter(variables::Column::Key.eq(input.key.as_str()))
+        .one(db)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Listings mask secrets, saving the mask back keeps the stored value
+    let value = match &existing {
+        Some(v) if v.secret && input.value == SECRET_MASK => v.value.clone(),
+        _ => input.value,
+    };
+
+    let model = variables::ActiveModel {
+        id: Set(existing
+            .as_ref()
+            .map(|v| v.id.clone())
+            .unwrap_or_else(|| Uuid::new_v4().to_string())),
+        scope: Set(scope.name().to_string()),
+        scope_id: Set(scope.id()),
+        key: Set(input.key),
+        value: Set(value),
+        secret: Set(input.secret),
+        enabled: Set(input.enabled),
+    };
+    let mut saved = match existing {
+        Some(_) => model.update(db).await,
+        None => model.insert(db).await,
+    }
+    .map_err(|e| e.to_string())?;
+
+    if saved.secret {
+        saved.value = SECRET_MASK.to_string();
+    }
+    Ok(saved)
+}
+
+pub async fn delete_variable(db: &DatabaseConnection, id: &str) -> Result<(), String> {
+    variables::Entity::delete_by_id(id.to_string())
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+pub async fn delete_scope<C: ConnectionTrait>(db: &C, scope: &VariableScope) -> Result<(), String> {
+    variables::Entity::delete_many()
+        .filter(scope_filter(scope))
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Interpolation
+
+/// The enabled variables visible to a request, after scope precedence.
+#[derive(Default)]
+pub struct Variables {
+    values: HashMap<String, String>,
+    secrets: Vec<String>,
+}
+
+impl Variables {
+    pub async fn resolve(
+        db: &DatabaseConnection,
+        collection_id: Option<&str>,
+        environment_id: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut scopes = vec![VariableScope::Global];
+        scopes.extend(collection_id.map(|id| VariableScope::Collection(id.to_string())));
+        scopes.extend(environment_id.map(|id| VariableScope::Environment(id.to_string())));
+
+        let mut found: HashMap<String, variables::Model> = HashMap::new();
+        // Narrower scopes come later and overwrite
+        for scope in &scopes {
+            let variables = variables::Entity::find()
+                .filter(scope_filter(scope))
+                .filter(variables::Column::Enabled.eq(true))
+                .all(db)
+                .await
+                .map_err(|e| e.to_string())?;
+            for variable in variables {
+                found.insert(variable.key.clone(), variable);
+            }
+        }
+
+        let mut resolved = Variables::default();
+        for (key, variable) in found {
+            if variable.secret && !variable.value.is_empty() {
+                resolved.secrets.push(variable.value.clone());
+            }
+            resolved.values.insert(key, variable.value);
+        }
+        Ok(resolved)
+    }
+
+    /// Replaces `{{name}}` placeholders, unknown names are added to `missing`.
+    pub fn interpolate(&self, text: &str, missing: &mut Vec<String>) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            out.push_str(&rest[..start]);
+            let placeholder = &rest[start..start + len + 4];
+            let name = placeholder[2..placeholder.len() - 2].trim();
+            match self.values.get(name) {
+                Some(value) => out.push_str(value),
+                None => {
+                    if !missing.iter().any(|m| m == name) {
+                        missing.push(name.to_string());
+                    }
+                    out.push_str(placeholder);
+                }
+            }
+            rest = &rest[start + len + 4..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// The same variables with secret values left as their `{{name}}`
+    /// placeholders, for requests shown outside the app.
+    pub fn without_secrets(self) -> Self {
+        let values = self
+            .values
+            .into_iter()
+            .map(|(key, value)| {
+                if self.secrets.contains(&value) {
+                    let placeholder = format!("{{{{{}}}}}", key);
+                    (key, placeholder)
+                } else {
+                    (key, value)
+                }
+            })
+            .collect();
+        Variables {
+            values,
+            secrets: Vec::new(),
+        }
+    }
+
+    /// Masks `value` too, e.g. credentials that don't come from a variable.
+    pub fn add_secret(&mut self, value: String) {
+        if !value.is_empty() && !self.secrets.contains(&value) {
+            self.secrets.push(value);
+        }
+    }
+
+    /// Hides secret values in text that gets stored or displayed.
+    pub fn mask(&self, text: &str) -> String {
+        self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret.as_str(), SECRET_MASK)
+        })
+    }
+
+    pub fn mask_bytes(&self, bytes: Vec<u8>) -> Vec<u8> {
+        if self.secrets.is_empty() {
+            return bytes;
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => self.mask(&text).into_bytes(),
+            Err(e) => e.into_bytes(),
+        }
+    }
+
+    /// Interpolates URL, headers and body. Fails listing every variable that
+    /// could not be resolved.
+    pub fn apply(&self, mut req: ClientRequest) -> Result<ClientRequest, String> {
+        let mut missing = Vec::new();
+
+        req.url = self.interpolate(&req.url, &mut missing);
+        req.headers = req
+            .headers
+            .into_iter()
+            .map(|(k, v)| {
+                (
+                    self.interpolate(&k, &mut missing),
+                    self.interpolate(&v, &mut missing),
+                )
+            })
+            .collect();
+        req.body = req.body.map(|body| match body {
+            RequestBody::Text { content } => RequestBody::Text {
+                content: self.interpolate(&content, &mut missing),
+            },
+            RequestBody::File { path } => RequestBody::File {
+                path: self.interpolate(&path, &mut missing),
+            },
+            RequestBody::Form { mut fields } => {
+                for field in &mut fields {
+                    field.name = self.interpolate(&field.name, &mut missing);
+                    field.value = self.interpolate(&field.value, &mut missing);
+                }
+                RequestBody::Form { fields }
+            }
+            RequestBody::Multipart { mut parts } => {
+                for part in &mut parts {
+                    part.name = self.interpolate(&part.name, &mut missing);
+                    part.value = part
+                        .value
+                        .as_ref()
+                        .map(|v| self.interpolate(v, &mut missing));
+                    part.file_path = part
+                        .file_path
+                        .as_ref()
+                        .map(|p| self.interpolate(p, &mut missing));
+                }
+                RequestBody::Multipart { parts }
+            }
+            // Raw bytes are sent as they are
+            body @ RequestBody::Base64 { .. } => body,
+        });
+
+        req.auth = req
+            .auth
+            .map(|auth| auth.map_strings(|s| self.interpolate(s, &mut missing)));
+
+        if missing.is_empty() {
+            Ok(req)
+        } else {
+            Err(format!("Unresolved variables: {}", missing.join(", ")))
+        }
+    }
+}
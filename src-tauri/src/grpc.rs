@@ -0,0 +1,892 @@
+use crate::auth::Auth;
+use crate::capture;
+use crate::client::{self, ClientOptions, ClientRequest, HttpVersion, RequestBody};
+use crate::db::{proto_files, requests};
+use crate::{AppState, ProxyEventPayload};
+use bytes::{Buf, BufMut, BytesMut};
+use http_body_util::{BodyExt, Limited};
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, Kind, MessageDescriptor, MethodDescriptor, SerializeOptions,
+    Value,
+};
+use protox::file::{ChainFileResolver, File, FileResolver, GoogleFileResolver};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Url;
+use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+const UNKNOWN: i32 = 2;
+const DEADLINE_EXCEEDED: i32 = 4;
+const PERMISSION_DENIED: i32 = 7;
+const RESOURCE_EXHAUSTED: i32 = 8;
+const UNIMPLEMENTED: i32 = 12;
+const INTERNAL: i32 = 13;
+const UNAVAILABLE: i32 = 14;
+const UNAUTHENTICATED: i32 = 16;
+
+// Streaming calls without a timeout are still cut off after this long
+const DEFAULT_DEADLINE: Duration = Duration::from_secs(60);
+// Responses are buffered, so long streams stop after this many messages
+const MAX_MESSAGES: usize = 10_000;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GrpcMethod {
+    pub name: String,
+    pub input_type: String,
+    pub output_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+    // The input message with every field at its default, to start editing from
+    pub input_example: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GrpcService {
+    pub name: String, // Fully qualified, e.g. "helloworld.Greeter"
+    pub file: String,
+    pub methods: Vec<GrpcMethod>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GrpcCatalog {
+    pub services: Vec<GrpcService>,
+    // Stored files that don't compile, "name: error"
+    pub errors: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GrpcRequest {
+    // Server base URL, http:// for h2c and https:// for TLS
+    pub url: String,
+    pub service: String,
+    pub method: String,
+    // One message, or an array of messages for client streaming methods
+    pub message: serde_json::Value,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub options: ClientOptions,
+    #[serde(default)]
+    pub auth: Option<Auth>,
+    #[serde(default)]
+    pub collection_id: Option<String>,
+    #[serde(default)]
+    pub environment_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GrpcResponse {
+    pub status: i32,
+    pub status_name: String,
+    pub status_message: Option<String>,
+    pub headers: HashMap<String, String>,
+    pub trailers: HashMap<String, String>,
+    pub messages: Vec<serde_json::Value>,
+    pub duration_ms: u64,
+    // Row in the shared requests history
+    pub request_id: String,
+}
+
+pub fn status_name(status: i32) -> String {
+    usize::try_from(status)
+        .ok()
+        .and_then(|i| STATUS_NAMES.get(i))
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("CODE_{}", status))
+}
+
+// Stored files are looked up by name, so imports use the names they were saved under
+struct StoredFiles(HashMap<String, String>);
+
+impl FileResolver for StoredFiles {
+    fn open_file(&self, name: &str) -> Result<File, protox::Error> {
+        match self.0.get(name) {
+            Some(source) => File::from_source(name, source),
+            None => Err(protox::Error::file_not_found(name)),
+        }
+    }
+}
+
+fn compiler(files: HashMap<String, String>) -> protox::Compiler {
+    let mut resolver = ChainFileResolver::new();
+    resolver.add(StoredFiles(files));
+    // google/protobuf/*.proto are always available
+    resolver.add(GoogleFileResolver::new());
+    protox::Compiler::with_file_resolver(resolver)
+}
+
+/// Compiles every stored file, skipping the ones with errors.
+fn compile(files: &[proto_files::Model]) -> (DescriptorPool, Vec<String>) {
+    let sources = files
+        .iter()
+        .map(|f| (f.name.clone(), f.content.clone()))
+        .collect();
+    let mut compiler = compiler(sources);
+    let mut errors = Vec::new();
+    for file in files {
+        if let Err(e) = compiler.open_file(&file.name) {
+            errors.push(format!("{}: {}", file.name, e));
+        }
+    }
+    (compiler.descriptor_pool(), errors)
+}
+
+pub async fn list_proto_files(db: &DatabaseConnection) -> Result<Vec<proto_files::Model>, String> {
+    proto_files::Entity::find()
+        .order_by_asc(proto_files::Column::Name)
+        .all(db)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Adds a file or replaces the one with the same name. Files that don't
+/// compile against the others are rejected.
+pub async fn save_proto_file(
+    db: &DatabaseConnection,
+    name: String,
+    content: String,
+) -> Result<proto_files::Model, String> {
+    let name = name.trim().trim_start_matches('/').replace('\\', "/");
+    if !name.ends_with(".proto") {
+        return Err("Proto file names must end with .proto".to_string());
+    }
+
+    let files = list_proto_files(db).await?;
+    let mut sources: HashMap<String, String> = files
+        .iter()
+        .map(|f| (f.name.clone(), f.content.clone()))
+        .collect();
+    sources.insert(name.clone(), content.clone());
+    compiler(sources)
+        .open_file(&name)
+        .map_err(|e| e.to_string())?;
+
+    let existing = files.into_iter().find(|f| f.name == name);
+    let model = proto_files::ActiveModel {
+        id: Set(existing
+            .as_ref()
+            .map(|f| f.id.clone())
+            .unwrap_or_else(|| Uuid::new_v4().to_string())),
+        name: Set(name),
+        content: Set(content),
+        added_at: Set(chrono::Utc::now().timestamp_millis()),
+    };
+    match existing {
+        Some(_) => model.update(db).await,
+        None => model.insert(db).await,
+    }
+    .map_err(|e| e.to_string())
+}
+
+pub async fn delete_proto_file(db: &DatabaseConnection, id: &str) -> Result<(), String> {
+    proto_files::Entity::delete_by_id(id.to_string())
+        .exec(db)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Services and methods defined by the stored proto files.
+pub async fn list_services(db: &DatabaseConnection) -> Result<GrpcCatalog, String> {
+    let files = list_proto_files(db).await?;
+    let (pool, errors) = compile(&files);
+    let defaults = SerializeOptions::new().skip_default_fields(false);
+
+    let mut services: Vec<GrpcService> = pool
+        .services()
+        .filter(|s| !s.parent_file().name().starts_with("google/protobuf/"))
+        .map(|service| GrpcService {
+            name: service.full_name().to_string(),
+            file: service.parent_file().name().to_string(),
+            methods: service
+                .methods()
+                .map(|method| GrpcMethod {
+                    name: method.name().to_string(),
+                    input_type: method.input().full_name().to_string(),
+                    output_type: method.output().full_name().to_string(),
+                    client_streaming: method.is_client_streaming(),
+                    server_streaming: method.is_server_streaming(),
+                    input_example: example_message(method.input(), 0)
+                        .serialize_with_options(serde_json::value::Serializer, &defaults)
+                        .unwrap_or_default(),
+                })
+                .collect(),
+        })
+        .collect();
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(GrpcCatalog { services, errors })
+}
+
+// Nested messages are filled in a few levels deep, recursive types would never end
+fn example_message(desc: MessageDescriptor, depth: usize) -> DynamicMessage {
+    let mut message = DynamicMessage::new(desc.clone());
+    if depth >= 3 {
+        return message;
+    }
+    for field in desc.fields() {
+        if field.is_list() || field.is_map() || field.containing_oneof().is_some() {
+            continue;
+        }
+        if let Kind::Message(nested) = field.kind() {
+            message.set_field(&field, Value::Message(example_message(nested, depth + 1)));
+        }
+    }
+    message
+}
+
+async fn find_method(
+    db: &DatabaseConnection,
+    service: &str,
+    method: &str,
+) -> Result<MethodDescriptor, String> {
+    let files = list_proto_files(db).await?;
+    let (pool, _) = compile(&files);
+    let service = pool
+        .get_service_by_name(service)
+        .ok_or_else(|| format!("Unknown gRPC service {}", service))?;
+    let found = service.methods().find(|m| m.name() == method);
+    found.ok_or_else(|| format!("{} has no method {}", service.full_name(), method))
+}
+
+// Length-prefixed messages, uncompressed
+fn encode_messages(
+    method: &MethodDescriptor,
+    message: serde_json::Value,
+) -> Result<Vec<u8>, String> {
+    let messages = match message {
+        serde_json::Value::Array(messages) if method.is_client_streaming() => messages,
+        _ if method.is_client_streaming() => {
+            return Err("Client streaming methods take an array of messages".to_string())
+        }
+        message => vec![message],
+    };
+
+    let mut framed = BytesMut::new();
+    for message in messages {
+        let message =
+            DynamicMessage::deserialize(method.input(), message).map_err(|e| e.to_string())?;
+        let encoded = message.encode_to_vec();
+        framed.put_u8(0);
+        framed.put_u32(encoded.len() as u32);
+        framed.put_slice(&encoded);
+    }
+    Ok(framed.to_vec())
+}
+
+fn decode_messages(
+    method: &MethodDescriptor,
+    buffer: &mut BytesMut,
+    messages: &mut Vec<serde_json::Value>,
+) -> Result<(), String> {
+    let options = SerializeOptions::new().skip_default_fields(false);
+    while buffer.len() >= 5 && messages.len() < MAX_MESSAGES {
+        let length = u32::from_be_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as usize;
+        if buffer.len() < 5 + length {
+            break;
+        }
+        let compressed = buffer[0] != 0;
+        buffer.advance(5);
+        let data = buffer.split_to(length).freeze();
+        if compressed {
+            return Err("The server sent a compressed message, which is not supported".to_string());
+        }
+        let message = DynamicMessage::decode(method.output(), data).map_err(|e| e.to_string())?;
+        messages.push(
+            message
+                .serialize_with_options(serde_json::value::Serializer, &options)
+                .map_err(|e| e.to_string())?,
+        );
+    }
+    Ok(())
+}
+
+fn header_map(headers: &HeaderMap) -> HashMap<String, String> {
+    headers
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+        .collect()
+}
+
+// grpc-message is percent-encoded
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn grpc_timeout(timeout: Duration) -> String {
+    format!("{}m", timeout.as_millis())
+}
+
+struct Exchange {
+    http_status: u16,
+    headers: HeaderMap,
+    trailers: HeaderMap,
+    messages: Vec<serde_json::Value>,
+    raw_body: Vec<u8>,
+    // Set when the deadline passed before the server finished
+    timed_out: bool,
+    // Set when the response outgrew the message or size limit
+    truncated: bool,
+}
+
+/// Calls a gRPC method, sending every request message before reading the
+/// responses, and records the call in the history.
+pub async fn call(state: &AppState, req: GrpcRequest) -> Result<GrpcResponse, String> {
+    let method = find_method(&state.db, &req.service, &req.method).await?;
+
+    // Variables, auth and OAuth tokens are resolved as for HTTP requests
+    let (resolved, variables) = client::resolve_request(
+        state,
+        ClientRequest {
+            method: "POST".to_string(),
+            url: req.url,
+            headers: req.metadata,
+            body: Some(RequestBody::Text {
+                content: req.message.to_string(),
+            }),
+            options: req.options,
+            auth: req.auth,
+            collection_id: req.collection_id,
+            environment_id: req.environment_id,
+        },
+    )
+    .await?;
+    let message = match &resolved.body {
+        Some(RequestBody::Text { content }) => {
+            serde_json::from_str(content).map_err(|e| e.to_string())?
+        }
+        _ => serde_json::Value::Null,
+    };
+    let body = encode_messages(&method, message)?;
+
+    let mut url = Url::parse(&resolved.url).map_err(|e| e.to_string())?;
+    let mut headers = HeaderMap::new();
+    for (k, v) in &resolved.headers {
+        let name = HeaderName::from_str(k).map_err(|e| e.to_string())?;
+        let value = HeaderValue::from_str(v).map_err(|e| e.to_string())?;
+        headers.insert(name, value);
+    }
+    match &resolved.auth {
+        Some(Auth::Digest { .. } | Auth::AwsSigV4 { .. }) => {
+            return Err("Digest and AWS SigV4 auth are not supported for gRPC".to_string())
+        }
+        Some(auth) => auth.prepare(&mut headers, &mut url)?,
+        None => {}
+    }
+    let path = format!(
+        "{}/{}/{}",
+        url.path().trim_end_matches('/'),
+        method.parent_service().full_name(),
+        method.name()
+    );
+    url.set_path(&path);
+
+    let timeout = resolved
+        .options
+        .timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_DEADLINE);
+    headers.insert("content-type", HeaderValue::from_static("application/grpc"));
+    headers.insert("te", HeaderValue::from_static("trailers"));
+    if let Ok(value) = HeaderValue::from_str(&grpc_timeout(timeout)) {
+        headers.insert("grpc-timeout", value);
+    }
+
+    // gRPC needs HTTP/2, without TLS it's spoken with prior knowledge
+    let mut options = resolved.options.clone();
+    options.http_version = if url.scheme() == "http" {
+        HttpVersion::H2c
+    } else {
+        HttpVersion::Http2
+    };
+
+    let id = Uuid::new_v4().to_string();
+    // Secrets don't end up in events or the history
+    let masked_url = variables.mask(url.as_str());
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    let start = Instant::now();
+    let _ = state.proxy_event_tx.send(ProxyEventPayload {
+        id: id.clone(),
+        method: "POST".to_string(),
+        url: masked_url.clone(),
+        status: None,
+        phase: "request".to_string(),
+        ..Default::default()
+    });
+
+    let request_headers = serde_json::to_string(&header_map(&headers)).unwrap_or_default();
+    let result = exchange(
+        state,
+        &options,
+        url,
+        headers,
+        body.clone(),
+        &method,
+        timeout,
+    )
+    .await;
+    let duration = start.elapsed().as_millis() as u64;
+
+    // The history keeps the HTTP status like for other requests, 0 when the
+    // call failed before a response arrived, and the gRPC status next to it
+    let result = result.map(|exchange| {
+        let status = grpc_status(&exchange);
+        (exchange, status)
+    });
+    let (http_status, code, response_headers, response_body) = match &result {
+        Ok((exchange, status)) => {
+            // Trailers are kept with the headers, grpc-status lives there
+            let mut all = header_map(&exchange.headers);
+            all.extend(header_map(&exchange.trailers));
+            (
+                exchange.http_status as i32,
+                status.as_ref().ok().map(|(code, _)| *code),
+                serde_json::to_string(&all).unwrap_or_default(),
+                exchange.raw_body.clone(),
+            )
+        }
+        Err(e) => (
+            0,
+            Some(UNAVAILABLE),
+            "".to_string(),
+            variables.mask(e).into_bytes(),
+        ),
+    };
+    let request_body = variables.mask_bytes(body);
+    let record = requests::ActiveModel {
+        id: Set(id.clone()),
+        method: Set("POST".to_string()),
+        url: Set(masked_url.clone()),
+        protocol: Set("grpc".to_string()),
+        request_headers: Set(variables.mask(&request_headers)),
+        request_body_size: Set(Some(request_body.len() as i64)),
+        request_body: Set(capture::truncate_for_storage(
+            &request_body,
+            capture::DEFAULT_MAX_CAPTURE_SIZE,
+        )),
+        response_status: Set(http_status),
+        grpc_status: Set(code),
+        response_headers: Set(response_headers),
+        response_body_size: Set(Some(response_body.len() as i64)),
+        response_body: Set(capture::truncate_for_storage(
+            &response_body,
+            capture::DEFAULT_MAX_CAPTURE_SIZE,
+        )),
+        duration: Set(duration as i64),
+        timestamp: Set(timestamp),
+        source: Set(Some("client".to_string())),
+        ..Default::default()
+    };
+    if let Err(e) = record.insert(&state.db).await {
+        eprintln!("Failed to store gRPC call: {}", e);
+    }
+
+    let _ = state.proxy_event_tx.send(ProxyEventPayload {
+        id: id.clone(),
+        method: "POST".to_string(),
+        url: masked_url,
+        status: Some(http_status),
+        phase: "response".to_string(),
+        ..Default::default()
+    });
+
+    let (exchange, status) = result?;
+    let (status, status_message) = status?;
+    Ok(GrpcResponse {
+        status,
+        status_name: status_name(status),
+        status_message,
+        headers: header_map(&exchange.headers),
+        trailers: header_map(&exchange.trailers),
+        messages: exchange.messages,
+        duration_ms: duration,
+        request_id: id,
+    })
+}
+
+fn grpc_status(exchange: &Exchange) -> Result<(i32, Option<String>), String> {
+    if exchange.timed_out {
+        return Ok((DEADLINE_EXCEEDED, Some("Deadline exceeded".to_string())));
+    }
+    if exchange.truncated {
+        return Ok((
+            RESOURCE_EXHAUSTED,
+            Some(format!(
+                "Stopped reading after {} messages or {} bytes",
+                MAX_MESSAGES,
+                capture::DEFAULT_MAX_CAPTURE_SIZE
+            )),
+        ));
+    }
+    // A trailers-only response carries the status in the headers
+    let source = if exchange.trailers.contains_key("grpc-status") {
+        &exchange.trailers
+    } else {
+        &exchange.headers
+    };
+    let status = source
+        .get("grpc-status")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i32>().ok());
+    let status = match status {
+        Some(status) => status,
+        // Proxies and load balancers answer errors without one
+        None if exchange.http_status != 200 => {
+            return Ok((
+                status_from_http(exchange.http_status),
+                Some(format!("HTTP {}", exchange.http_status)),
+            ))
+        }
+        None => return Err("The server ended the call without a grpc-status".to_string()),
+    };
+    let message = source
+        .get("grpc-message")
+        .and_then(|v| v.to_str().ok())
+        .map(percent_decode)
+        .filter(|m| !m.is_empty());
+    Ok((status, message))
+}
+
+// https://github.com/grpc/grpc/blob/master/doc/http-grpc-status-mapping.md
+fn status_from_http(status: u16) -> i32 {
+    match status {
+        400 => INTERNAL,
+        401 => UNAUTHENTICATED,
+        403 => PERMISSION_DENIED,
+        404 => UNIMPLEMENTED,
+        429 | 502 | 503 | 504 => UNAVAILABLE,
+        _ => UNKNOWN,
+    }
+}
+
+async fn exchange(
+    state: &AppState,
+    options: &ClientOptions,
+    url: Url,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    method: &MethodDescriptor,
+    timeout: Duration,
+) -> Result<Exchange, String> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let client = state.http_clients.get(options, &url)?;
+    let send = client.post(url).headers(headers).body(body).send();
+    let response = tokio::time::timeout_at(deadline, send)
+        .await
+        .map_err(|_| "Deadline exceeded before the server answered".to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let http_status = response.status().as_u16();
+    let response: http::Response<reqwest::Body> = response.into();
+    let (parts, body) = response.into_parts();
+    let mut exchange = Exchange {
+        http_status,
+        headers: parts.headers,
+        trailers: HeaderMap::new(),
+        messages: Vec::new(),
+        raw_body: Vec::new(),
+        timed_out: false,
+        truncated: false,
+    };
+
+    if http_status != 200 {
+        // Not a gRPC response, the body is only kept for the history
+        let limited = Limited::new(body, capture::DEFAULT_MAX_CAPTURE_SIZE);
+        if let Ok(Ok(collected)) = tokio::time::timeout_at(deadline, limited.collect()).await {
+            exchange.raw_body = collected.to_bytes().to_vec();
+        }
+        return Ok(exchange);
+    }
+    read_messages(&mut exchange, body, method, deadline).await?;
+    Ok(exchange)
+}
+
+// Server streaming responses are read until the trailers, the deadline or
+// one of the limits
+async fn read_messages(
+    exchange: &mut Exchange,
+    mut body: reqwest::Body,
+    method: &MethodDescriptor,
+    deadline: tokio::time::Instant,
+) -> Result<(), String> {
+    let mut buffer = BytesMut::new();
+    loop {
+        if exchange.messages.len() >= MAX_MESSAGES
+            || exchange.raw_body.len() >= capture::DEFAULT_MAX_CAPTURE_SIZE
+        {
+            exchange.truncated = true;
+            break;
+        }
+        let frame = match tokio::time::timeout_at(deadline, body.frame()).await {
+            Ok(frame) => frame,
+            Err(_) => {
+                exchange.timed_out = true;
+                break;
+            }
+        };
+        let Some(frame) = frame else {
+            break;
+        };
+        let frame = frame.map_err(|e| e.to_string())?;
+        match frame.into_data() {
+            Ok(data) => {
+                exchange.raw_body.extend_from_slice(&data);
+                buffer.extend_from_slice(&data);
+                decode_messages(method, &mut buffer, &mut exchange.messages)?;
+            }
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    exchange.trailers.extend(trailers);
+                }
+            }
+        }
+    }
+    if !buffer.is_empty() && !exchange.timed_out && !exchange.truncated {
+        return Err(format!(
+            "The response ended inside a message ({} bytes left)",
+            buffer.len()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROTO: &str = r#"
+        syntax = "proto3";
+        package test;
+        message Item {
+            string name = 1;
+            int32 count = 2;
+        }
+        service Items {
+            rpc Get(Item) returns (Item);
+            rpc Upload(stream Item) returns (Item);
+        }
+    "#;
+
+    fn method(name: &str) -> MethodDescriptor {
+        let file = proto_files::Model {
+            id: "1".to_string(),
+            name: "items.proto".to_string(),
+            content: PROTO.to_string(),
+            added_at: 0,
+        };
+        let (pool, errors) = compile(&[file]);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let service = pool.get_service_by_name("test.Items").unwrap();
+        let found = service.methods().find(|m| m.name() == name);
+        found.unwrap()
+    }
+
+    fn exchange(http_status: u16, headers: &[(&'static str, &'static str)]) -> Exchange {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        Exchange {
+            http_status,
+            headers: map,
+            trailers: HeaderMap::new(),
+            messages: Vec::new(),
+            raw_body: Vec::new(),
+            timed_out: false,
+            truncated: false,
+        }
+    }
+
+    fn deadline() -> tokio::time::Instant {
+        tokio::time::Instant::now() + Duration::from_secs(5)
+    }
+
+    #[test]
+    fn messages_are_length_prefixed() {
+        let method = method("Get");
+        let framed =
+            encode_messages(&method, serde_json::json!({"name": "a", "count": 1})).unwrap();
+        // Uncompressed, 5 bytes of payload: field 1 "a", field 2 = 1
+        assert_eq!(framed, [0, 0, 0, 0, 5, 0x0a, 1, b'a', 0x10, 1]);
+
+        let mut buffer = BytesMut::from(&framed[..]);
+        let mut messages = Vec::new();
+        decode_messages(&method, &mut buffer, &mut messages).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(messages, [serde_json::json!({"name": "a", "count": 1})]);
+    }
+
+    #[test]
+    fn client_streams_take_arrays() {
+        let method = method("Upload");
+        let framed = encode_messages(&method, serde_json::json!([{}, {"count": 2}])).unwrap();
+        assert_eq!(framed, [0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0x10, 2]);
+        assert!(encode_messages(&method, serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn partial_frames_wait_for_more_data() {
+        let method = method("Get");
+        let mut messages = Vec::new();
+
+        let mut buffer = BytesMut::from(&[0u8, 0, 0][..]);
+        decode_messages(&method, &mut buffer, &mut messages).unwrap();
+        assert_eq!(buffer.len(), 3);
+
+        let mut buffer = BytesMut::from(&[0u8, 0, 0, 0, 3, 0x0a, 1][..]);
+        decode_messages(&method, &mut buffer, &mut messages).unwrap();
+        assert_eq!(buffer.len(), 7);
+        assert!(messages.is_empty());
+
+        buffer.extend_from_slice(b"x");
+        decode_messages(&method, &mut buffer, &mut messages).unwrap();
+        assert!(buffer.is_empty());
+        assert_eq!(messages.len(), 1);
+    }
+
+    #[test]
+    fn compressed_messages_rejected() {
+        let method = method("Get");
+        let mut buffer = BytesMut::from(&[1u8, 0, 0, 0, 0][..]);
+        let err = decode_messages(&method, &mut buffer, &mut Vec::new()).unwrap_err();
+        assert!(err.contains("compressed"), "{}", err);
+    }
+
+    #[test]
+    fn status_from_trailers() {
+        let mut exchange = exchange(200, &[("grpc-status", "2")]);
+        exchange
+            .trailers
+            .insert("grpc-status", HeaderValue::from_static("5"));
+        exchange.trailers.insert(
+            "grpc-message",
+            HeaderValue::from_static("item%20%22a%22%20not%20found%E2%9C%93"),
+        );
+        assert_eq!(
+            grpc_status(&exchange).unwrap(),
+            (5, Some("item \"a\" not found\u{2713}".to_string()))
+        );
+    }
+
+    #[test]
+    fn trailers_only_status() {
+        let exchange = exchange(
+            200,
+            &[("grpc-status", "12"), ("grpc-message", "unknown%20method")],
+        );
+        assert_eq!(
+            grpc_status(&exchange).unwrap(),
+            (UNIMPLEMENTED, Some("unknown method".to_string()))
+        );
+        assert!(grpc_status(&self::exchange(200, &[])).is_err());
+    }
+
+    #[test]
+    fn http_errors_map_to_grpc_status() {
+        let cases = [
+            (400, INTERNAL),
+            (401, UNAUTHENTICATED),
+            (403, PERMISSION_DENIED),
+            (404, UNIMPLEMENTED),
+            (429, UNAVAILABLE),
+            (502, UNAVAILABLE),
+            (503, UNAVAILABLE),
+            (504, UNAVAILABLE),
+            (500, UNKNOWN),
+            (302, UNKNOWN),
+        ];
+        for (http, expected) in cases {
+            let (status, message) = grpc_status(&exchange(http, &[])).unwrap();
+            assert_eq!(status, expected, "HTTP {}", http);
+            assert_eq!(message, Some(format!("HTTP {}", http)));
+        }
+        // A grpc-status from the server still wins
+        let exchange = exchange(503, &[("grpc-status", "8")]);
+        assert_eq!(grpc_status(&exchange).unwrap().0, RESOURCE_EXHAUSTED);
+    }
+
+    #[tokio::test]
+    async fn response_ending_inside_a_message() {
+        let method = method("Get");
+        let mut exchange = exchange(200, &[]);
+        let body = reqwest::Body::from(vec![0u8, 0, 0, 0, 10, 0x0a]);
+        let err = read_messages(&mut exchange, body, &method, deadline())
+            .await
+            .unwrap_err();
+        assert!(err.contains("inside a message"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn message_count_is_limited() {
+        let method = method("Get");
+        let mut exchange = exchange(200, &[]);
+        // Empty messages, 5 bytes each
+        let body = reqwest::Body::from(vec![0u8; 5 * (MAX_MESSAGES + 10)]);
+        read_messages(&mut exchange, body, &method, deadline())
+            .await
+            .unwrap();
+        assert!(exchange.truncated);
+        assert_eq!(exchange.messages.len(), MAX_MESSAGES);
+        assert_eq!(grpc_status(&exchange).unwrap().0, RESOURCE_EXHAUSTED);
+    }
+
+    #[tokio::test]
+    async fn response_size_is_limited() {
+        let method = method("Get");
+        let mut exchange = exchange(200, &[]);
+        // One message announced larger than the limit
+        let size = capture::DEFAULT_MAX_CAPTURE_SIZE + 10;
+        let mut data = vec![0u8];
+        data.extend_from_slice(&(size as u32).to_be_bytes());
+        data.resize(capture::DEFAULT_MAX_CAPTURE_SIZE, 0);
+        read_messages(
+            &mut exchange,
+            reqwest::Body::from(data),
+            &method,
+            deadline(),
+        )
+        .await
+        .unwrap();
+        assert!(exchange.truncated);
+        assert!(exchange.messages.is_empty());
+        assert_eq!(grpc_status(&exchange).unwrap().0, RESOURCE_EXHAUSTED);
+    }
+}
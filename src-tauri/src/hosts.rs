@@ -0,0 +1,71 @@
+/// Matches a host against a pattern: an exact name, `*.example.com` for any
+/// subdomain, or `*` for every host. Case insensitive, ports are ignored.
+pub fn matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    let host = strip_port(host).trim_end_matches('.').to_ascii_lowercase();
+
+    if pattern == "*" {
+        return true;
+    }
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .strip_suffix(suffix)
+            .and_then(|rest| rest.strip_suffix('.'))
+            .is_some_and(|label| !label.is_empty()),
+        None => host == pattern.trim_end_matches('.'),
+    }
+}
+
+// "example.com:443" -> "example.com", "[::1]:8080" -> "::1"
+fn strip_port(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.parse::<u16>().is_ok() => name,
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_names() {
+        assert!(matches("api.example.com", "api.example.com"));
+        assert!(!matches("api.example.com", "www.example.com"));
+        assert!(!matches("api.example.com", "v1.api.example.com"));
+        assert!(matches("api.example.com.", "api.example.com"));
+        assert!(matches(" api.example.com ", "api.example.com."));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.example.com", "www.example.com"));
+        assert!(matches("*.example.com", "a.b.example.com"));
+        assert!(!matches("*.example.com", "example.com"));
+        assert!(!matches("*.example.com", "badexample.com"));
+        assert!(!matches("*.example.com", ".example.com"));
+        assert!(matches("*", "anything.test"));
+        assert!(matches("*", "127.0.0.1:8080"));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        assert!(matches("API.Example.COM", "api.example.com"));
+        assert!(matches("*.EXAMPLE.com", "WWW.example.COM"));
+    }
+
+    #[test]
+    fn ports_ignored() {
+        assert!(matches("example.com", "example.com:443"));
+        assert!(matches("*.example.com", "www.example.com:8443"));
+        assert!(matches("::1", "[::1]:8080"));
+        assert!(matches("::1", "[::1]"));
+        assert!(matches("127.0.0.1", "127.0.0.1:80"));
+        // A bare IPv6 address has no port to strip
+        assert!(matches("fe80::1", "fe80::1"));
+        assert!(!matches("example.com", "example.com:http"));
+    }
+}
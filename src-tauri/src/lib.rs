@@ -1,121 +1,632 @@
-use hudsucker::{certificate_authority::RcgenAuthority, Proxy};
-use sea_orm::DatabaseConnection;
-use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
-use tauri::{App, AppHandle, Manager, State};
-use tokio::sync::{broadcast, oneshot};
+use sea_orm::{DatabaseConnection, EntityTrait};
+use std::sync::Arc;
+use tauri::{App, Manager, State};
+use tokio::sync::broadcast;
 
+pub mod access;
+pub mod auth;
+pub mod blocklist;
+pub mod capture;
 pub mod certs;
 pub mod client;
+pub mod codegen;
+pub mod collections;
+pub mod cookies;
+pub mod curl;
 pub mod db;
+pub mod dns;
+pub mod environments;
+pub mod grpc;
+pub mod hosts;
+pub mod listeners;
+pub mod mitm;
+pub mod oauth;
+pub mod process;
 pub mod proxy;
 pub mod rewrites;
 pub mod server;
+pub mod socks;
+pub mod sse;
+pub mod stats;
+pub mod transparent;
+pub mod tunnel;
+pub mod upstream;
 
 // Define payload here or in proxy
-#[derive(Clone, serde::Serialize, Debug)]
+#[derive(Clone, serde::Serialize, Debug, Default)]
 pub struct ProxyEventPayload {
     pub id: String,
     pub method: String,
     pub url: String,
     pub status: Option<i32>,
     pub phase: String,
+    // Only set for phase "sse"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sse_event: Option<sse::SseEvent>,
 }
 
 pub struct AppState {
     pub db: DatabaseConnection,
-    pub proxy_shutdown_tx: Mutex<Option<oneshot::Sender<()>>>,
+    pub proxies: listeners::ProxyRegistry,
     pub proxy_event_tx: broadcast::Sender<ProxyEventPayload>,
     pub rewrite_manager: Arc<rewrites::RewriteManager>,
+    pub ca: Arc<certs::CaManager>,
+    pub client_certs: Arc<certs::CertStore>,
+    // Certificates presented to intercepted clients instead of generated ones
+    pub host_certs: Arc<certs::CertStore>,
+    pub dns: Arc<dns::DnsOverrides>,
+    pub block_list: Arc<blocklist::BlockList>,
+    pub http_clients: client::ClientPool,
+    pub oauth: Arc<oauth::OAuthManager>,
+    pub cookies: Arc<cookies::CookieJar>,
 }
 
+// A broken index shouldn't keep the app from starting. It's moved aside so
+// adding a certificate doesn't overwrite it.
+fn load_cert_store(dir: std::path::PathBuf) -> certs::CertStore {
+    certs::CertStore::load(dir.clone()).unwrap_or_else(|e| {
+        eprintln!("Failed to load certificates from {}: {}", dir.display(), e);
+        let index = dir.join("index.json");
+        if let Err(e) = std::fs::rename(&index, dir.join("index.json.broken")) {
+            eprintln!("Failed to move {} aside: {}", index.display(), e);
+        }
+        certs::CertStore::empty(dir)
+    })
+}
+
+#[tauri::command]
+async fn get_ca_cert(state: State<'_, Arc<AppState>>) -> Result<String, String> {
+    // The proxies sign with this CA, so this is the one clients must trust
+    Ok(state.ca.get_ca_pem())
+}
+
+#[tauri::command]
+async fn list_client_certs(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<certs::HostCert>, String> {
+    Ok(state.client_certs.list())
+}
+
+#[tauri::command]
+async fn add_client_cert(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    host_pattern: String,
+    source: certs::CertSource,
+) -> Result<certs::HostCert, String> {
+    state
+        .client_certs
+        .add(name, host_pattern, source)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn remove_client_cert(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.client_certs.remove(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_host_certs(state: State<'_, Arc<AppState>>) -> Result<Vec<certs::HostCert>, String> {
+    Ok(state.host_certs.list())
+}
+
+#[tauri::command]
+async fn add_host_cert(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    host_pattern: String,
+    source: certs::CertSource,
+) -> Result<certs::HostCert, String> {
+    state
+        .host_certs
+        .add(name, host_pattern, source)
+        .map_err(|e| e.to_string())
+}
+
+// Registers a deliberately invalid certificate for negative testing
 #[tauri::command]
-async fn get_ca_cert(app_handle: AppHandle) -> Result<String, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
+async fn generate_host_cert(
+    state: State<'_, Arc<AppState>>,
+    host: String,
+    kind: certs::BrokenCertKind,
+) -> Result<certs::HostCert, String> {
+    let pem = state
+        .ca
+        .generate_broken_cert(&host, kind)
         .map_err(|e| e.to_string())?;
-    let ca_manager = certs::CaManager::new(app_dir).map_err(|e| e.to_string())?;
-    Ok(ca_manager.get_ca_pem())
+    let name = format!("{:?} ({})", kind, host);
+    state
+        .host_certs
+        .add_pem(name, host, pem)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn remove_host_cert(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.host_certs.remove(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_dns_overrides(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<db::dns_overrides::Model>, String> {
+    state.dns.list().await
+}
+
+#[tauri::command]
+async fn add_dns_override(
+    state: State<'_, Arc<AppState>>,
+    host_pattern: String,
+    ip: String,
+) -> Result<db::dns_overrides::Model, String> {
+    state.dns.add(host_pattern, ip).await
+}
+
+#[tauri::command]
+async fn remove_dns_override(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.dns.remove(id).await
+}
+
+#[tauri::command]
+async fn list_block_rules(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<db::block_rules::Model>, String> {
+    state.block_list.list().await
+}
+
+#[tauri::command]
+async fn add_block_rule(
+    state: State<'_, Arc<AppState>>,
+    rule: blocklist::NewBlockRule,
+) -> Result<db::block_rules::Model, String> {
+    state.block_list.add(rule).await
+}
+
+#[tauri::command]
+async fn remove_block_rule(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.block_list.remove(id).await
+}
+
+#[tauri::command]
+async fn list_environments(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<db::environments::Model>, String> {
+    environments::list_environments(&state.db).await
+}
+
+#[tauri::command]
+async fn create_environment(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+) -> Result<db::environments::Model, String> {
+    environments::create_environment(&state.db, name).await
+}
+
+#[tauri::command]
+async fn rename_environment(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    name: String,
+) -> Result<db::environments::Model, String> {
+    environments::rename_environment(&state.db, &id, name).await
+}
+
+#[tauri::command]
+async fn delete_environment(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    environments::delete_environment(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn list_variables(
+    state: State<'_, Arc<AppState>>,
+    scope: environments::VariableScope,
+) -> Result<Vec<db::variables::Model>, String> {
+    environments::list_variables(&state.db, &scope).await
+}
+
+#[tauri::command]
+async fn set_variable(
+    state: State<'_, Arc<AppState>>,
+    scope: environments::VariableScope,
+    variable: environments::VariableInput,
+) -> Result<db::variables::Model, String> {
+    environments::set_variable(&state.db, scope, variable).await
+}
+
+#[tauri::command]
+async fn delete_variable(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    environments::delete_variable(&state.db, &id).await
+}
+
+// Runs the browser or device login, the returned flow id reports progress
+#[tauri::command]
+async fn start_oauth_flow(
+    state: State<'_, Arc<AppState>>,
+    config: oauth::OAuth2Config,
+    environment_id: Option<String>,
+) -> Result<oauth::FlowStart, String> {
+    match config.grant_type {
+        oauth::GrantType::AuthorizationCode => {
+            state
+                .oauth
+                .start_authorization(config, environment_id)
+                .await
+        }
+        oauth::GrantType::DeviceCode => state.oauth.start_device_flow(config, environment_id).await,
+        _ => Err("Only interactive grants use a flow, fetch the token instead".to_string()),
+    }
+}
+
+#[tauri::command]
+async fn get_oauth_flow(
+    state: State<'_, Arc<AppState>>,
+    flow_id: String,
+) -> Result<oauth::FlowStatus, String> {
+    state
+        .oauth
+        .flow_status(&flow_id)
+        .ok_or_else(|| format!("Unknown flow {}", flow_id))
+}
+
+#[tauri::command]
+async fn fetch_oauth_token(
+    state: State<'_, Arc<AppState>>,
+    config: oauth::OAuth2Config,
+    environment_id: Option<String>,
+) -> Result<db::oauth_tokens::Model, String> {
+    state
+        .oauth
+        .fetch_token(&config, environment_id.as_deref())
+        .await
+}
+
+#[tauri::command]
+async fn list_oauth_tokens(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<db::oauth_tokens::Model>, String> {
+    state.oauth.list_tokens().await
+}
+
+#[tauri::command]
+async fn remove_oauth_token(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.oauth.remove_token(id).await
+}
+
+#[tauri::command]
+async fn clear_oauth_tokens(
+    state: State<'_, Arc<AppState>>,
+    environment_id: Option<String>,
+) -> Result<(), String> {
+    state.oauth.clear_tokens(environment_id).await
+}
+
+#[tauri::command]
+async fn list_cookies(
+    state: State<'_, Arc<AppState>>,
+    domain: Option<String>,
+) -> Result<Vec<db::cookies::Model>, String> {
+    Ok(state.cookies.list(domain.as_deref()))
+}
+
+#[tauri::command]
+async fn set_cookie(
+    state: State<'_, Arc<AppState>>,
+    cookie: cookies::CookieInput,
+) -> Result<db::cookies::Model, String> {
+    state.cookies.set(cookie).await
+}
+
+#[tauri::command]
+async fn remove_cookie(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    state.cookies.remove(id).await
+}
+
+#[tauri::command]
+async fn clear_cookies(
+    state: State<'_, Arc<AppState>>,
+    domain: Option<String>,
+) -> Result<(), String> {
+    state.cookies.clear(domain).await
+}
+
+#[tauri::command]
+async fn import_curl(command: String) -> Result<client::ClientRequest, String> {
+    curl::parse_curl(&command)
+}
+
+// Exports what would be sent, with variables and OAuth tokens resolved
+#[tauri::command]
+async fn export_curl(
+    state: State<'_, Arc<AppState>>,
+    req: client::ClientRequest,
+) -> Result<String, String> {
+    let (req, _) = client::resolve_request(&state, req).await?;
+    Ok(curl::to_curl(&req))
+}
+
+#[tauri::command]
+async fn export_saved_request_curl(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    environment_id: Option<String>,
+) -> Result<String, String> {
+    let saved = collections::get_saved_request(&state.db, &id).await?;
+    let req = collections::to_client_request(&saved, environment_id)?;
+    let (req, _) = client::resolve_request(&state, req).await?;
+    Ok(curl::to_curl(&req))
+}
+
+#[tauri::command]
+async fn export_captured_request_curl(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<String, String> {
+    let record = db::requests::Entity::find_by_id(id.clone())
+        .one(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown request {}", id))?;
+    Ok(curl::record_to_curl(&record))
+}
+
+#[tauri::command]
+async fn generate_code(
+    state: State<'_, Arc<AppState>>,
+    req: client::ClientRequest,
+    target: codegen::Target,
+) -> Result<String, String> {
+    let (req, _) = client::resolve_request(&state, req).await?;
+    Ok(codegen::generate(&req, target))
+}
+
+#[tauri::command]
+async fn generate_saved_request_code(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    environment_id: Option<String>,
+    target: codegen::Target,
+) -> Result<String, String> {
+    let saved = collections::get_saved_request(&state.db, &id).await?;
+    let req = collections::to_client_request(&saved, environment_id)?;
+    let (req, _) = client::resolve_request(&state, req).await?;
+    Ok(codegen::generate(&req, target))
+}
+
+#[tauri::command]
+async fn generate_captured_request_code(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    target: codegen::Target,
+) -> Result<String, String> {
+    let record = db::requests::Entity::find_by_id(id.clone())
+        .one(&state.db)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Unknown request {}", id))?;
+    Ok(codegen::generate_for_record(&record, target))
+}
+
+#[tauri::command]
+async fn list_proto_files(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<db::proto_files::Model>, String> {
+    grpc::list_proto_files(&state.db).await
+}
+
+#[tauri::command]
+async fn save_proto_file(
+    state: State<'_, Arc<AppState>>,
+    name: String,
+    content: String,
+) -> Result<db::proto_files::Model, String> {
+    grpc::save_proto_file(&state.db, name, content).await
+}
+
+#[tauri::command]
+async fn delete_proto_file(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    grpc::delete_proto_file(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn list_grpc_services(state: State<'_, Arc<AppState>>) -> Result<grpc::GrpcCatalog, String> {
+    grpc::list_services(&state.db).await
+}
+
+#[tauri::command]
+async fn send_grpc_request(
+    state: State<'_, Arc<AppState>>,
+    req: grpc::GrpcRequest,
+) -> Result<grpc::GrpcResponse, String> {
+    grpc::call(&state, req).await
+}
+
+#[tauri::command]
+async fn list_collections(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<db::collections::Model>, String> {
+    collections::list_collections(&state.db).await
+}
+
+#[tauri::command]
+async fn get_collection(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<collections::CollectionTree, String> {
+    collections::get_collection_tree(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn create_collection(
+    state: State<'_, Arc<AppState>>,
+    collection: collections::CollectionInput,
+) -> Result<db::collections::Model, String> {
+    collections::create_collection(&state.db, collection).await
+}
+
+#[tauri::command]
+async fn update_collection(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    collection: collections::CollectionInput,
+) -> Result<db::collections::Model, String> {
+    collections::update_collection(&state.db, &id, collection).await
+}
+
+#[tauri::command]
+async fn delete_collection(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    collections::delete_collection(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn reorder_collection(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    position: usize,
+) -> Result<Vec<db::collections::Model>, String> {
+    collections::reorder_collection(&state.db, &id, position).await
+}
+
+#[tauri::command]
+async fn duplicate_collection(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<collections::CollectionTree, String> {
+    collections::duplicate_collection(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn create_folder(
+    state: State<'_, Arc<AppState>>,
+    folder: collections::FolderInput,
+) -> Result<db::folders::Model, String> {
+    collections::create_folder(&state.db, folder).await
+}
+
+#[tauri::command]
+async fn rename_folder(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    name: String,
+) -> Result<db::folders::Model, String> {
+    collections::rename_folder(&state.db, &id, name).await
+}
+
+#[tauri::command]
+async fn delete_folder(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    collections::delete_folder(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn move_folder(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    target: collections::MoveTarget,
+) -> Result<db::folders::Model, String> {
+    collections::move_folder(&state.db, &id, target).await
+}
+
+#[tauri::command]
+async fn duplicate_folder(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<db::folders::Model, String> {
+    collections::duplicate_folder(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn get_saved_request(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<db::saved_requests::Model, String> {
+    collections::get_saved_request(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn create_saved_request(
+    state: State<'_, Arc<AppState>>,
+    request: collections::NewSavedRequest,
+) -> Result<db::saved_requests::Model, String> {
+    collections::create_saved_request(&state.db, request).await
+}
+
+#[tauri::command]
+async fn update_saved_request(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    request: collections::SavedRequestInput,
+) -> Result<db::saved_requests::Model, String> {
+    collections::update_saved_request(&state.db, &id, request).await
+}
+
+#[tauri::command]
+async fn delete_saved_request(state: State<'_, Arc<AppState>>, id: String) -> Result<(), String> {
+    collections::delete_saved_request(&state.db, &id).await
+}
+
+#[tauri::command]
+async fn move_saved_request(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+    target: collections::MoveTarget,
+) -> Result<db::saved_requests::Model, String> {
+    collections::move_saved_request(&state.db, &id, target).await
+}
+
+#[tauri::command]
+async fn duplicate_saved_request(
+    state: State<'_, Arc<AppState>>,
+    id: String,
+) -> Result<db::saved_requests::Model, String> {
+    collections::duplicate_saved_request(&state.db, &id).await
 }
 
 #[tauri::command]
 async fn start_proxy(
     state: State<'_, Arc<AppState>>,
-    app_handle: AppHandle,
     port: u16,
+    id: Option<String>,
+    options: Option<proxy::ProxyOptions>,
 ) -> Result<String, String> {
-    let mut guard = state.proxy_shutdown_tx.lock().unwrap();
-    if guard.is_some() {
-        return Err("Proxy already running".into());
-    }
+    let id = id.unwrap_or_else(|| listeners::DEFAULT_LISTENER_ID.to_string());
+    let status = state
+        .proxies
+        .start(&state, id, port, options.unwrap_or_default())?;
 
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?;
+    Ok(format!(
+        "Proxy {} started on {}",
+        status.id, status.bound_address
+    ))
+}
 
-    // Silence warning
-    let _ca_manager = certs::CaManager::new(app_dir.clone()).map_err(|e| e.to_string())?;
-
-    // REGENERATE STRATEGY
-    let mut params = rcgen::CertificateParams::default();
-    params
-        .distinguished_name
-        .push(rcgen::DnType::CommonName, "Yuri Proxy CA");
-    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Constrained(0));
-
-    let key_pair = rcgen::KeyPair::generate().map_err(|e| e.to_string())?;
-    let cert = params.self_signed(&key_pair).map_err(|e| e.to_string())?;
-
-    let cert_der = cert.der().to_vec();
-    let key_der = key_pair.serialize_der();
-
-    let private_key = hudsucker::rustls::PrivateKey(key_der);
-    let ca_cert = hudsucker::rustls::Certificate(cert_der);
-
-    let ca = RcgenAuthority::new(private_key, ca_cert, 1000).map_err(|e| e.to_string())?;
-
-    let handler = proxy::ProxyHandler {
-        db: state.db.clone(),
-        pending_ids: Arc::new(Mutex::new(std::collections::VecDeque::new())),
-        event_tx: state.proxy_event_tx.clone(),
-        rewrite_manager: state.rewrite_manager.clone(),
-    };
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], port));
-    let proxy = Proxy::builder()
-        .with_addr(addr)
-        .with_rustls_client()
-        .with_ca(ca)
-        .with_http_handler(handler)
-        .build();
-
-    let (tx, rx) = oneshot::channel();
-    *guard = Some(tx);
-
-    tauri::async_runtime::spawn(async move {
-        if let Err(e) = proxy
-            .start(async {
-                rx.await.ok();
-                println!("Proxy shutdown signal received");
-            })
-            .await
-        {
-            eprintln!("Proxy failed: {}", e);
-        }
-    });
+// Without an id every running proxy is stopped
+#[tauri::command]
+async fn stop_proxy(
+    state: State<'_, Arc<AppState>>,
+    id: Option<String>,
+) -> Result<Vec<listeners::ListenerStatus>, String> {
+    match id {
+        Some(id) => state.proxies.stop(&id).map(|status| vec![status]),
+        None => Ok(state.proxies.stop_all()),
+    }
+}
 
-    Ok(format!("Proxy started on {}", port))
+#[tauri::command]
+async fn get_proxy_status(
+    state: State<'_, Arc<AppState>>,
+    id: Option<String>,
+) -> Result<listeners::ListenerStatus, String> {
+    let id = id.unwrap_or_else(|| listeners::DEFAULT_LISTENER_ID.to_string());
+    state
+        .proxies
+        .get(&id)
+        .ok_or_else(|| format!("Unknown proxy '{}'", id))
 }
 
 #[tauri::command]
-async fn stop_proxy(state: State<'_, AppState>) -> Result<(), String> {
-    let mut guard = state.proxy_shutdown_tx.lock().unwrap();
-    if let Some(tx) = guard.take() {
-        let _ = tx.send(());
-    }
-    Ok(())
+async fn list_proxies(
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<listeners::ListenerStatus>, String> {
+    Ok(state.proxies.list())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -128,6 +639,10 @@ pub fn run() {
 
             println!("App dir: {}", app_dir.display());
 
+            let ca = Arc::new(certs::CaManager::new(app_dir.clone()).expect("failed to load CA"));
+            let client_certs = Arc::new(load_cert_store(app_dir.join("client_certs")));
+            let host_certs = Arc::new(load_cert_store(app_dir.join("host_certs")));
+
             tauri::async_runtime::block_on(async move {
                 let db = db::init_db(app_dir).await.expect("failed to init db");
                 let (tx, _rx) = broadcast::channel(100);
@@ -135,11 +650,31 @@ pub fn run() {
                 let rewrite_manager = Arc::new(rewrites::RewriteManager::new(db.clone()));
                 rewrite_manager.load_rules().await;
 
+                let dns = Arc::new(dns::DnsOverrides::new(db.clone()));
+                dns.load().await;
+
+                let block_list = Arc::new(blocklist::BlockList::new(db.clone()));
+                block_list.load().await;
+
+                let cookies = Arc::new(cookies::CookieJar::new(db.clone()));
+                cookies.load().await;
+
+                let http_clients = client::ClientPool::new(dns.clone(), client_certs.clone());
+                let oauth = Arc::new(oauth::OAuthManager::new(db.clone(), dns.clone()));
+
                 let state = Arc::new(AppState {
                     db,
-                    proxy_shutdown_tx: Mutex::new(None),
+                    proxies: listeners::ProxyRegistry::default(),
                     proxy_event_tx: tx,
                     rewrite_manager,
+                    ca,
+                    client_certs,
+                    host_certs,
+                    dns,
+                    block_list,
+                    http_clients,
+                    oauth,
+                    cookies,
                 });
 
                 app_handle.manage(state.clone());
@@ -154,6 +689,68 @@ pub fn run() {
             get_ca_cert,
             start_proxy,
             stop_proxy,
+            list_proxies,
+            get_proxy_status,
+            list_client_certs,
+            add_client_cert,
+            remove_client_cert,
+            list_host_certs,
+            add_host_cert,
+            generate_host_cert,
+            remove_host_cert,
+            list_dns_overrides,
+            add_dns_override,
+            remove_dns_override,
+            list_block_rules,
+            add_block_rule,
+            remove_block_rule,
+            list_environments,
+            create_environment,
+            rename_environment,
+            delete_environment,
+            list_variables,
+            set_variable,
+            delete_variable,
+            start_oauth_flow,
+            get_oauth_flow,
+            fetch_oauth_token,
+            list_oauth_tokens,
+            remove_oauth_token,
+            clear_oauth_tokens,
+            list_cookies,
+            set_cookie,
+            remove_cookie,
+            clear_cookies,
+            import_curl,
+            export_curl,
+            export_saved_request_curl,
+            export_captured_request_curl,
+            generate_code,
+            generate_saved_request_code,
+            generate_captured_request_code,
+            list_proto_files,
+            save_proto_file,
+            delete_proto_file,
+            list_grpc_services,
+            send_grpc_request,
+            list_collections,
+            get_collection,
+            create_collection,
+            update_collection,
+            delete_collection,
+            reorder_collection,
+            duplicate_collection,
+            create_folder,
+            rename_folder,
+            delete_folder,
+            move_folder,
+            duplicate_folder,
+            get_saved_request,
+            create_saved_request,
+            update_saved_request,
+            delete_saved_request,
+            move_saved_request,
+            duplicate_saved_request,
             client::send_request
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,273 @@
+use crate::proxy::{ProxyHandler, ProxyMode, ProxyOptions};
+use crate::stats::{ProxyStats, StatsSnapshot};
+use crate::{access, mitm, process, socks, transparent, tunnel, upstream, AppState};
+use hudsucker::Proxy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+pub const DEFAULT_LISTENER_ID: &str = "default";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListenerState {
+    Running,
+    Stopping,
+    Stopped,
+    Failed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ListenerStatus {
+    pub id: String,
+    pub port: u16,
+    // Address actually bound, resolves port 0 to the chosen port
+    pub bound_address: String,
+    pub state: ListenerState,
+    pub started_at: i64,
+    pub stopped_at: Option<i64>,
+    pub last_error: Option<String>,
+    pub options: ProxyOptions,
+    pub stats: StatsSnapshot,
+}
+
+struct ListenerEntry {
+    // Tells a finished task whether its entry was already replaced
+    instance: String,
+    status: ListenerStatus,
+    stats: Arc<ProxyStats>,
+    shutdown_tx: Option<oneshot::Sender<()>>,
+}
+
+impl ListenerEntry {
+    fn status(&self) -> ListenerStatus {
+        let mut status = self.status.clone();
+        status.stats = self.stats.snapshot();
+        status
+    }
+
+    fn is_active(&self) -> bool {
+        matches!(
+            self.status.state,
+            ListenerState::Running | ListenerState::Stopping
+        )
+    }
+}
+
+/// Named proxy listeners that run side by side with their own settings.
+///
+/// Stopped and failed listeners stay listed with their final stats until
+/// they are started again.
+#[derive(Default)]
+pub struct ProxyRegistry {
+    listeners: Arc<Mutex<HashMap<String, ListenerEntry>>>,
+}
+
+impl ProxyRegistry {
+    pub fn list(&self) -> Vec<ListenerStatus> {
+        let listeners = self.listeners.lock().unwrap();
+        let mut statuses: Vec<_> = listeners.values().map(|l| l.status()).collect();
+        statuses.sort_by(|a, b| a.id.cmp(&b.id));
+        statuses
+    }
+
+    pub fn get(&self, id: &str) -> Option<ListenerStatus> {
+        let listeners = self.listeners.lock().unwrap();
+        listeners.get(id).map(|l| l.status())
+    }
+
+    pub fn stop(&self, id: &str) -> Result<ListenerStatus, String> {
+        let mut listeners = self.listeners.lock().unwrap();
+        let entry = listeners
+            .get_mut(id)
+            .filter(|l| l.shutdown_tx.is_some())
+            .ok_or_else(|| format!("Proxy '{}' is not running", id))?;
+
+        if let Some(tx) = entry.shutdown_tx.take() {
+            let _ = tx.send(());
+        }
+        entry.status.state = ListenerState::Stopping;
+        Ok(entry.status())
+    }
+
+    pub fn stop_all(&self) -> Vec<ListenerStatus> {
+        let mut listeners = self.listeners.lock().unwrap();
+        listeners
+            .values_mut()
+            .filter_map(|entry| {
+                let tx = entry.shutdown_tx.take()?;
+                let _ = tx.send(());
+                entry.status.state = ListenerState::Stopping;
+                Some(entry.status())
+            })
+            .collect()
+    }
+
+    pub fn start(
+        &self,
+        state: &AppState,
+        id: String,
+        port: u16,
+        options: ProxyOptions,
+    ) -> Result<ListenerStatus, String> {
+        options.validate()?;
+
+        let mut listeners = self.listeners.lock().unwrap();
+        if listeners.get(&id).map(|l| l.is_active()) == Some(true) {
+            return Err(format!("Proxy '{}' already running", id));
+        }
+
+        let ca = mitm::InterceptAuthority::new(&state.ca, state.host_certs.clone())?;
+
+        let access = access::AccessControl::new(options.auth.clone(), &options.allowed_ips)?;
+        let stats = Arc::new(ProxyStats::default());
+        let bridges = Arc::new(tunnel::Bridges::default());
+        let handler = ProxyHandler {
+            db: state.db.clone(),
+            pending_ids: Arc::new(Mutex::new(std::collections::VecDeque::new())),
+            event_tx: state.proxy_event_tx.clone(),
+            rewrite_manager: state.rewrite_manager.clone(),
+            options: Arc::new(options.clone()),
+            bridges: bridges.clone(),
+            access: Arc::new(access),
+            listener_id: id.clone(),
+            stats: stats.clone(),
+            processes: Arc::new(process::ProcessResolver::default()),
+            block_list: state.block_list.clone(),
+            tunnel: None,
+        };
+
+        // Bind right away so a taken port is reported to the caller instead
+        // of failing later in the background task
+        let addr = SocketAddr::new(options.bind_address, port);
+        let public = std::net::TcpListener::bind(addr)
+            .map_err(|e| format!("Failed to bind {}: {}", addr, e))?;
+        let bound_addr = public.local_addr().map_err(|e| e.to_string())?;
+
+        // Where our own listeners reach the HTTP proxy
+        let mut proxy_addr = loopback_for(bound_addr);
+        let mut transparent_listener = None;
+        let builder = if let ProxyMode::Transparent = options.mode {
+            // Redirected traffic lands on `port`, the HTTP proxy itself only
+            // listens on an internal loopback port
+            public.set_nonblocking(true).map_err(|e| e.to_string())?;
+            let internal =
+                std::net::TcpListener::bind(("127.0.0.1", 0)).map_err(|e| e.to_string())?;
+            proxy_addr = internal.local_addr().map_err(|e| e.to_string())?;
+            transparent_listener = Some(public);
+            Proxy::builder().with_listener(internal)
+        } else {
+            Proxy::builder().with_listener(public)
+        };
+
+        let socks_listener = match &options.socks {
+            Some(socks) => {
+                let socks_addr = SocketAddr::new(options.bind_address, socks.port);
+                let listener = std::net::TcpListener::bind(socks_addr)
+                    .map_err(|e| format!("Failed to bind SOCKS5 {}: {}", socks_addr, e))?;
+                listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+                Some((listener, Arc::new(socks.clone())))
+            }
+            None => None,
+        };
+
+        let proxy = builder
+            .with_client(upstream::client(
+                state.client_certs.clone(),
+                state.dns.clone(),
+            ))
+            .with_ca(ca)
+            .with_http_handler(handler)
+            .build();
+
+        let (tx, rx) = oneshot::channel();
+        let instance = Uuid::new_v4().to_string();
+        let status = ListenerStatus {
+            id: id.clone(),
+            port: bound_addr.port(),
+            bound_address: bound_addr.to_string(),
+            state: ListenerState::Running,
+            started_at: chrono::Utc::now().timestamp_millis(),
+            stopped_at: None,
+            last_error: None,
+            options,
+            stats: StatsSnapshot::default(),
+        };
+        listeners.insert(
+            id.clone(),
+            ListenerEntry {
+                instance: instance.clone(),
+                status: status.clone(),
+                stats,
+                shutdown_tx: Some(tx),
+            },
+        );
+
+        let registry = self.listeners.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut listener_tasks = Vec::new();
+            if let Some(public) = transparent_listener {
+                if let Ok(listener) = tokio::net::TcpListener::from_std(public) {
+                    listener_tasks.push(tokio::spawn(transparent::run(
+                        listener,
+                        proxy_addr,
+                        bridges.clone(),
+                    )));
+                }
+            }
+            if let Some((listener, socks)) = socks_listener {
+                if let Ok(listener) = tokio::net::TcpListener::from_std(listener) {
+                    listener_tasks.push(tokio::spawn(socks::run(
+                        listener,
+                        proxy_addr,
+                        bridges.clone(),
+                        socks,
+                    )));
+                }
+            }
+
+            let result = proxy
+                .start(async {
+                    rx.await.ok();
+                    println!("Proxy {} shutdown signal received", id);
+                })
+                .await;
+
+            for task in listener_tasks {
+                task.abort();
+            }
+
+            let mut listeners = registry.lock().unwrap();
+            if let Some(entry) = listeners.get_mut(&id).filter(|l| l.instance == instance) {
+                entry.shutdown_tx = None;
+                entry.status.stopped_at = Some(chrono::Utc::now().timestamp_millis());
+                match result {
+                    Ok(()) => entry.status.state = ListenerState::Stopped,
+                    Err(e) => {
+                        eprintln!("Proxy {} failed: {}", id, e);
+                        entry.status.state = ListenerState::Failed;
+                        entry.status.last_error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+
+        Ok(status)
+    }
+}
+
+// Listening on all interfaces still has to be dialed through a concrete address
+fn loopback_for(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::from((Ipv4Addr::LOCALHOST, addr.port()))
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::from((Ipv6Addr::LOCALHOST, addr.port()))
+        }
+        _ => addr,
+    }
+}
@@ -0,0 +1,66 @@
+use crate::certs::{self, CaManager, CertStore, StoredCert};
+use hudsucker::{
+    async_trait::async_trait,
+    certificate_authority::{CertificateAuthority, RcgenAuthority},
+    hyper::http::uri::Authority,
+    rustls::ServerConfig,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Certificates presented to intercepted clients: generated by our CA unless
+/// a certificate was registered for the host.
+pub struct InterceptAuthority {
+    generated: RcgenAuthority,
+    overrides: Arc<CertStore>,
+    // Server configs of override certificates, by certificate id
+    configs: Mutex<HashMap<String, Arc<ServerConfig>>>,
+}
+
+impl InterceptAuthority {
+    pub fn new(ca: &CaManager, overrides: Arc<CertStore>) -> Result<Self, String> {
+        let (mut chain, key) = certs::parse_identity(&format!("{}\n{}", ca.cert_pem, ca.key_pem))
+            .map_err(|e| e.to_string())?;
+        let generated =
+            RcgenAuthority::new(key, chain.remove(0), 1000).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            generated,
+            overrides,
+            configs: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn override_config(&self, cert: &StoredCert) -> Result<Arc<ServerConfig>, String> {
+        let mut configs = self.configs.lock().unwrap();
+        if let Some(config) = configs.get(&cert.info.id) {
+            return Ok(config.clone());
+        }
+
+        let (chain, key) = cert.rustls_identity().map_err(|e| e.to_string())?;
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .map_err(|e| e.to_string())?;
+        // Same protocols as the generated certificates
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let config = Arc::new(config);
+        configs.insert(cert.info.id.clone(), config.clone());
+        Ok(config)
+    }
+}
+
+#[async_trait]
+impl CertificateAuthority for InterceptAuthority {
+    async fn gen_server_config(&self, authority: &Authority) -> Arc<ServerConfig> {
+        if let Some(cert) = self.overrides.find(authority.host()) {
+            match self.override_config(&cert) {
+                Ok(config) => return config,
+                Err(e) => eprintln!("Certificate override {} unusable: {}", cert.info.name, e),
+            }
+        }
+        self.generated.gen_server_config(authority).await
+    }
+}
@@ -0,0 +1,720 @@
+use crate::auth::Auth;
+use crate::db::oauth_tokens;
+use crate::dns::{DnsOverrides, OverrideResolver};
+use crate::environments::SECRET_MASK;
+use axum::{extract::Query, routing::get, Router};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::Url;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+// Tokens this close to expiry are refreshed before use
+const EXPIRY_SKEW_MS: i64 = 30_000;
+// How long to wait for the user to finish a browser or device login
+const FLOW_TIMEOUT: Duration = Duration::from_secs(300);
+// Finished flows are forgotten once the UI had this long to poll them
+const FLOW_RETENTION: Duration = Duration::from_secs(600);
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    ClientCredentials,
+    AuthorizationCode,
+    DeviceCode,
+    RefreshToken,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub grant_type: GrantType,
+    pub token_url: String,
+    // Authorization code flow
+    pub authorization_url: Option<String>,
+    // Device flow
+    pub device_authorization_url: Option<String>,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+    // Loopback port for the authorization code redirect, 0 or None picks one
+    pub redirect_port: Option<u16>,
+    // Seed for the refresh token grant
+    pub refresh_token: Option<String>,
+}
+
+impl OAuth2Config {
+    pub fn map_strings(self, mut f: impl FnMut(&str) -> String) -> Self {
+        OAuth2Config {
+            grant_type: self.grant_type,
+            token_url: f(&self.token_url),
+            authorization_url: self.authorization_url.map(|u| f(&u)),
+            device_authorization_url: self.device_authorization_url.map(|u| f(&u)),
+            client_id: f(&self.client_id),
+            client_secret: self.client_secret.map(|s| f(&s)),
+            scope: self.scope.map(|s| f(&s)),
+            redirect_port: self.redirect_port,
+            refresh_token: self.refresh_token.map(|t| f(&t)),
+        }
+    }
+
+    // Tokens are shared by requests with the same client and scope
+    fn cache_key(&self, environment_id: Option<&str>) -> String {
+        let key = format!(
+            "{}\n{}\n{}\n{}",
+            environment_id.unwrap_or_default(),
+            self.token_url,
+            self.client_id,
+            self.scope.as_deref().unwrap_or_default()
+        );
+        Sha256::digest(key.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: Option<String>,
+    expires_in: Option<i64>,
+    refresh_token: Option<String>,
+    scope: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenError {
+    error: String,
+    error_description: Option<String>,
+}
+
+/// What the user has to do to finish an interactive flow.
+#[derive(Clone, Debug, Serialize)]
+pub struct FlowStart {
+    pub flow_id: String,
+    // Authorization code: open this in a browser
+    pub authorization_url: Option<String>,
+    pub redirect_uri: Option<String>,
+    // Device flow: enter the code at the verification URI
+    pub user_code: Option<String>,
+    pub verification_uri: Option<String>,
+    pub verification_uri_complete: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "state", content = "error", rename_all = "lowercase")]
+pub enum FlowStatus {
+    Pending,
+    Complete,
+    Failed(String),
+}
+
+#[derive(Deserialize)]
+struct DeviceAuthorization {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: Option<u64>,
+    interval: Option<u64>,
+}
+
+/// Acquires, caches and refreshes OAuth 2.0 tokens for the API client.
+pub struct OAuthManager {
+    db: DatabaseConnection,
+    http: reqwest::Client,
+    flows: Mutex<HashMap<String, (FlowStatus, Instant)>>,
+}
+
+impl OAuthManager {
+    pub fn new(db: DatabaseConnection, dns: Arc<DnsOverrides>) -> Self {
+        let http = match reqwest::Client::builder()
+            .dns_resolver(Arc::new(OverrideResolver::new(dns)))
+            .build()
+        {
+            Ok(http) => http,
+            Err(e) => {
+                eprintln!(
+                    "Failed to build OAuth client, DNS overrides won't apply: {}",
+                    e
+                );
+                reqwest::Client::new()
+            }
+        };
+        Self {
+            db,
+            http,
+            flows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replaces OAuth 2.0 auth with the bearer token it stands for.
+    pub async fn resolve(
+        &self,
+        auth: Option<Auth>,
+        environment_id: Option<&str>,
+    ) -> Result<Option<Auth>, String> {
+        match auth {
+            Some(Auth::OAuth2(config)) => {
+                let token = self.access_token(&config, environment_id).await?;
+                Ok(Some(Auth::Bearer { token }))
+            }
+            auth => Ok(auth),
+        }
+    }
+
+    /// A valid access token, refreshed or fetched when needed. Interactive
+    /// grants have to be authorized first.
+    pub async fn access_token(
+        &self,
+        config: &OAuth2Config,
+        environment_id: Option<&str>,
+    ) -> Result<String, String> {
+        let id = config.cache_key(environment_id);
+        let cached = oauth_tokens::Entity::find_by_id(id)
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(token) = &cached {
+            let fresh = token
+                .expires_at
+                .is_none_or(|expires| expires - EXPIRY_SKEW_MS > now());
+            if fresh {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let refresh_token = cached
+            .and_then(|t| t.refresh_token)
+            .or_else(|| config.refresh_token.clone());
+        let refreshed = match refresh_token {
+            Some(refresh_token) => Some(
+                self.request_token(
+                    config,
+                    environment_id,
+                    vec![
+                        ("grant_type", "refresh_token".to_string()),
+                        ("refresh_token", refresh_token),
+                    ],
+                )
+                .await,
+            ),
+            None => None,
+        };
+        let token = match (refreshed, config.grant_type) {
+            (Some(Ok(token)), _) => token,
+            // A rejected refresh token is no loss when a new token is one call away
+            (_, GrantType::ClientCredentials) => {
+                self.fetch_client_credentials(config, environment_id)
+                    .await?
+            }
+            (Some(Err(e)), _) => return Err(format!("Failed to refresh the token: {}", e)),
+            (None, _) => {
+                return Err(
+                    "No OAuth 2.0 token for this request, authorize the client first".to_string(),
+                )
+            }
+        };
+        Ok(token.access_token)
+    }
+
+    /// Fetches a token without interaction, for the client credentials and
+    /// refresh token grants.
+    pub async fn fetch_token(
+        &self,
+        config: &OAuth2Config,
+        environment_id: Option<&str>,
+    ) -> Result<oauth_tokens::Model, String> {
+        let token = match config.grant_type {
+            GrantType::ClientCredentials => {
+                self.fetch_client_credentials(config, environment_id)
+                    .await?
+            }
+            GrantType::RefreshToken => {
+                let refresh_token = config
+                    .refresh_token
+                    .clone()
+                    .ok_or_else(|| "The refresh token grant needs a refresh token".to_string())?;
+                self.request_token(
+                    config,
+                    environment_id,
+                    vec![
+                        ("grant_type", "refresh_token".to_string()),
+                        ("refresh_token", refresh_token),
+                    ],
+                )
+                .await?
+            }
+            GrantType::AuthorizationCode | GrantType::DeviceCode => {
+                return Err("This grant needs an interactive login".to_string())
+            }
+        };
+        Ok(masked(token))
+    }
+
+    async fn fetch_client_credentials(
+        &self,
+        config: &OAuth2Config,
+        environment_id: Option<&str>,
+    ) -> Result<oauth_tokens::Model, String> {
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if let Some(scope) = &config.scope {
+            form.push(("scope", scope.clone()));
+        }
+        self.request_token(config, environment_id, form).await
+    }
+
+    // Posts to the token endpoint and caches the result
+    async fn request_token(
+        &self,
+        config: &OAuth2Config,
+        environment_id: Option<&str>,
+        mut form: Vec<(&'static str, String)>,
+    ) -> Result<oauth_tokens::Model, String> {
+        form.push(("client_id", config.client_id.clone()));
+        let mut request = self.http.post(&config.token_url);
+        if let Some(secret) = &config.client_secret {
+            request = request.basic_auth(&config.client_id, Some(secret));
+        }
+        let response = request
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let status = response.status();
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(token_error(status.as_u16(), &body));
+        }
+        let token: TokenResponse =
+            serde_json::from_slice(&body).map_err(|e| format!("Invalid token response: {}", e))?;
+
+        self.store(config, environment_id, token).await
+    }
+
+    async fn store(
+        &self,
+        config: &OAuth2Config,
+        environment_id: Option<&str>,
+        token: TokenResponse,
+    ) -> Result<oauth_tokens::Model, String> {
+        let id = config.cache_key(environment_id);
+        let existing = oauth_tokens::Entity::find_by_id(id.clone())
+            .one(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Servers may keep the refresh token the same without resending it
+        let refresh_token = token
+            .refresh_token
+            .or_else(|| existing.as_ref().and_then(|t| t.refresh_token.clone()));
+
+        let model = oauth_tokens::ActiveModel {
+            id: Set(id),
+            environment_id: Set(environment_id.map(String::from)),
+            token_url: Set(config.token_url.clone()),
+            client_id: Set(config.client_id.clone()),
+            scope: Set(token.scope.or_else(|| config.scope.clone())),
+            access_token: Set(token.access_token),
+            token_type: Set(token.token_type.unwrap_or_else(|| "Bearer".to_string())),
+            refresh_token: Set(refresh_token),
+            expires_at: Set(token.expires_in.map(|secs| now() + secs * 1000)),
+            created_at: Set(now()),
+        };
+        match existing {
+            Some(_) => model.update(&self.db).await,
+            None => model.insert(&self.db).await,
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn flow_status(&self, flow_id: &str) -> Option<FlowStatus> {
+        self.flows
+            .lock()
+            .unwrap()
+            .get(flow_id)
+            .map(|(status, _)| status.clone())
+    }
+
+    fn set_flow(&self, flow_id: &str, status: FlowStatus) {
+        let mut flows = self.flows.lock().unwrap();
+        flows.retain(|_, (status, updated)| {
+            matches!(status, FlowStatus::Pending) || updated.elapsed() < FLOW_RETENTION
+        });
+        flows.insert(flow_id.to_string(), (status, Instant::now()));
+    }
+
+    /// Starts the authorization code flow with PKCE. The token is stored once
+    /// the browser comes back to the loopback redirect.
+    pub async fn start_authorization(
+        self: &Arc<Self>,
+        config: OAuth2Config,
+        environment_id: Option<String>,
+    ) -> Result<FlowStart, String> {
+        let authorization_url = config
+            .authorization_url
+            .clone()
+            .ok_or_else(|| "The authorization code flow needs an authorization URL".to_string())?;
+
+        let listener =
+            tokio::net::TcpListener::bind(("127.0.0.1", config.redirect_port.unwrap_or(0)))
+                .await
+                .map_err(|e| format!("Failed to open the redirect listener: {}", e))?;
+        let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+        let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+        let verifier = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        let state = Uuid::new_v4().simple().to_string();
+
+        let mut url = Url::parse(&authorization_url).map_err(|e| e.to_string())?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("response_type", "code")
+                .append_pair("client_id", &config.client_id)
+                .append_pair("redirect_uri", &redirect_uri)
+                .append_pair("state", &state)
+                .append_pair("code_challenge", &challenge)
+                .append_pair("code_challenge_method", "S256");
+            if let Some(scope) = &config.scope {
+                query.append_pair("scope", scope);
+            }
+        }
+
+        let flow_id = Uuid::new_v4().to_string();
+        self.set_flow(&flow_id, FlowStatus::Pending);
+
+        let manager = self.clone();
+        let id = flow_id.clone();
+        let redirect = redirect_uri.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let code = wait_for_code(listener, &state).await?;
+                manager
+                    .request_token(
+                        &config,
+                        environment_id.as_deref(),
+                        vec![
+                            ("grant_type", "authorization_code".to_string()),
+                            ("code", code),
+                            ("redirect_uri", redirect),
+                            ("code_verifier", verifier),
+                        ],
+                    )
+                    .await
+            }
+            .await;
+            manager.finish_flow(&id, result);
+        });
+
+        Ok(FlowStart {
+            flow_id,
+            authorization_url: Some(url.to_string()),
+            redirect_uri: Some(redirect_uri),
+            user_code: None,
+            verification_uri: None,
+            verification_uri_complete: None,
+        })
+    }
+
+    /// Starts the device authorization flow (RFC 8628) and polls for the
+    /// token in the background.
+    pub async fn start_device_flow(
+        self: &Arc<Self>,
+        config: OAuth2Config,
+        environment_id: Option<String>,
+    ) -> Result<FlowStart, String> {
+        let device_url = config
+            .device_authorization_url
+            .clone()
+            .ok_or_else(|| "The device flow needs a device authorization URL".to_string())?;
+
+        let mut form = vec![("client_id", config.client_id.clone())];
+        if let Some(scope) = &config.scope {
+            form.push(("scope", scope.clone()));
+        }
+        let response = self
+            .http
+            .post(&device_url)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response.bytes().await.map_err(|e| e.to_string())?;
+        if !status.is_success() {
+            return Err(token_error(status.as_u16(), &body));
+        }
+        let device: DeviceAuthorization = serde_json::from_slice(&body)
+            .map_err(|e| format!("Invalid device authorization response: {}", e))?;
+
+        let flow_id = Uuid::new_v4().to_string();
+        self.set_flow(&flow_id, FlowStatus::Pending);
+
+        let manager = self.clone();
+        let id = flow_id.clone();
+        let device_code = device.device_code.clone();
+        let expires = device
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(FLOW_TIMEOUT);
+        let mut interval = device.interval.unwrap_or(5);
+        tokio::spawn(async move {
+            let started = tokio::time::Instant::now();
+            let result = loop {
+                tokio::time::sleep(Duration::from_secs(interval)).await;
+                if started.elapsed() > expires {
+                    break Err("The device code expired".to_string());
+                }
+                let result = manager
+                    .request_token(
+                        &config,
+                        environment_id.as_deref(),
+                        vec![
+                            (
+                                "grant_type",
+                                "urn:ietf:params:oauth:grant-type:device_code".to_string(),
+                            ),
+                            ("device_code", device_code.clone()),
+                        ],
+                    )
+                    .await;
+                match result {
+                    Err(e) if e.starts_with("authorization_pending") => continue,
+                    Err(e) if e.starts_with("slow_down") => interval += 5,
+                    result => break result,
+                }
+            };
+            manager.finish_flow(&id, result);
+        });
+
+        Ok(FlowStart {
+            flow_id,
+            authorization_url: None,
+            redirect_uri: None,
+            user_code: Some(device.user_code),
+            verification_uri: Some(device.verification_uri),
+            verification_uri_complete: device.verification_uri_complete,
+        })
+    }
+
+    fn finish_flow(&self, flow_id: &str, result: Result<oauth_tokens::Model, String>) {
+        let status = match result {
+            Ok(_) => FlowStatus::Complete,
+            Err(e) => FlowStatus::Failed(e),
+        };
+        self.set_flow(flow_id, status);
+    }
+
+    /// Cached tokens, token values masked.
+    pub async fn list_tokens(&self) -> Result<Vec<oauth_tokens::Model>, String> {
+        let tokens = oauth_tokens::Entity::find()
+            .all(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(tokens.into_iter().map(masked).collect())
+    }
+
+    /// Forgets cached tokens, all of them or those of one environment.
+    pub async fn clear_tokens(&self, environment_id: Option<String>) -> Result<(), String> {
+        let mut delete = oauth_tokens::Entity::delete_many();
+        if let Some(environment_id) = environment_id {
+            delete = delete.filter(oauth_tokens::Column::EnvironmentId.eq(environment_id));
+        }
+        delete.exec(&self.db).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub async fn remove_token(&self, id: String) -> Result<(), String> {
+        oauth_tokens::Entity::delete_by_id(id)
+            .exec(&self.db)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn masked(mut token: oauth_tokens::Model) -> oauth_tokens::Model {
+    token.access_token = SECRET_MASK.to_string();
+    token.refresh_token = token.refresh_token.map(|_| SECRET_MASK.to_string());
+    token
+}
+
+// "error: description" so the device flow can tell pending from failed
+fn token_error(status: u16, body: &[u8]) -> String {
+    match serde_json::from_slice::<TokenError>(body) {
+        Ok(e) => match e.error_description {
+            Some(description) => format!("{}: {}", e.error, description),
+            None => e.error,
+        },
+        Err(_) => format!(
+            "Token endpoint returned {}: {}",
+            status,
+            String::from_utf8_lossy(body)
+        ),
+    }
+}
+
+// Serves the loopback redirect until the browser delivers the code
+async fn wait_for_code(listener: tokio::net::TcpListener, state: &str) -> Result<String, String> {
+    let (tx, mut rx) = mpsc::channel::<HashMap<String, String>>(1);
+    let app = Router::new().route(
+        "/callback",
+        get(move |Query(params): Query<HashMap<String, String>>| {
+            let tx = tx.clone();
+            async move {
+                let message = if params.contains_key("error") {
+                    "Authorization failed, you can close this window."
+                } else {
+                    "Authorization complete, you can close this window."
+                };
+                let _ = tx.send(params).await;
+                message
+            }
+        }),
+    );
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        let _ = axum::serve(listener, app)
+            .with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            })
+            .await;
+    });
+
+    let params = tokio::time::timeout(FLOW_TIMEOUT, rx.recv()).await;
+    let _ = shutdown_tx.send(());
+    let _ = server.await;
+
+    let params = params
+        .map_err(|_| "Timed out waiting for the authorization redirect".to_string())?
+        .ok_or_else(|| "The redirect listener stopped".to_string())?;
+    if let Some(error) = params.get("error") {
+        return Err(match params.get("error_description") {
+            Some(description) => format!("{}: {}", error, description),
+            None => error.clone(),
+        });
+    }
+    if params.get("state").map(String::as_str) != Some(state) {
+        return Err("The authorization redirect had the wrong state".to_string());
+    }
+    params
+        .get("code")
+        .cloned()
+        .ok_or_else(|| "The authorization redirect had no code".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Form};
+
+    // Token endpoint that records the grants it was asked for
+    async fn token_server(expires_in: i64) -> (String, Arc<Mutex<Vec<HashMap<String, String>>>>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let app = Router::new().route(
+            "/token",
+            post(move |Form(form): Form<HashMap<String, String>>| {
+                let seen = seen.clone();
+                async move {
+                    let mut calls = seen.lock().unwrap();
+                    calls.push(form);
+                    axum::Json(serde_json::json!({
+                        "access_token": format!("token-{}", calls.len()),
+                        "token_type": "Bearer",
+                        "expires_in": expires_in,
+                        "refresh_token": "refresh-1",
+                    }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        (url, calls)
+    }
+
+    // The database lives as long as the returned dir
+    async fn manager() -> (OAuthManager, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = crate::db::init_db(dir.path().to_path_buf()).await.unwrap();
+        let manager = OAuthManager::new(db.clone(), Arc::new(DnsOverrides::new(db)));
+        (manager, dir)
+    }
+
+    fn config(token_url: String) -> OAuth2Config {
+        OAuth2Config {
+            grant_type: GrantType::ClientCredentials,
+            token_url,
+            authorization_url: None,
+            device_authorization_url: None,
+            client_id: "client".to_string(),
+            client_secret: Some("secret".to_string()),
+            scope: Some("read".to_string()),
+            redirect_port: None,
+            refresh_token: None,
+        }
+    }
+
+    fn grants(calls: &Mutex<Vec<HashMap<String, String>>>) -> Vec<String> {
+        calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|form| form["grant_type"].clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn client_credentials_are_cached() {
+        let (url, calls) = token_server(3600).await;
+        let (manager, _dir) = manager().await;
+        let config = config(url);
+
+        assert_eq!(
+            manager.access_token(&config, None).await.unwrap(),
+            "token-1"
+        );
+        assert_eq!(
+            manager.access_token(&config, None).await.unwrap(),
+            "token-1"
+        );
+        assert_eq!(grants(&calls), vec!["client_credentials"]);
+        assert_eq!(calls.lock().unwrap()[0]["scope"], "read");
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_refreshed() {
+        let (url, calls) = token_server(0).await;
+        let (manager, _dir) = manager().await;
+        let config = config(url);
+
+        assert_eq!(
+            manager.access_token(&config, None).await.unwrap(),
+            "token-1"
+        );
+        assert_eq!(
+            manager.access_token(&config, None).await.unwrap(),
+            "token-2"
+        );
+        assert_eq!(grants(&calls), vec!["client_credentials", "refresh_token"]);
+        assert_eq!(calls.lock().unwrap()[1]["refresh_token"], "refresh-1");
+    }
+}
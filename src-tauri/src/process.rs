@@ -0,0 +1,217 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Sockets are cached by inode, which is never shared by two live sockets, so
+// a reused client port can't be attributed to the previous owner
+const CACHE_TTL: Duration = Duration::from_secs(30);
+const CACHE_LIMIT: usize = 1024;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub exe: Option<String>,
+}
+
+/// Finds the local process owning the client side of a proxied connection.
+#[derive(Default)]
+pub struct ProcessResolver {
+    cache: Mutex<HashMap<u64, (Instant, Option<ProcessInfo>)>>,
+}
+
+impl ProcessResolver {
+    pub async fn resolve(&self, client_addr: SocketAddr) -> Option<ProcessInfo> {
+        // Walking /proc is blocking file IO
+        let inode = tokio::task::spawn_blocking(move || socket_inode(client_addr))
+            .await
+            .ok()
+            .flatten()?;
+
+        let now = Instant::now();
+        if let Some((at, info)) = self.cache.lock().unwrap().get(&inode) {
+            if now.duration_since(*at) < CACHE_TTL {
+                return info.clone();
+            }
+        }
+
+        let info = tokio::task::spawn_blocking(move || lookup(inode))
+            .await
+            .ok()
+            .flatten();
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_LIMIT {
+            cache.retain(|_, (at, _)| now.duration_since(*at) < CACHE_TTL);
+        }
+        cache.insert(inode, (now, info.clone()));
+        info
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn lookup(inode: u64) -> Option<ProcessInfo> {
+    let pid = socket_owner(inode)?;
+
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default();
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid))
+        .ok()
+        .map(|p| p.to_string_lossy().into_owned());
+
+    Some(ProcessInfo { pid, name, exe })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lookup(_inode: u64) -> Option<ProcessInfo> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+fn socket_inode(_client_addr: SocketAddr) -> Option<u64> {
+    None
+}
+
+// The client's socket is the one whose local address is the peer we saw.
+// Remote clients have no socket here, so this also filters them out.
+#[cfg(target_os = "linux")]
+fn socket_inode(client_addr: SocketAddr) -> Option<u64> {
+    let wanted = SocketAddr::new(client_addr.ip().to_canonical(), client_addr.port());
+
+    ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|table| std::fs::read_to_string(table).ok())
+        .find_map(|content| find_inode(&content, wanted))
+}
+
+// sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+#[cfg(target_os = "linux")]
+fn find_inode(table: &str, wanted: SocketAddr) -> Option<u64> {
+    for line in table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 10 {
+            continue;
+        }
+        if parse_proc_addr(fields[1]) == Some(wanted) {
+            if let Ok(inode) = fields[9].parse::<u64>() {
+                // Sockets in TIME_WAIT have no inode any more
+                if inode != 0 {
+                    return Some(inode);
+                }
+            }
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn socket_owner(inode: u64) -> Option<u32> {
+    let target = format!("socket:[{}]", inode);
+
+    for entry in std::fs::read_dir("/proc").ok()?.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        // Other users' processes are not readable without privileges
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            if let Ok(link) = std::fs::read_link(fd.path()) {
+                if link.as_os_str() == target.as_str() {
+                    return Some(pid);
+                }
+            }
+        }
+    }
+    None
+}
+
+// "0100007F:1F90", addresses are printed as 32-bit words in host byte order
+#[cfg(target_os = "linux")]
+fn parse_proc_addr(s: &str) -> Option<SocketAddr> {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    let (addr, port) = s.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let ip = match addr.len() {
+        8 => {
+            let word = u32::from_str_radix(addr, 16).ok()?;
+            IpAddr::V4(Ipv4Addr::from(word.to_ne_bytes()))
+        }
+        32 => {
+            let mut octets = [0u8; 16];
+            for (i, chunk) in octets.chunks_mut(4).enumerate() {
+                let word = u32::from_str_radix(&addr[i * 8..i * 8 + 8], 16).ok()?;
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip.to_canonical(), port))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proc_addresses() {
+        // Words are little endian on the architectures we build for
+        assert_eq!(
+            parse_proc_addr("0100007F:1F90"),
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(
+            parse_proc_addr("00000000000000000000000001000000:0050"),
+            Some("[::1]:80".parse().unwrap())
+        );
+        // IPv4-mapped sockets in tcp6 compare equal to plain IPv4 clients
+        assert_eq!(
+            parse_proc_addr("0000000000000000FFFF00000100007F:0016"),
+            Some("127.0.0.1:22".parse().unwrap())
+        );
+        assert_eq!(parse_proc_addr("0100007F"), None);
+        assert_eq!(parse_proc_addr("0100007:1F90"), None);
+        assert_eq!(parse_proc_addr("0100007F:XYZ"), None);
+    }
+
+    #[test]
+    fn inode_from_table() {
+        let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 4242 1 0000000000000000 100 0 0 10 0
+   1: 0100007F:C350 0100007F:1F90 06 00000000:00000000 03:00001234 00000000     0        0 0 3 0000000000000000
+   2: 0100007F:C351 0100007F:1F90 01 00000000:00000000 00:00000000 00000000  1000        0 5151 1 0000000000000000 20 4 30 10 -1
+";
+        let addr = |s: &str| s.parse::<SocketAddr>().unwrap();
+        assert_eq!(find_inode(table, addr("127.0.0.1:8080")), Some(4242));
+        assert_eq!(find_inode(table, addr("127.0.0.1:50001")), Some(5151));
+        // TIME_WAIT entry without an inode
+        assert_eq!(find_inode(table, addr("127.0.0.1:50000")), None);
+        assert_eq!(find_inode(table, addr("10.0.0.1:8080")), None);
+        assert_eq!(find_inode("", addr("127.0.0.1:8080")), None);
+    }
+
+    #[tokio::test]
+    async fn resolves_own_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let resolver = ProcessResolver::default();
+        let info = resolver
+            .resolve(client.local_addr().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(info.pid, std::process::id());
+        assert!(info.exe.is_some());
+    }
+}
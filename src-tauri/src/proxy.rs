@@ -1,53 +1,323 @@
+use crate::access::{self, AccessControl, ProxyCredentials, TunnelGrant};
+use crate::blocklist::{BlockList, BlockRule};
+use crate::capture::{self, TeeBody};
+use crate::process::ProcessResolver;
 use crate::rewrites::RewriteManager;
-use crate::{db::requests, ProxyEventPayload};
+use crate::socks::SocksOptions;
+use crate::sse::{SseEvent, SseParser};
+use crate::stats::ProxyStats;
+use crate::tunnel::{BridgeInfo, Bridges};
+use crate::upstream::TlsDetails;
+use crate::{
+    db::{requests, sse_events},
+    ProxyEventPayload,
+};
 use hudsucker::{
     async_trait::async_trait,
-    hyper::{Body, Request, Response},
+    hyper::{self, Body, HeaderMap, Method, Request, Response, StatusCode, Uri},
     HttpContext, HttpHandler, RequestOrResponse,
 };
 use sea_orm::{ActiveModelTrait, DatabaseConnection, Set};
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
 use uuid::Uuid;
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProxyMode {
+    // Regular HTTP(S) proxy, clients send absolute URLs or CONNECT
+    #[default]
+    Forward,
+    // Every request is sent to `upstream`, e.g. "https://api.example.com/v1"
+    Reverse {
+        upstream: String,
+    },
+    // Accepts connections redirected by the OS (iptables REDIRECT) and infers
+    // the target from SO_ORIGINAL_DST, SNI or the Host header
+    Transparent,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProxyOptions {
+    pub mode: ProxyMode,
+    // Use 0.0.0.0 to let other devices on the network connect
+    pub bind_address: IpAddr,
+    // Required Proxy-Authorization (basic) for forward proxy clients
+    pub auth: Option<ProxyCredentials>,
+    // IPs or CIDR ranges allowed to connect, empty allows everyone
+    pub allowed_ips: Vec<String>,
+    // Store traffic in the database, off only rewrites and forwards
+    pub capture: bool,
+    // Rewrite rules tagged with this profile apply in addition to untagged ones
+    pub rules_profile: Option<String>,
+    // Extra SOCKS5 listener feeding the same pipeline
+    pub socks: Option<SocksOptions>,
+    // Pass every body through as it arrives instead of buffering it first.
+    // Body rewrite rules are not applied to streamed bodies.
+    pub streaming: bool,
+    // Upper bound of bytes stored per body, the rest is forwarded but not kept
+    pub max_capture_size: usize,
+}
+
+impl Default for ProxyOptions {
+    fn default() -> Self {
+        Self {
+            mode: ProxyMode::Forward,
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            auth: None,
+            allowed_ips: Vec::new(),
+            capture: true,
+            rules_profile: None,
+            socks: None,
+            streaming: false,
+            max_capture_size: capture::DEFAULT_MAX_CAPTURE_SIZE,
+        }
+    }
+}
+
+impl ProxyOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        // SOCKS5 clients never send Proxy-Authorization, without their own
+        // credentials they would get around the proxy auth
+        if self.auth.is_some()
+            && self
+                .socks
+                .as_ref()
+                .is_some_and(|socks| socks.credentials().is_none())
+        {
+            return Err(
+                "SOCKS5 needs its own username and password when proxy auth is enabled".into(),
+            );
+        }
+        if let ProxyMode::Reverse { upstream } = &self.mode {
+            let uri: Uri = upstream
+                .parse()
+                .map_err(|e| format!("Invalid upstream URL: {}", e))?;
+            if uri.authority().is_none()
+                || !matches!(uri.scheme_str(), Some("http") | Some("https"))
+            {
+                return Err("Upstream URL must be an absolute http(s) URL".into());
+            }
+        }
+        Ok(())
+    }
+
+    // Maps an incoming origin-form request onto the configured upstream
+    fn reverse_target(&self, uri: &Uri) -> Option<Uri> {
+        let ProxyMode::Reverse { upstream } = &self.mode else {
+            return None;
+        };
+        let base: Uri = upstream.parse().ok()?;
+        let prefix = base.path().trim_end_matches('/');
+        let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+        Uri::builder()
+            .scheme(base.scheme_str()?)
+            .authority(base.authority()?.as_str())
+            .path_and_query(format!("{}{}", prefix, path_and_query))
+            .build()
+            .ok()
+    }
+
+    // Bodies that are too big (or never end) are streamed even when streaming is off
+    fn should_stream(&self, headers: &HeaderMap) -> bool {
+        self.streaming
+            || capture::is_event_stream(headers)
+            || capture::content_length(headers)
+                .map(|len| len > self.max_capture_size as u64)
+                .unwrap_or(false)
+    }
+}
+
+fn transparent_target(req: &Request<Body>, bridge: &BridgeInfo) -> Option<Uri> {
+    // The Host header is sent on unchanged, it only picks the destination
+    // when the client didn't name one
+    let host = bridge
+        .target
+        .clone()
+        .or_else(|| {
+            req.headers()
+                .get(hudsucker::hyper::header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string())
+        })
+        .or_else(|| bridge.original_dst.map(|dst| dst.to_string()))?;
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+
+    format!("http://{}{}", host, path_and_query).parse().ok()
+}
+
 #[derive(Clone)]
 pub struct ProxyHandler {
     pub db: DatabaseConnection,
     pub pending_ids: Arc<Mutex<VecDeque<String>>>,
     pub event_tx: broadcast::Sender<ProxyEventPayload>,
     pub rewrite_manager: Arc<RewriteManager>,
+    pub options: Arc<ProxyOptions>,
+    pub bridges: Arc<Bridges>,
+    pub access: Arc<AccessControl>,
+    pub listener_id: String,
+    pub stats: Arc<ProxyStats>,
+    pub processes: Arc<ProcessResolver>,
+    pub block_list: Arc<BlockList>,
+    // Set on the handler that accepted a CONNECT, hudsucker keeps that clone
+    // until the tunnel closes
+    pub tunnel: Option<Arc<TunnelGrant>>,
+}
+
+impl ProxyHandler {
+    // Stores parsed events in order and pushes them to live listeners
+    fn spawn_sse_recorder(&self, request_id: String) -> mpsc::UnboundedSender<(i64, SseEvent)> {
+        let (tx, mut rx) = mpsc::unbounded_channel::<(i64, SseEvent)>();
+        let db = self.db.clone();
+        let event_tx = self.event_tx.clone();
+
+        tokio::spawn(async move {
+            let mut seq = 0;
+            while let Some((timestamp, event)) = rx.recv().await {
+                let record = sse_events::ActiveModel {
+                    id: Set(Uuid::new_v4().to_string()),
+                    request_id: Set(request_id.clone()),
+                    seq: Set(seq),
+                    event_id: Set(event.id.clone()),
+                    event_type: Set(event.event.clone()),
+                    data: Set(event.data.clone()),
+                    retry: Set(event.retry),
+                    timestamp: Set(timestamp),
+                };
+                let _ = record.insert(&db).await;
+                seq += 1;
+
+                let _ = event_tx.send(ProxyEventPayload {
+                    id: request_id.clone(),
+                    phase: "sse".to_string(),
+                    sse_event: Some(event),
+                    ..Default::default()
+                });
+            }
+        });
+
+        tx
+    }
+
+    // Scanning /proc for the client process is slow, so it runs next to the
+    // exchange and the row is patched once it's known
+    fn spawn_process_lookup(&self, id: String, client_addr: SocketAddr) {
+        let processes = self.processes.clone();
+        let db = self.db.clone();
+        tokio::spawn(async move {
+            let Some(process) = processes.resolve(client_addr).await else {
+                return;
+            };
+            let update_model = requests::ActiveModel {
+                id: Set(id),
+                process_name: Set(Some(process.name)),
+                process_id: Set(Some(process.pid as i64)),
+                process_exe: Set(process.exe),
+                ..Default::default()
+            };
+            let _ = update_model.update(&db).await;
+        });
+    }
+
+    // Answers a request matching a block rule without contacting upstream
+    async fn block(
+        &self,
+        rule: BlockRule,
+        client_addr: SocketAddr,
+        record: requests::ActiveModel,
+    ) -> RequestOrResponse {
+        self.stats.record_request();
+        if self.options.capture {
+            let mut record = record;
+            record.response_status = Set(rule.status_code());
+            record.blocked_by = Set(Some(rule.id.clone()));
+
+            if let Ok(model) = record.insert(&self.db).await {
+                self.spawn_process_lookup(model.id.clone(), client_addr);
+                let _ = self.event_tx.send(ProxyEventPayload {
+                    id: model.id,
+                    method: model.method,
+                    url: model.url,
+                    status: Some(model.response_status),
+                    phase: "blocked".to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        rule.response().into()
+    }
 }
 
 #[async_trait]
 impl HttpHandler for ProxyHandler {
     async fn handle_request(
         &mut self,
-        _ctx: &HttpContext,
+        ctx: &HttpContext,
         mut req: Request<Body>,
     ) -> RequestOrResponse {
+        // Connections from our own listeners are checked against their real peer
+        let bridge = self.bridges.get(&ctx.client_addr);
+        let client_addr = bridge.as_ref().map(|b| b.peer).unwrap_or(ctx.client_addr);
+        if !self.access.is_allowed(client_addr.ip()) {
+            return access::forbidden().into();
+        }
+
+        // Only forward proxy clients know about Proxy-Authorization, SOCKS5 has
+        // its own auth and transparent/reverse clients can't send it
+        if bridge.is_none() && matches!(self.options.mode, ProxyMode::Forward) {
+            if !self.access.is_authenticated(ctx.client_addr, req.headers()) {
+                return access::proxy_auth_required().into();
+            }
+            if req.method() == Method::CONNECT {
+                self.tunnel = self.access.remember_tunnel(ctx.client_addr).map(Arc::new);
+            }
+        }
+        // Never leak the proxy credentials upstream
+        req.headers_mut()
+            .remove(hudsucker::hyper::header::PROXY_AUTHORIZATION);
+
+        if req.method() != Method::CONNECT {
+            if let Some(target) = self.options.reverse_target(req.uri()) {
+                *req.uri_mut() = target;
+            } else if req.uri().authority().is_none() {
+                // Plain HTTP handed over by the transparent or SOCKS5 listener
+                if let Some(bridge) = bridge {
+                    if let Some(target) = transparent_target(&req, &bridge) {
+                        *req.uri_mut() = target;
+                    }
+                }
+            }
+        }
+
+        let profile = self.options.rules_profile.as_deref();
+        let capture = self.options.capture;
+
         // Rewrite URL
         let new_url_str = self
             .rewrite_manager
-            .apply_request_url(&req.uri().to_string());
+            .apply_request_url(&req.uri().to_string(), profile);
         if let Ok(new_uri) = new_url_str.parse() {
             *req.uri_mut() = new_uri;
         }
 
         // Rewrite Headers
         self.rewrite_manager
-            .apply_request_headers(req.headers_mut());
+            .apply_request_headers(req.headers_mut(), profile);
 
         let url = req.uri().to_string();
         let method = req.method().to_string();
         let req_id = Uuid::new_v4().to_string();
 
-        {
-            if let Ok(mut ids) = self.pending_ids.lock() {
-                ids.push_back(req_id.clone());
-            }
-        }
-
         let protocol = if req
             .headers()
             .get("content-type")
@@ -66,18 +336,89 @@ impl HttpHandler for ProxyHandler {
             .collect();
         let headers_json = serde_json::to_string(&headers_map).unwrap_or_default();
 
-        let (parts, body) = req.into_parts();
-        // Collect body using hyper 0.14
-        let body_bytes = match hudsucker::hyper::body::to_bytes(body).await {
-            Ok(collected) => collected.to_vec(),
-            Err(e) => {
-                eprintln!("Failed to read request body: {}", e);
-                Vec::new()
+        if let Some(rule) = self.block_list.find(&url, profile) {
+            let record = requests::ActiveModel {
+                id: Set(req_id),
+                method: Set(method),
+                url: Set(url),
+                protocol: Set(protocol.to_string()),
+                request_headers: Set(headers_json),
+                request_body: Set(None),
+                request_body_size: Set(None),
+                timestamp: Set(chrono::Utc::now().timestamp_millis()),
+                duration: Set(0),
+                response_status: Set(0),
+                grpc_status: Set(None),
+                response_headers: Set("".to_string()),
+                response_body: Set(None),
+                response_body_size: Set(None),
+                listener_id: Set(Some(self.listener_id.clone())),
+                process_name: Set(None),
+                process_id: Set(None),
+                process_exe: Set(None),
+                tls_info: Set(None),
+                blocked_by: Set(None),
+                source: Set(Some("proxy".to_string())),
+            };
+            return self.block(rule, client_addr, record).await;
+        }
+
+        // CONNECT never gets a response through the handler, and neither do
+        // WebSocket upgrades, hudsucker hands those to the WebSocket handler
+        if is_websocket_upgrade(req.headers()) {
+            self.stats.record_request();
+        } else if req.method() != Method::CONNECT {
+            self.stats.request_started();
+            if capture {
+                if let Ok(mut ids) = self.pending_ids.lock() {
+                    ids.push_back(req_id.clone());
+                }
             }
-        };
+        }
 
-        // Rewrite Body
-        let body_bytes = self.rewrite_manager.apply_request_body(body_bytes);
+        let streaming = self.options.should_stream(req.headers());
+        let max_capture_size = self.options.max_capture_size;
+
+        let (parts, body) = req.into_parts();
+        let (body, body_bytes) = if streaming {
+            // The body is stored once it has been fully forwarded upstream
+            let db = self.db.clone();
+            let stats = self.stats.clone();
+            let id = req_id.clone();
+            let limit = if capture { max_capture_size } else { 0 };
+            let body = TeeBody::new(body, limit)
+                .on_complete(move |captured| {
+                    stats.add_bytes_in(captured.total_size);
+                    if !capture {
+                        return;
+                    }
+                    tokio::spawn(async move {
+                        let update_model = requests::ActiveModel {
+                            id: Set(id),
+                            request_body_size: Set(Some(captured.total_size as i64)),
+                            request_body: Set(captured.into_stored()),
+                            ..Default::default()
+                        };
+                        let _ = update_model.update(&db).await;
+                    });
+                })
+                .into_body();
+            (body, Vec::new())
+        } else {
+            // Collect body using hyper 0.14
+            let body_bytes = match hudsucker::hyper::body::to_bytes(body).await {
+                Ok(collected) => collected.to_vec(),
+                Err(e) => {
+                    eprintln!("Failed to read request body: {}", e);
+                    Vec::new()
+                }
+            };
+
+            // Rewrite Body
+            let body_bytes = self.rewrite_manager.apply_request_body(body_bytes, profile);
+            self.stats.add_bytes_in(body_bytes.len() as u64);
+            (Body::from(body_bytes.clone()), body_bytes)
+        };
 
         let db_record = requests::ActiveModel {
             id: Set(req_id.clone()),
@@ -85,29 +426,44 @@ impl HttpHandler for ProxyHandler {
             url: Set(url.clone()),
             protocol: Set(protocol.to_string()),
             request_headers: Set(headers_json),
-            request_body: Set(if body_bytes.is_empty() {
+            request_body: Set(capture::truncate_for_storage(&body_bytes, max_capture_size)),
+            request_body_size: Set(if streaming {
                 None
             } else {
-                Some(body_bytes.clone())
+                Some(body_bytes.len() as i64)
             }),
             timestamp: Set(chrono::Utc::now().timestamp_millis()),
             duration: Set(0),
             response_status: Set(0),
+            grpc_status: Set(None),
             response_headers: Set("".to_string()),
             response_body: Set(None),
+            response_body_size: Set(None),
+            listener_id: Set(Some(self.listener_id.clone())),
+            process_name: Set(None),
+            process_id: Set(None),
+            process_exe: Set(None),
+            tls_info: Set(None),
+            blocked_by: Set(None),
+            source: Set(Some("proxy".to_string())),
         };
 
-        let _ = db_record.insert(&self.db).await;
+        if capture {
+            if db_record.insert(&self.db).await.is_ok() {
+                self.spawn_process_lookup(req_id.clone(), client_addr);
+            }
 
-        let _ = self.event_tx.send(ProxyEventPayload {
-            id: req_id.clone(),
-            method: method.clone(),
-            url: url.clone(),
-            status: None,
-            phase: "request".to_string(),
-        });
+            let _ = self.event_tx.send(ProxyEventPayload {
+                id: req_id.clone(),
+                method: method.clone(),
+                url: url.clone(),
+                status: None,
+                phase: "request".to_string(),
+                ..Default::default()
+            });
+        }
 
-        let new_req = Request::from_parts(parts, Body::from(body_bytes));
+        let new_req = Request::from_parts(parts, body);
         RequestOrResponse::Request(new_req)
     }
 
@@ -116,9 +472,12 @@ impl HttpHandler for ProxyHandler {
         _ctx: &HttpContext,
         mut res: Response<Body>,
     ) -> Response<Body> {
+        self.stats.request_finished();
+
         // Rewrite Headers
+        let profile = self.options.rules_profile.as_deref();
         self.rewrite_manager
-            .apply_response_headers(res.headers_mut());
+            .apply_response_headers(res.headers_mut(), profile);
 
         let req_id = {
             if let Ok(mut ids) = self.pending_ids.lock() {
@@ -128,7 +487,85 @@ impl HttpHandler for ProxyHandler {
             }
         };
 
+        let streaming = self.options.should_stream(res.headers());
+        let max_capture_size = self.options.max_capture_size;
+
+        // Set by the upstream connector for HTTPS connections
+        let tls_info = res
+            .extensions()
+            .get::<Arc<TlsDetails>>()
+            .and_then(|tls| serde_json::to_string(tls.as_ref()).ok());
+
         let (parts, body) = res.into_parts();
+        let status = parts.status.as_u16() as i32;
+
+        let headers_map: std::collections::HashMap<String, String> = parts
+            .headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
+            .collect();
+        let headers_json = serde_json::to_string(&headers_map).unwrap_or_default();
+
+        if streaming {
+            let limit = if req_id.is_some() {
+                max_capture_size
+            } else {
+                0
+            };
+            let mut tee = TeeBody::new(body, limit);
+
+            if let Some(id) = &req_id {
+                let update_model = requests::ActiveModel {
+                    id: Set(id.clone()),
+                    response_status: Set(status),
+                    response_headers: Set(headers_json),
+                    tls_info: Set(tls_info),
+                    ..Default::default()
+                };
+                let _ = update_model.update(&self.db).await;
+
+                let _ = self.event_tx.send(ProxyEventPayload {
+                    id: id.clone(),
+                    method: "".to_string(),
+                    url: "".to_string(),
+                    status: Some(status),
+                    phase: "response".to_string(),
+                    ..Default::default()
+                });
+
+                if capture::is_event_stream(&parts.headers) {
+                    let events_tx = self.spawn_sse_recorder(id.clone());
+                    let mut parser = SseParser::new();
+                    tee = tee.on_chunk(move |chunk| {
+                        for event in parser.feed(chunk) {
+                            let _ = events_tx.send((chrono::Utc::now().timestamp_millis(), event));
+                        }
+                    });
+                }
+            }
+
+            let db = self.db.clone();
+            let stats = self.stats.clone();
+            let body = tee
+                .on_complete(move |captured| {
+                    stats.add_bytes_out(captured.total_size);
+                    let Some(id) = req_id else {
+                        return;
+                    };
+                    tokio::spawn(async move {
+                        let update_model = requests::ActiveModel {
+                            id: Set(id),
+                            response_body_size: Set(Some(captured.total_size as i64)),
+                            response_body: Set(captured.into_stored()),
+                            ..Default::default()
+                        };
+                        let _ = update_model.update(&db).await;
+                    });
+                })
+                .into_body();
+            return Response::from_parts(parts, body);
+        }
+
         let body_bytes = match hudsucker::hyper::body::to_bytes(body).await {
             Ok(collected) => collected.to_vec(),
             Err(e) => {
@@ -138,27 +575,19 @@ impl HttpHandler for ProxyHandler {
         };
 
         // Rewrite Body
-        let body_bytes = self.rewrite_manager.apply_response_body(body_bytes);
-
-        let status = parts.status.as_u16() as i32;
+        let body_bytes = self
+            .rewrite_manager
+            .apply_response_body(body_bytes, profile);
+        self.stats.add_bytes_out(body_bytes.len() as u64);
 
         if let Some(id) = req_id {
-            let headers_map: std::collections::HashMap<String, String> = parts
-                .headers
-                .iter()
-                .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or("").to_string()))
-                .collect();
-            let headers_json = serde_json::to_string(&headers_map).unwrap_or_default();
-
             let update_model = requests::ActiveModel {
                 id: Set(id.clone()),
                 response_status: Set(status),
                 response_headers: Set(headers_json),
-                response_body: Set(if body_bytes.is_empty() {
-                    None
-                } else {
-                    Some(body_bytes.clone())
-                }),
+                response_body: Set(capture::truncate_for_storage(&body_bytes, max_capture_size)),
+                response_body_size: Set(Some(body_bytes.len() as i64)),
+                tls_info: Set(tls_info),
                 ..Default::default()
             };
 
@@ -170,9 +599,128 @@ impl HttpHandler for ProxyHandler {
                 url: "".to_string(),
                 status: Some(status),
                 phase: "response".to_string(),
+                ..Default::default()
             });
         }
 
         Response::from_parts(parts, Body::from(body_bytes))
     }
+
+    async fn handle_error(&mut self, _ctx: &HttpContext, err: hyper::Error) -> Response<Body> {
+        eprintln!("Failed to forward request: {}", err);
+        self.stats.request_finished();
+        self.stats.record_error();
+
+        let req_id = {
+            if let Ok(mut ids) = self.pending_ids.lock() {
+                ids.pop_front()
+            } else {
+                None
+            }
+        };
+
+        let status = StatusCode::BAD_GATEWAY;
+        if let Some(id) = req_id {
+            let update_model = requests::ActiveModel {
+                id: Set(id.clone()),
+                response_status: Set(status.as_u16() as i32),
+                response_body: Set(Some(err.to_string().into_bytes())),
+                ..Default::default()
+            };
+            let _ = update_model.update(&self.db).await;
+
+            let _ = self.event_tx.send(ProxyEventPayload {
+                id,
+                status: Some(status.as_u16() as i32),
+                phase: "response".to_string(),
+                ..Default::default()
+            });
+        }
+
+        Response::builder()
+            .status(status)
+            .body(Body::empty())
+            .expect("Failed to build response")
+    }
+}
+
+// Same test hudsucker applies before handing a request to upgrade_websocket
+fn is_websocket_upgrade(headers: &HeaderMap) -> bool {
+    let has_token = |name, token: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().eq_ignore_ascii_case(token))
+    };
+    has_token(hyper::header::CONNECTION, "upgrade")
+        && has_token(hyper::header::UPGRADE, "websocket")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn bridged_requests_go_where_the_client_asked() {
+        let req = Request::builder()
+            .uri("/path?q=1")
+            .header("host", "b.example")
+            .body(Body::empty())
+            .unwrap();
+        let mut bridge = BridgeInfo {
+            peer: "127.0.0.1:5000".parse().unwrap(),
+            original_dst: None,
+            target: Some("10.0.0.1:8080".to_string()),
+        };
+        assert_eq!(
+            transparent_target(&req, &bridge).unwrap().to_string(),
+            "http://10.0.0.1:8080/path?q=1"
+        );
+
+        // Transparent clients only have the Host header and the socket
+        bridge.target = None;
+        bridge.original_dst = Some("10.0.0.2:80".parse().unwrap());
+        assert_eq!(
+            transparent_target(&req, &bridge).unwrap().to_string(),
+            "http://b.example/path?q=1"
+        );
+        let no_host = Request::builder().uri("/").body(Body::empty()).unwrap();
+        assert_eq!(
+            transparent_target(&no_host, &bridge).unwrap().to_string(),
+            "http://10.0.0.2:80/"
+        );
+    }
+
+    #[test]
+    fn should_stream_by_type_and_length() {
+        let options = ProxyOptions {
+            max_capture_size: 100,
+            ..Default::default()
+        };
+        assert!(!options.should_stream(&headers(&[])));
+        assert!(!options.should_stream(&headers(&[("content-type", "application/json")])));
+        assert!(options.should_stream(&headers(&[(
+            "content-type",
+            "text/event-stream; charset=utf-8"
+        )])));
+        assert!(!options.should_stream(&headers(&[("content-length", "100")])));
+        assert!(options.should_stream(&headers(&[("content-length", "101")])));
+        assert!(!options.should_stream(&headers(&[("content-length", "bogus")])));
+
+        let streaming = ProxyOptions {
+            streaming: true,
+            ..Default::default()
+        };
+        assert!(streaming.should_stream(&headers(&[])));
+    }
 }
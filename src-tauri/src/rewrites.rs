@@ -12,6 +12,13 @@ pub struct RewriteRule {
     pub location: String, // "request", "response"
     pub action: String,   // "replace", "delete", "add"
     pub enabled: bool,
+    pub profile: Option<String>,
+}
+
+impl RewriteRule {
+    fn applies_to(&self, profile: Option<&str>) -> bool {
+        self.enabled && (self.profile.is_none() || self.profile.as_deref() == profile)
+    }
 }
 
 impl From<rewrites::Model> for RewriteRule {
@@ -24,6 +31,7 @@ impl From<rewrites::Model> for RewriteRule {
             location: model.location,
             action: model.action,
             enabled: model.enabled,
+            profile: model.profile,
         }
     }
 }
@@ -49,13 +57,13 @@ impl RewriteManager {
         }
     }
 
-    pub fn apply_request_url(&self, url: &str) -> String {
+    pub fn apply_request_url(&self, url: &str, profile: Option<&str>) -> String {
         let rules = self.rules.read().unwrap();
         let mut new_url = url.to_string();
 
         for rule in rules
             .iter()
-            .filter(|r| r.enabled && r.location == "request" && r.rule_type == "url")
+            .filter(|r| r.applies_to(profile) && r.location == "request" && r.rule_type == "url")
         {
             if let Ok(re) = Regex::new(&rule.match_pattern) {
                 new_url = re.replace_all(&new_url, &rule.replace_with).to_string();
@@ -64,11 +72,15 @@ impl RewriteManager {
         new_url
     }
 
-    pub fn apply_request_headers(&self, headers: &mut hudsucker::hyper::HeaderMap) {
+    pub fn apply_request_headers(
+        &self,
+        headers: &mut hudsucker::hyper::HeaderMap,
+        profile: Option<&str>,
+    ) {
         let rules = self.rules.read().unwrap();
         for rule in rules
             .iter()
-            .filter(|r| r.enabled && r.location == "request" && r.rule_type == "header")
+            .filter(|r| r.applies_to(profile) && r.location == "request" && r.rule_type == "header")
         {
             let key = rule.match_pattern.as_str();
             if let Ok(header_name) =
@@ -96,13 +108,13 @@ impl RewriteManager {
     }
 
     // Body rewrite needs byte manipulation, expensive. Assume String for now.
-    pub fn apply_request_body(&self, body: Vec<u8>) -> Vec<u8> {
+    pub fn apply_request_body(&self, body: Vec<u8>, profile: Option<&str>) -> Vec<u8> {
         let rules = self.rules.read().unwrap();
         let mut new_body = body;
 
         for rule in rules
             .iter()
-            .filter(|r| r.enabled && r.location == "request" && r.rule_type == "body")
+            .filter(|r| r.applies_to(profile) && r.location == "request" && r.rule_type == "body")
         {
             // Only support utf8 string replace for now
             if let Ok(text) = String::from_utf8(new_body.clone()) {
@@ -116,12 +128,15 @@ impl RewriteManager {
     }
 
     // Similarly for Response...
-    pub fn apply_response_headers(&self, headers: &mut hudsucker::hyper::HeaderMap) {
+    pub fn apply_response_headers(
+        &self,
+        headers: &mut hudsucker::hyper::HeaderMap,
+        profile: Option<&str>,
+    ) {
         let rules = self.rules.read().unwrap();
-        for rule in rules
-            .iter()
-            .filter(|r| r.enabled && r.location == "response" && r.rule_type == "header")
-        {
+        for rule in rules.iter().filter(|r| {
+            r.applies_to(profile) && r.location == "response" && r.rule_type == "header"
+        }) {
             let key = rule.match_pattern.as_str();
             if let Ok(header_name) =
                 hudsucker::hyper::header::HeaderName::from_bytes(key.as_bytes())
@@ -147,13 +162,13 @@ impl RewriteManager {
         }
     }
 
-    pub fn apply_response_body(&self, body: Vec<u8>) -> Vec<u8> {
+    pub fn apply_response_body(&self, body: Vec<u8>, profile: Option<&str>) -> Vec<u8> {
         let rules = self.rules.read().unwrap();
         let mut new_body = body;
 
         for rule in rules
             .iter()
-            .filter(|r| r.enabled && r.location == "response" && r.rule_type == "body")
+            .filter(|r| r.applies_to(profile) && r.location == "response" && r.rule_type == "body")
         {
             if let Ok(text) = String::from_utf8(new_body.clone()) {
                 if let Ok(re) = Regex::new(&rule.match_pattern) {
@@ -1,16 +1,21 @@
-use crate::db::requests;
+use crate::client::{self, ClientRequest};
+use crate::codegen::{self, Target};
+use crate::collections;
+use crate::db::{requests, sse_events};
+use crate::environments::{self, VariableScope};
+use crate::grpc;
 use crate::AppState;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        Path, State, WebSocketUpgrade,
+        Path, Query, State, WebSocketUpgrade,
     },
-    response::IntoResponse,
-    routing::get,
+    response::{IntoResponse, Response},
+    routing::{get, post},
     Json, Router,
 };
-use sea_orm::EntityTrait;
-use serde::Serialize;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
@@ -44,6 +49,47 @@ async fn get_status() -> Json<SystemInfo> {
     })
 }
 
+#[derive(Deserialize)]
+struct RequestFilter {
+    // Process name, e.g. "curl"
+    process: Option<String>,
+    pid: Option<i64>,
+    listener: Option<String>,
+    // "proxy" or "client"
+    source: Option<String>,
+    limit: Option<u64>,
+}
+
+async fn list_requests(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<RequestFilter>,
+) -> impl IntoResponse {
+    let mut query = requests::Entity::find();
+    if let Some(process) = filter.process {
+        query = query.filter(requests::Column::ProcessName.eq(process));
+    }
+    if let Some(pid) = filter.pid {
+        query = query.filter(requests::Column::ProcessId.eq(pid));
+    }
+    if let Some(listener) = filter.listener {
+        query = query.filter(requests::Column::ListenerId.eq(listener));
+    }
+    if let Some(source) = filter.source {
+        query = query.filter(requests::Column::Source.eq(source));
+    }
+
+    let result = query
+        .order_by_desc(requests::Column::Timestamp)
+        .limit(filter.limit.unwrap_or(100))
+        .all(&state.db)
+        .await;
+
+    match result {
+        Ok(requests) => Json(requests).into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
 async fn get_request_details(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
@@ -57,10 +103,227 @@ async fn get_request_details(
     }
 }
 
+async fn get_request_sse_events(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = sse_events::Entity::find()
+        .filter(sse_events::Column::RequestId.eq(id))
+        .order_by_asc(sse_events::Column::Seq)
+        .all(&state.db)
+        .await;
+
+    match result {
+        Ok(events) => Json(events).into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn list_proxies(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    Json(state.proxies.list())
+}
+
+async fn get_proxy(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.proxies.get(&id) {
+        Some(status) => Json(status).into_response(),
+        None => (axum::http::StatusCode::NOT_FOUND, "Unknown proxy").into_response(),
+    }
+}
+
+// Collection and environment errors are mostly unknown ids or invalid input
+fn collection_response<T: Serialize>(result: Result<T, String>) -> Response {
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+async fn list_collections(State(state): State<Arc<AppState>>) -> Response {
+    collection_response(collections::list_collections(&state.db).await)
+}
+
+async fn get_collection(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> Response {
+    let result = collections::get_collection_tree(&state.db, &id)
+        .await
+        .map(|mut tree| {
+            tree.requests = tree
+                .requests
+                .into_iter()
+                .map(collections::mask_auth)
+                .collect();
+            tree
+        });
+    collection_response(result)
+}
+
+async fn get_saved_request(State(state): State<Arc<AppState>>, Path(id): Path<String>) -> Response {
+    let result = collections::get_saved_request(&state.db, &id).await;
+    collection_response(result.map(collections::mask_auth))
+}
+
+async fn list_environments(State(state): State<Arc<AppState>>) -> Response {
+    collection_response(environments::list_environments(&state.db).await)
+}
+
+// ?scope=global, ?scope=collection&id=... or ?scope=environment&id=...
+#[derive(Deserialize)]
+struct ScopeQuery {
+    scope: String,
+    id: Option<String>,
+}
+
+async fn list_variables(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ScopeQuery>,
+) -> Response {
+    let result = match VariableScope::from_parts(&query.scope, query.id) {
+        Ok(scope) => environments::list_variables(&state.db, &scope).await,
+        Err(e) => Err(e),
+    };
+    collection_response(result)
+}
+
+// Plain text body in, client request JSON out
+async fn import_curl(body: String) -> Response {
+    collection_response(crate::curl::parse_curl(&body))
+}
+
+fn curl_response(result: Result<String, String>) -> Response {
+    match result {
+        Ok(command) => command.into_response(),
+        Err(e) => (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    }
+}
+
+async fn export_curl(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<ClientRequest>,
+) -> Response {
+    let result = client::resolve_for_export(&state, req)
+        .await
+        .map(|req| crate::curl::to_curl(&req));
+    curl_response(result)
+}
+
+async fn get_request_curl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = requests::Entity::find_by_id(id).one(&state.db).await;
+
+    match result {
+        Ok(Some(request)) => crate::curl::record_to_curl(&request).into_response(),
+        Ok(None) => (axum::http::StatusCode::NOT_FOUND, "Request not found").into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct EnvironmentQuery {
+    environment_id: Option<String>,
+}
+
+async fn get_saved_request_curl(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<EnvironmentQuery>,
+) -> Response {
+    let result = async {
+        let saved = collections::get_saved_request(&state.db, &id).await?;
+        let req = collections::to_client_request(&saved, query.environment_id)?;
+        let req = client::resolve_for_export(&state, req).await?;
+        Ok(crate::curl::to_curl(&req))
+    }
+    .await;
+    curl_response(result)
+}
+
+async fn generate_code(
+    State(state): State<Arc<AppState>>,
+    Path(target): Path<String>,
+    Json(req): Json<ClientRequest>,
+) -> Response {
+    let result = async {
+        let target = target.parse::<Target>()?;
+        let req = client::resolve_for_export(&state, req).await?;
+        Ok(codegen::generate(&req, target))
+    }
+    .await;
+    curl_response(result)
+}
+
+async fn get_request_code(
+    State(state): State<Arc<AppState>>,
+    Path((id, target)): Path<(String, String)>,
+) -> Response {
+    let target = match target.parse::<Target>() {
+        Ok(target) => target,
+        Err(e) => return (axum::http::StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    match requests::Entity::find_by_id(id).one(&state.db).await {
+        Ok(Some(request)) => codegen::generate_for_record(&request, target).into_response(),
+        Ok(None) => (axum::http::StatusCode::NOT_FOUND, "Request not found").into_response(),
+        Err(e) => (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+async fn get_saved_request_code(
+    State(state): State<Arc<AppState>>,
+    Path((id, target)): Path<(String, String)>,
+    Query(query): Query<EnvironmentQuery>,
+) -> Response {
+    let result = async {
+        let target = target.parse::<Target>()?;
+        let saved = collections::get_saved_request(&state.db, &id).await?;
+        let req = collections::to_client_request(&saved, query.environment_id)?;
+        let req = client::resolve_for_export(&state, req).await?;
+        Ok(codegen::generate(&req, target))
+    }
+    .await;
+    curl_response(result)
+}
+
+async fn list_proto_files(State(state): State<Arc<AppState>>) -> Response {
+    collection_response(grpc::list_proto_files(&state.db).await)
+}
+
+async fn list_grpc_services(State(state): State<Arc<AppState>>) -> Response {
+    collection_response(grpc::list_services(&state.db).await)
+}
+
+// Changes to collections, environments, variables and proto files and gRPC
+// calls are left to the app, and saved credentials are masked. This server
+// listens on every interface with permissive CORS, so any web page could
+// otherwise edit the user's collections, read their secrets or reach internal
+// hosts.
 pub async fn run(state: Arc<AppState>, port: u16) {
     let app = Router::new()
         .route("/api/status", get(get_status))
+        .route("/api/requests", get(list_requests))
         .route("/api/requests/:id", get(get_request_details))
+        .route("/api/requests/:id/sse", get(get_request_sse_events))
+        .route("/api/requests/:id/curl", get(get_request_curl))
+        .route("/api/curl/import", post(import_curl))
+        .route("/api/curl/export", post(export_curl))
+        .route("/api/requests/:id/code/:target", get(get_request_code))
+        .route("/api/codegen/:target", post(generate_code))
+        .route("/api/proxies", get(list_proxies))
+        .route("/api/proxies/:id", get(get_proxy))
+        .route("/api/collections", get(list_collections))
+        .route("/api/collections/:id", get(get_collection))
+        .route("/api/environments", get(list_environments))
+        .route("/api/variables", get(list_variables))
+        .route("/api/proto-files", get(list_proto_files))
+        .route("/api/grpc/services", get(list_grpc_services))
+        .route("/api/saved-requests/:id", get(get_saved_request))
+        .route("/api/saved-requests/:id/curl", get(get_saved_request_curl))
+        .route(
+            "/api/saved-requests/:id/code/:target",
+            get(get_saved_request_code),
+        )
         .route("/ws/events", get(ws_handler))
         .layer(CorsLayer::permissive())
         .with_state(state);
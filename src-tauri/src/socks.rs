@@ -0,0 +1,351 @@
+use crate::tunnel::{self, BridgeInfo, Bridges};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const VERSION: u8 = 0x05;
+const NO_AUTH: u8 = 0x00;
+const USER_PASS: u8 = 0x02;
+const NO_ACCEPTABLE_METHOD: u8 = 0xFF;
+const CMD_CONNECT: u8 = 0x01;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+const REPLY_SUCCEEDED: u8 = 0x00;
+const REPLY_GENERAL_FAILURE: u8 = 0x01;
+const REPLY_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const REPLY_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SocksOptions {
+    pub port: u16,
+    // When both are set clients must authenticate (RFC 1929)
+    pub username: Option<String>,
+    #[serde(skip_serializing)]
+    pub password: Option<String>,
+}
+
+impl SocksOptions {
+    pub fn credentials(&self) -> Option<(&str, &str)> {
+        match (&self.username, &self.password) {
+            (Some(user), Some(pass)) => Some((user, pass)),
+            _ => None,
+        }
+    }
+}
+
+/// Accepts SOCKS5 clients and hands their connections to the HTTP proxy at
+/// `proxy_addr`, so they go through the same interception and capture.
+pub async fn run(
+    listener: TcpListener,
+    proxy_addr: SocketAddr,
+    bridges: Arc<Bridges>,
+    options: Arc<SocksOptions>,
+) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("SOCKS5 accept failed: {}", e);
+                continue;
+            }
+        };
+
+        let bridges = bridges.clone();
+        let options = options.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, peer, proxy_addr, bridges, &options).await {
+                eprintln!("SOCKS5 connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    mut client: TcpStream,
+    peer: SocketAddr,
+    proxy_addr: SocketAddr,
+    bridges: Arc<Bridges>,
+    options: &SocksOptions,
+) -> io::Result<()> {
+    negotiate_auth(&mut client, options).await?;
+
+    let target = match read_connect_request(&mut client).await {
+        Ok(target) => target,
+        Err((reply, e)) => {
+            send_reply(&mut client, reply).await?;
+            return Err(e);
+        }
+    };
+    send_reply(&mut client, REPLY_SUCCEEDED).await?;
+
+    // Look at what the client sends first to pick how it enters the proxy.
+    // Server-speaks-first protocols send nothing, so don't wait forever.
+    let mut head = vec![0u8; 4096];
+    let read = match tokio::time::timeout(Duration::from_secs(2), client.read(&mut head)).await {
+        Ok(read) => read?,
+        Err(_) => 0,
+    };
+
+    let info = BridgeInfo {
+        peer,
+        original_dst: target.parse().ok(),
+        target: Some(target.clone()),
+    };
+    // Plain HTTP is sent as is so every request gets captured, anything else
+    // (TLS or unknown protocols) goes through a CONNECT tunnel
+    let connect_target = if looks_like_http(&head[..read]) {
+        None
+    } else {
+        Some(target.as_str())
+    };
+
+    let mut bridge = tunnel::open(proxy_addr, bridges, info, connect_target).await?;
+    bridge.stream.write_all(&head[..read]).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut bridge.stream).await?;
+    Ok(())
+}
+
+async fn negotiate_auth(client: &mut TcpStream, options: &SocksOptions) -> io::Result<()> {
+    let mut header = [0u8; 2];
+    client.read_exact(&mut header).await?;
+    if header[0] != VERSION {
+        return Err(invalid_data("unsupported SOCKS version"));
+    }
+    let mut methods = vec![0u8; header[1] as usize];
+    client.read_exact(&mut methods).await?;
+
+    let Some((username, password)) = options.credentials() else {
+        if !methods.contains(&NO_AUTH) {
+            client.write_all(&[VERSION, NO_ACCEPTABLE_METHOD]).await?;
+            return Err(invalid_data("client does not offer no-auth"));
+        }
+        return client.write_all(&[VERSION, NO_AUTH]).await;
+    };
+
+    if !methods.contains(&USER_PASS) {
+        client.write_all(&[VERSION, NO_ACCEPTABLE_METHOD]).await?;
+        return Err(invalid_data("client does not offer username/password auth"));
+    }
+    client.write_all(&[VERSION, USER_PASS]).await?;
+
+    // RFC 1929 sub-negotiation
+    let mut version = [0u8; 1];
+    client.read_exact(&mut version).await?;
+    let given_username = read_short_string(client).await?;
+    let given_password = read_short_string(client).await?;
+
+    if given_username == username.as_bytes() && given_password == password.as_bytes() {
+        client.write_all(&[0x01, 0x00]).await
+    } else {
+        client.write_all(&[0x01, 0x01]).await?;
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "invalid SOCKS5 credentials",
+        ))
+    }
+}
+
+// Returns the requested "host:port", or the reply code to fail with
+async fn read_connect_request(client: &mut TcpStream) -> Result<String, (u8, io::Error)> {
+    let general = |e: io::Error| (REPLY_GENERAL_FAILURE, e);
+
+    let mut header = [0u8; 4];
+    client.read_exact(&mut header).await.map_err(general)?;
+    if header[1] != CMD_CONNECT {
+        return Err((
+            REPLY_COMMAND_NOT_SUPPORTED,
+            invalid_data("only CONNECT is supported"),
+        ));
+    }
+
+    let host = match header[3] {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            client.read_exact(&mut octets).await.map_err(general)?;
+            Ipv4Addr::from(octets).to_string()
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            client.read_exact(&mut octets).await.map_err(general)?;
+            format!("[{}]", Ipv6Addr::from(octets))
+        }
+        ATYP_DOMAIN => {
+            let name = read_short_string(client).await.map_err(general)?;
+            String::from_utf8(name).map_err(|_| general(invalid_data("invalid domain name")))?
+        }
+        _ => {
+            return Err((
+                REPLY_ADDRESS_NOT_SUPPORTED,
+                invalid_data("unknown address type"),
+            ))
+        }
+    };
+
+    let mut port = [0u8; 2];
+    client.read_exact(&mut port).await.map_err(general)?;
+    Ok(format!("{}:{}", host, u16::from_be_bytes(port)))
+}
+
+async fn read_short_string(client: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 1];
+    client.read_exact(&mut len).await?;
+    let mut value = vec![0u8; len[0] as usize];
+    client.read_exact(&mut value).await?;
+    Ok(value)
+}
+
+async fn send_reply(client: &mut TcpStream, reply: u8) -> io::Result<()> {
+    // The bound address is not meaningful for us, report 0.0.0.0:0
+    client
+        .write_all(&[VERSION, reply, 0x00, ATYP_IPV4, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
+// "GET /", "POST /" ... an uppercase token followed by a space
+fn looks_like_http(head: &[u8]) -> bool {
+    let Some(space) = head.iter().position(|b| *b == b' ') else {
+        return false;
+    };
+    space > 0 && space <= 10 && head[..space].iter().all(|b| b.is_ascii_uppercase())
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Client and server ends of a loopback connection
+    async fn pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (client, server)
+    }
+
+    fn open() -> SocksOptions {
+        SocksOptions {
+            port: 0,
+            username: None,
+            password: None,
+        }
+    }
+
+    fn with_credentials() -> SocksOptions {
+        SocksOptions {
+            port: 0,
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+        }
+    }
+
+    async fn connect(request: &[u8]) -> Result<String, (u8, io::Error)> {
+        let (mut client, mut server) = pair().await;
+        client.write_all(&[VERSION, 1, NO_AUTH]).await.unwrap();
+        client.write_all(request).await.unwrap();
+        negotiate_auth(&mut server, &open()).await.unwrap();
+
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [VERSION, NO_AUTH]);
+        read_connect_request(&mut server).await
+    }
+
+    #[tokio::test]
+    async fn connect_targets() {
+        let ipv4 = [VERSION, CMD_CONNECT, 0, ATYP_IPV4, 10, 0, 0, 1, 0x01, 0xBB];
+        assert_eq!(connect(&ipv4).await.unwrap(), "10.0.0.1:443");
+
+        let mut ipv6 = vec![VERSION, CMD_CONNECT, 0, ATYP_IPV6];
+        ipv6.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        ipv6.extend_from_slice(&8080u16.to_be_bytes());
+        assert_eq!(connect(&ipv6).await.unwrap(), "[::1]:8080");
+
+        let mut domain = vec![VERSION, CMD_CONNECT, 0, ATYP_DOMAIN, 11];
+        domain.extend_from_slice(b"example.com");
+        domain.extend_from_slice(&80u16.to_be_bytes());
+        assert_eq!(connect(&domain).await.unwrap(), "example.com:80");
+    }
+
+    #[tokio::test]
+    async fn unsupported_requests() {
+        let bind = [VERSION, 0x02, 0, ATYP_IPV4, 10, 0, 0, 1, 0, 80];
+        assert_eq!(
+            connect(&bind).await.unwrap_err().0,
+            REPLY_COMMAND_NOT_SUPPORTED
+        );
+        let unknown = [VERSION, CMD_CONNECT, 0, 0x09, 0, 80];
+        assert_eq!(
+            connect(&unknown).await.unwrap_err().0,
+            REPLY_ADDRESS_NOT_SUPPORTED
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_version() {
+        let (mut client, mut server) = pair().await;
+        client.write_all(&[0x04, 1, NO_AUTH]).await.unwrap();
+        let err = negotiate_auth(&mut server, &open()).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    async fn login(username: &[u8], password: &[u8]) -> (io::Result<()>, [u8; 4]) {
+        let (mut client, mut server) = pair().await;
+        client
+            .write_all(&[VERSION, 1, USER_PASS, 0x01])
+            .await
+            .unwrap();
+        client.write_all(&[username.len() as u8]).await.unwrap();
+        client.write_all(username).await.unwrap();
+        client.write_all(&[password.len() as u8]).await.unwrap();
+        client.write_all(password).await.unwrap();
+
+        let result = negotiate_auth(&mut server, &with_credentials()).await;
+        let mut replies = [0u8; 4];
+        client.read_exact(&mut replies).await.unwrap();
+        (result, replies)
+    }
+
+    #[tokio::test]
+    async fn username_password() {
+        let (result, replies) = login(b"user", b"secret").await;
+        assert!(result.is_ok());
+        assert_eq!(replies, [VERSION, USER_PASS, 0x01, 0x00]);
+
+        let (result, replies) = login(b"user", b"wrong").await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(replies, [VERSION, USER_PASS, 0x01, 0x01]);
+    }
+
+    #[tokio::test]
+    async fn credentials_required() {
+        let (mut client, mut server) = pair().await;
+        client.write_all(&[VERSION, 1, NO_AUTH]).await.unwrap();
+        assert!(negotiate_auth(&mut server, &with_credentials())
+            .await
+            .is_err());
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [VERSION, NO_ACCEPTABLE_METHOD]);
+    }
+
+    #[test]
+    fn http_detection() {
+        assert!(looks_like_http(b"GET / HTTP/1.1\r\n"));
+        assert!(looks_like_http(b"OPTIONS * HTTP/1.1\r\n"));
+        assert!(!looks_like_http(b"\x16\x03\x01\x00\x96"));
+        assert!(!looks_like_http(b"get / HTTP/1.1"));
+        assert!(!looks_like_http(b" / HTTP/1.1"));
+        assert!(!looks_like_http(b"VERYLONGMETHOD / HTTP/1.1"));
+        assert!(!looks_like_http(b""));
+    }
+}
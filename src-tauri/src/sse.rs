@@ -0,0 +1,137 @@
+use serde::Serialize;
+
+/// One dispatched `text/event-stream` event.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SseEvent {
+    pub id: Option<String>,
+    pub event: Option<String>,
+    pub data: String,
+    pub retry: Option<i64>,
+}
+
+/// Incremental parser following the WHATWG event stream rules.
+///
+/// Chunks can split lines (and UTF-8 sequences) anywhere, so incomplete
+/// lines are kept until the next chunk arrives.
+#[derive(Default)]
+pub struct SseParser {
+    line: Vec<u8>,
+    last_was_cr: bool,
+    pending: SseEvent,
+    data_lines: Vec<String>,
+    // Kept across events until the next `id:` line
+    last_event_id: Option<String>,
+    // Set once the first line was read, a BOM may only start the stream
+    started: bool,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        for &b in chunk {
+            match b {
+                b'\n' if self.last_was_cr => {
+                    // Second half of a CRLF, the line was already handled
+                    self.last_was_cr = false;
+                }
+                b'\r' | b'\n' => {
+                    self.last_was_cr = b == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(event) = self.process_line(&line) {
+                        events.push(event);
+                    }
+                }
+                _ => {
+                    self.last_was_cr = false;
+                    self.line.push(b);
+                }
+            }
+        }
+        events
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Option<SseEvent> {
+        let line = if self.started {
+            line
+        } else {
+            self.started = true;
+            line.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(line)
+        };
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        // Comment
+        if line[0] == b':' {
+            return None;
+        }
+
+        let line = String::from_utf8_lossy(line);
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line.as_ref(), ""),
+        };
+
+        match field {
+            "event" => self.pending.event = Some(value.to_string()),
+            "data" => self.data_lines.push(value.to_string()),
+            "id" if !value.contains('\0') => {
+                self.last_event_id = Some(value.to_string());
+                self.pending.id = Some(value.to_string());
+            }
+            "retry" => {
+                if let Ok(retry) = value.parse() {
+                    self.pending.retry = Some(retry);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let mut event = std::mem::take(&mut self.pending);
+        let data_lines = std::mem::take(&mut self.data_lines);
+        // Blank lines without any field in between have nothing to show
+        if data_lines.is_empty() && event.retry.is_none() && event.id.is_none() {
+            return None;
+        }
+        event.id = self.last_event_id.clone();
+        event.data = data_lines.join("\n");
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn last_event_id_persists() {
+        let mut parser = SseParser::new();
+        let events = parser.feed(b"id: 1\ndata: a\n\ndata: b\n\nid\ndata: c\n\n");
+        let ids: Vec<Option<&str>> = events.iter().map(|e| e.id.as_deref()).collect();
+        assert_eq!(ids, vec![Some("1"), Some("1"), Some("")]);
+    }
+
+    #[test]
+    fn id_only_event_is_kept() {
+        let mut parser = SseParser::new();
+        let events = parser.feed(b"id: 7\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id.as_deref(), Some("7"));
+        assert_eq!(events[0].data, "");
+    }
+
+    #[test]
+    fn leading_bom_is_stripped() {
+        let mut parser = SseParser::new();
+        let mut events = parser.feed(b"\xEF\xBB");
+        events.extend(parser.feed(b"\xBFdata: x\r\n\r\n"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "x");
+    }
+}
@@ -0,0 +1,92 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Requests per second are averaged over this window
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Live counters of one proxy listener, shared by all its handler clones.
+#[derive(Default)]
+pub struct ProxyStats {
+    // hudsucker doesn't expose accepted sockets, so this counts exchanges
+    // currently being proxied
+    active_connections: AtomicI64,
+    total_requests: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    errors: AtomicU64,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StatsSnapshot {
+    pub active_connections: i64,
+    pub total_requests: u64,
+    pub requests_per_sec: f64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub errors: u64,
+}
+
+impl ProxyStats {
+    pub fn request_started(&self) {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        self.record_request();
+    }
+
+    /// Counts a request that is never forwarded through the handler, such as
+    /// blocked requests and WebSocket upgrades.
+    pub fn record_request(&self) {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        recent.push_back(now);
+        prune(&mut recent, now);
+    }
+
+    pub fn request_finished(&self) {
+        self.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_in(&self, n: u64) {
+        self.bytes_in.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn add_bytes_out(&self, n: u64) {
+        self.bytes_out.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let requests_per_sec = {
+            let mut recent = self.recent.lock().unwrap();
+            prune(&mut recent, Instant::now());
+            recent.len() as f64 / RATE_WINDOW.as_secs_f64()
+        };
+
+        StatsSnapshot {
+            active_connections: self.active_connections.load(Ordering::Relaxed).max(0),
+            total_requests: self.total_requests.load(Ordering::Relaxed),
+            requests_per_sec,
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn prune(recent: &mut VecDeque<Instant>, now: Instant) {
+    while let Some(first) = recent.front() {
+        if now.duration_since(*first) > RATE_WINDOW {
+            recent.pop_front();
+        } else {
+            break;
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use crate::tunnel::{self, BridgeInfo, Bridges};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// TLS record header plus the largest record we wait for before giving up on SNI
+const MAX_CLIENT_HELLO: usize = 5 + 16384;
+
+/// Accepts connections redirected to us (e.g. iptables REDIRECT) and feeds them
+/// into the HTTP proxy listening on `proxy_addr`.
+pub async fn run(listener: TcpListener, proxy_addr: SocketAddr, bridges: Arc<Bridges>) {
+    let listen_addr = listener.local_addr().ok();
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("Transparent accept failed: {}", e);
+                continue;
+            }
+        };
+
+        let bridges = bridges.clone();
+        tokio::spawn(async move {
+            // A connection made straight to our port was not redirected
+            let original_dst = original_dst(&stream).filter(|dst| Some(*dst) != listen_addr);
+            let info = BridgeInfo {
+                peer,
+                original_dst,
+                target: None,
+            };
+            if let Err(e) = handle(stream, proxy_addr, bridges, info).await {
+                eprintln!("Transparent connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(
+    mut client: TcpStream,
+    proxy_addr: SocketAddr,
+    bridges: Arc<Bridges>,
+    info: BridgeInfo,
+) -> io::Result<()> {
+    let mut head = vec![0u8; MAX_CLIENT_HELLO];
+    let mut read = client.read(&mut head).await?;
+    if read == 0 {
+        return Ok(());
+    }
+
+    let target = if head[0] == 0x16 {
+        // TLS: wait for the whole ClientHello record to learn the SNI
+        while (read < 5 || read < 5 + record_len(&head)) && read < head.len() {
+            let more = tokio::time::timeout(Duration::from_secs(5), client.read(&mut head[read..]))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "incomplete ClientHello"))??;
+            if more == 0 {
+                break;
+            }
+            read += more;
+        }
+
+        let host = parse_sni(&head[..read])
+            .or_else(|| info.original_dst.map(|dst| dst.ip().to_string()))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "no SNI or original destination")
+            })?;
+        let port = info.original_dst.map(|dst| dst.port()).unwrap_or(443);
+        Some(format_authority(&host, port))
+    } else {
+        // Plain HTTP goes through as is, the handler uses the Host header
+        None
+    };
+
+    let mut bridge = tunnel::open(proxy_addr, bridges, info, target.as_deref()).await?;
+    bridge.stream.write_all(&head[..read]).await?;
+    tokio::io::copy_bidirectional(&mut client, &mut bridge.stream).await?;
+    Ok(())
+}
+
+fn format_authority(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+fn record_len(buf: &[u8]) -> usize {
+    u16::from_be_bytes([buf[3], buf[4]]) as usize
+}
+
+/// Pulls the server_name extension out of a TLS ClientHello record.
+fn parse_sni(buf: &[u8]) -> Option<String> {
+    fn take<'a>(buf: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+        if buf.len() < n {
+            return None;
+        }
+        let (head, rest) = buf.split_at(n);
+        *buf = rest;
+        Some(head)
+    }
+    fn take_u8(buf: &mut &[u8]) -> Option<usize> {
+        take(buf, 1).map(|b| b[0] as usize)
+    }
+    fn take_u16(buf: &mut &[u8]) -> Option<usize> {
+        take(buf, 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+    }
+
+    let mut buf = buf;
+    // Record header
+    let header = take(&mut buf, 5)?;
+    if header[0] != 0x16 {
+        return None;
+    }
+    // Handshake header, must be a ClientHello
+    let handshake = take(&mut buf, 4)?;
+    if handshake[0] != 0x01 {
+        return None;
+    }
+    // Version and random
+    take(&mut buf, 2 + 32)?;
+    let session_id_len = take_u8(&mut buf)?;
+    take(&mut buf, session_id_len)?;
+    let cipher_suites_len = take_u16(&mut buf)?;
+    take(&mut buf, cipher_suites_len)?;
+    let compression_len = take_u8(&mut buf)?;
+    take(&mut buf, compression_len)?;
+
+    let extensions_len = take_u16(&mut buf)?;
+    let mut extensions = take(&mut buf, extensions_len)?;
+    while !extensions.is_empty() {
+        let ext_type = take_u16(&mut extensions)?;
+        let ext_len = take_u16(&mut extensions)?;
+        let mut ext = take(&mut extensions, ext_len)?;
+        if ext_type != 0x0000 {
+            continue;
+        }
+
+        let list_len = take_u16(&mut ext)?;
+        let mut list = take(&mut ext, list_len)?;
+        while !list.is_empty() {
+            let name_type = take_u8(&mut list)?;
+            let name_len = take_u16(&mut list)?;
+            let name = take(&mut list, name_len)?;
+            if name_type == 0 {
+                return String::from_utf8(name.to_vec()).ok();
+            }
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn original_dst(stream: &TcpStream) -> Option<SocketAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6};
+    use std::os::fd::AsRawFd;
+
+    let fd = stream.as_raw_fd();
+    let is_v6 = stream.local_addr().ok()?.is_ipv6();
+
+    // SAFETY: getsockopt writes at most `len` bytes into the zeroed storage
+    unsafe {
+        let mut storage: libc::sockaddr_storage = std::mem::zeroed();
+        let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let level = if is_v6 { libc::SOL_IPV6 } else { libc::SOL_IP };
+        let ret = libc::getsockopt(
+            fd,
+            level,
+            libc::SO_ORIGINAL_DST,
+            &mut storage as *mut _ as *mut libc::c_void,
+            &mut len,
+        );
+        if ret != 0 {
+            return None;
+        }
+
+        match storage.ss_family as libc::c_int {
+            libc::AF_INET => {
+                let addr = &*(&storage as *const _ as *const libc::sockaddr_in);
+                let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+                Some(SocketAddr::V4(SocketAddrV4::new(
+                    ip,
+                    u16::from_be(addr.sin_port),
+                )))
+            }
+            libc::AF_INET6 => {
+                let addr = &*(&storage as *const _ as *const libc::sockaddr_in6);
+                let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+                Some(SocketAddr::V6(SocketAddrV6::new(
+                    ip,
+                    u16::from_be(addr.sin6_port),
+                    0,
+                    0,
+                )))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn original_dst(_stream: &TcpStream) -> Option<SocketAddr> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Captured from `openssl s_client -tls1_2 -servername example.com`
+    const HELLO_SNI: &[u8] = &[
+        0x16, 0x03, 0x01, 0x00, 0x96, 0x01, 0x00, 0x00, 0x92, 0x03, 0x03, 0x1e, 0x17, 0x59, 0x59,
+        0xc7, 0xc5, 0x46, 0x3e, 0xdc, 0x4f, 0xf1, 0x97, 0x71, 0xd4, 0x9b, 0xa8, 0xf2, 0x0f, 0xe8,
+        0x9d, 0xf7, 0xa6, 0xbc, 0xe4, 0x49, 0x4a, 0xb3, 0x22, 0x42, 0x0a, 0xce, 0x7a, 0x00, 0x00,
+        0x02, 0xc0, 0x2f, 0x01, 0x00, 0x00, 0x67, 0xff, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        0x10, 0x00, 0x0e, 0x00, 0x00, 0x0b, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63,
+        0x6f, 0x6d, 0x00, 0x0b, 0x00, 0x04, 0x03, 0x00, 0x01, 0x02, 0x00, 0x0a, 0x00, 0x0c, 0x00,
+        0x0a, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x1e, 0x00, 0x18, 0x00, 0x19, 0x00, 0x16, 0x00, 0x00,
+        0x00, 0x17, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x2a, 0x00, 0x28, 0x04, 0x03, 0x05, 0x03, 0x06,
+        0x03, 0x08, 0x07, 0x08, 0x08, 0x08, 0x09, 0x08, 0x0a, 0x08, 0x0b, 0x08, 0x04, 0x08, 0x05,
+        0x08, 0x06, 0x04, 0x01, 0x05, 0x01, 0x06, 0x01, 0x03, 0x03, 0x03, 0x01, 0x03, 0x02, 0x04,
+        0x02, 0x05, 0x02, 0x06, 0x02,
+    ];
+
+    // Same client with -noservername
+    const HELLO_NO_SNI: &[u8] = &[
+        0x16, 0x03, 0x01, 0x00, 0x82, 0x01, 0x00, 0x00, 0x7e, 0x03, 0x03, 0xa9, 0xf1, 0x53, 0xd3,
+        0xe0, 0x60, 0xe3, 0x57, 0x46, 0x14, 0xce, 0x64, 0xd8, 0xd7, 0x39, 0x68, 0xe3, 0x0b, 0x57,
+        0x4e, 0xf0, 0x26, 0x13, 0xca, 0x83, 0x56, 0x52, 0xef, 0x09, 0xe7, 0x07, 0xfd, 0x00, 0x00,
+        0x02, 0xc0, 0x2f, 0x01, 0x00, 0x00, 0x53, 0xff, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0b, 0x00,
+        0x04, 0x03, 0x00, 0x01, 0x02, 0x00, 0x0a, 0x00, 0x0c, 0x00, 0x0a, 0x00, 0x1d, 0x00, 0x17,
+        0x00, 0x1e, 0x00, 0x18, 0x00, 0x19, 0x00, 0x16, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x00,
+        0x0d, 0x00, 0x2a, 0x00, 0x28, 0x04, 0x03, 0x05, 0x03, 0x06, 0x03, 0x08, 0x07, 0x08, 0x08,
+        0x08, 0x09, 0x08, 0x0a, 0x08, 0x0b, 0x08, 0x04, 0x08, 0x05, 0x08, 0x06, 0x04, 0x01, 0x05,
+        0x01, 0x06, 0x01, 0x03, 0x03, 0x03, 0x01, 0x03, 0x02, 0x04, 0x02, 0x05, 0x02, 0x06, 0x02,
+    ];
+
+    #[test]
+    fn record_len_reads_the_header() {
+        assert_eq!(record_len(HELLO_SNI), HELLO_SNI.len() - 5);
+        assert_eq!(record_len(HELLO_NO_SNI), HELLO_NO_SNI.len() - 5);
+    }
+
+    #[test]
+    fn sni_from_client_hello() {
+        assert_eq!(parse_sni(HELLO_SNI).as_deref(), Some("example.com"));
+        assert_eq!(parse_sni(HELLO_NO_SNI), None);
+    }
+
+    #[test]
+    fn truncated_or_foreign_records() {
+        // Cut inside the extensions
+        assert_eq!(parse_sni(&HELLO_SNI[..70]), None);
+        assert_eq!(parse_sni(&HELLO_SNI[..3]), None);
+        assert_eq!(parse_sni(b""), None);
+
+        // Not a handshake record
+        let mut alert = HELLO_SNI.to_vec();
+        alert[0] = 0x15;
+        assert_eq!(parse_sni(&alert), None);
+        // A handshake, but no ClientHello
+        let mut server_hello = HELLO_SNI.to_vec();
+        server_hello[5] = 0x02;
+        assert_eq!(parse_sni(&server_hello), None);
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Where a bridged connection really came from.
+#[derive(Clone, Debug)]
+pub struct BridgeInfo {
+    pub peer: SocketAddr,
+    // Destination recovered from the redirected socket, if any
+    pub original_dst: Option<SocketAddr>,
+    // "host:port" the client explicitly asked for (SOCKS5 CONNECT), which
+    // wins over the Host header of plain HTTP requests
+    pub target: Option<String>,
+}
+
+/// Connections that our own listeners (transparent, SOCKS5) open to the HTTP proxy.
+///
+/// hudsucker only sees the loopback side of these, so the handler looks them up
+/// here to find the real client.
+#[derive(Default)]
+pub struct Bridges {
+    inner: Mutex<HashMap<SocketAddr, BridgeInfo>>,
+}
+
+impl Bridges {
+    pub fn get(&self, local: &SocketAddr) -> Option<BridgeInfo> {
+        self.inner.lock().unwrap().get(local).cloned()
+    }
+
+    fn insert(&self, local: SocketAddr, info: BridgeInfo) {
+        self.inner.lock().unwrap().insert(local, info);
+    }
+
+    fn remove(&self, local: &SocketAddr) {
+        self.inner.lock().unwrap().remove(local);
+    }
+}
+
+/// Loopback connection into the HTTP proxy, unregistered when dropped.
+pub struct Bridge {
+    pub stream: TcpStream,
+    local: SocketAddr,
+    bridges: Arc<Bridges>,
+}
+
+impl Drop for Bridge {
+    fn drop(&mut self) {
+        self.bridges.remove(&self.local);
+    }
+}
+
+/// Opens a connection to the HTTP proxy on behalf of `info.peer`.
+///
+/// With a `target` the connection is turned into a CONNECT tunnel, so TLS gets
+/// intercepted exactly like for regular proxy clients. Without one, the caller
+/// sends plain origin-form HTTP and the handler infers the target.
+pub async fn open(
+    proxy_addr: SocketAddr,
+    bridges: Arc<Bridges>,
+    info: BridgeInfo,
+    target: Option<&str>,
+) -> io::Result<Bridge> {
+    let stream = TcpStream::connect(proxy_addr).await?;
+    let local = stream.local_addr()?;
+    bridges.insert(local, info);
+    let mut bridge = Bridge {
+        stream,
+        local,
+        bridges,
+    };
+
+    if let Some(target) = target {
+        let connect = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", target, target);
+        bridge.stream.write_all(connect.as_bytes()).await?;
+
+        // Read the proxy's answer up to the end of its headers
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            if bridge.stream.read(&mut byte).await? == 0 || head.len() > 8192 {
+                return Err(io::Error::new(
+                    io::ErrorKind::ConnectionAborted,
+                    "proxy closed the CONNECT tunnel",
+                ));
+            }
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8_lossy(&head);
+        let status_line = head.lines().next().unwrap_or("");
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("proxy refused CONNECT {}: {}", target, status_line),
+            ));
+        }
+    }
+
+    Ok(bridge)
+}
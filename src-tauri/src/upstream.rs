@@ -0,0 +1,270 @@
+use crate::certs::{CertStore, StoredCert};
+use crate::dns::{DnsOverrides, OverrideResolver};
+use hudsucker::hyper::{
+    client::{
+        connect::{Connected, Connection},
+        HttpConnector,
+    },
+    service::Service,
+    Client, Uri,
+};
+use hudsucker::rustls;
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder, MaybeHttpsStream};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Handshake parameters of an upstream TLS connection. hyper attaches them to
+/// every response received over that connection.
+#[derive(Clone, Debug, Serialize)]
+pub struct TlsDetails {
+    pub sni: Option<String>,
+    pub alpn: Option<String>,
+    pub version: Option<String>,
+    pub cipher_suite: Option<String>,
+    // Leaf first, as sent by the server
+    pub certificates: Vec<CertificateDetails>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CertificateDetails {
+    pub subject: String,
+    pub issuer: String,
+    pub sans: Vec<String>,
+    pub serial: String,
+    pub not_before: i64,
+    pub not_after: i64,
+    pub sha256_fingerprint: String,
+}
+
+/// The proxy's upstream client, hudsucker's rustls client plus handshake
+/// capture, per-host client certificates and DNS overrides.
+pub fn client(client_certs: Arc<CertStore>, dns: Arc<DnsOverrides>) -> Client<UpstreamConnector> {
+    Client::builder()
+        .http1_title_case_headers(true)
+        .http1_preserve_header_case(true)
+        .build(UpstreamConnector::new(client_certs, dns))
+}
+
+type Https = HttpsConnector<HttpConnector<OverrideResolver>>;
+
+fn http_connector(resolver: &OverrideResolver) -> HttpConnector<OverrideResolver> {
+    let mut http = HttpConnector::new_with_resolver(resolver.clone());
+    // https URIs are handed to the TLS layer
+    http.enforce_http(false);
+    http
+}
+
+#[derive(Clone)]
+pub struct UpstreamConnector {
+    https: Https,
+    resolver: OverrideResolver,
+    client_certs: Arc<CertStore>,
+    // Connectors presenting a client certificate, by certificate id
+    mtls: Arc<Mutex<HashMap<String, Https>>>,
+}
+
+impl UpstreamConnector {
+    pub fn new(client_certs: Arc<CertStore>, dns: Arc<DnsOverrides>) -> Self {
+        let resolver = OverrideResolver::new(dns);
+        let https = HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .enable_http2()
+            .wrap_connector(http_connector(&resolver));
+        Self {
+            https,
+            resolver,
+            client_certs,
+            mtls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn connector_for(&self, host: &str) -> Https {
+        let Some(cert) = self.client_certs.find(host) else {
+            return self.https.clone();
+        };
+
+        let mut mtls = self.mtls.lock().unwrap();
+        if let Some(connector) = mtls.get(&cert.info.id) {
+            return connector.clone();
+        }
+        match mtls_connector(&cert, &self.resolver) {
+            Ok(connector) => {
+                mtls.insert(cert.info.id.clone(), connector.clone());
+                connector
+            }
+            Err(e) => {
+                eprintln!("Client certificate {} unusable: {}", cert.info.name, e);
+                self.https.clone()
+            }
+        }
+    }
+}
+
+fn mtls_connector(cert: &StoredCert, resolver: &OverrideResolver) -> anyhow::Result<Https> {
+    let (chain, key) = cert.rustls_identity()?;
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_client_auth_cert(chain, key)?;
+
+    Ok(HttpsConnectorBuilder::new()
+        .with_tls_config(config)
+        .https_or_http()
+        .enable_http1()
+        .enable_http2()
+        .wrap_connector(http_connector(resolver)))
+}
+
+impl Service<Uri> for UpstreamConnector {
+    type Response = UpstreamStream;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.https.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        // rustls only sends SNI for DNS names
+        let sni = uri
+            .host()
+            .filter(|host| host.trim_matches(['[', ']']).parse::<IpAddr>().is_err())
+            .map(|host| host.to_string());
+        let mut https = self.connector_for(uri.host().unwrap_or_default());
+        let connecting = https.call(uri);
+
+        Box::pin(async move {
+            let inner = connecting.await?;
+            let tls = match &inner {
+                MaybeHttpsStream::Https(stream) => {
+                    Some(Arc::new(tls_details(stream.get_ref().1, sni)))
+                }
+                MaybeHttpsStream::Http(_) => None,
+            };
+            Ok(UpstreamStream { inner, tls })
+        })
+    }
+}
+
+pub struct UpstreamStream {
+    inner: MaybeHttpsStream<TcpStream>,
+    tls: Option<Arc<TlsDetails>>,
+}
+
+impl Connection for UpstreamStream {
+    fn connected(&self) -> Connected {
+        let connected = self.inner.connected();
+        match &self.tls {
+            Some(tls) => connected.extra(tls.clone()),
+            None => connected,
+        }
+    }
+}
+
+impl AsyncRead for UpstreamStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for UpstreamStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+fn tls_details(conn: &hudsucker::rustls::ClientConnection, sni: Option<String>) -> TlsDetails {
+    TlsDetails {
+        sni,
+        alpn: conn
+            .alpn_protocol()
+            .map(|p| String::from_utf8_lossy(p).into_owned()),
+        version: conn.protocol_version().map(|v| format!("{:?}", v)),
+        cipher_suite: conn
+            .negotiated_cipher_suite()
+            .map(|s| format!("{:?}", s.suite())),
+        certificates: conn
+            .peer_certificates()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|cert| certificate_details(&cert.0))
+            .collect(),
+    }
+}
+
+pub fn certificate_details(der: &[u8]) -> Option<CertificateDetails> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+
+    let sans = match cert.subject_alternative_name() {
+        Ok(Some(ext)) => ext
+            .value
+            .general_names
+            .iter()
+            .filter_map(|name| match name {
+                GeneralName::DNSName(dns) => Some(dns.to_string()),
+                GeneralName::IPAddress(ip) => match ip.len() {
+                    4 => Some(IpAddr::from(<[u8; 4]>::try_from(*ip).ok()?).to_string()),
+                    16 => Some(IpAddr::from(<[u8; 16]>::try_from(*ip).ok()?).to_string()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let fingerprint = Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":");
+
+    Some(CertificateDetails {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        sans,
+        serial: cert.raw_serial_as_string(),
+        not_before: cert.validity().not_before.timestamp(),
+        not_after: cert.validity().not_after.timestamp(),
+        sha256_fingerprint: fingerprint,
+    })
+}
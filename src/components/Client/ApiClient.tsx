@@ -3,6 +3,7 @@ import { useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { useAtom } from 'jotai';
 import { clientHistoryAtom } from '../../store';
+import { ClientRequest, ClientResponse } from '../../types';
 
 export const ApiClient = () => {
     const [method, setMethod] = useState('GET');
@@ -23,13 +24,16 @@ export const ApiClient = () => {
         setHistory(prev => [newItem, ...prev].slice(0, 50)); // Keep last 50
 
         try {
-            const res: any = await invoke('send_request', {
+            const req: ClientRequest = {
                 method,
                 url,
                 headers: JSON.parse(headers),
-                body: body || null
-            });
-            setResponse(res);
+                body: body ? { type: 'text', content: body } : null
+            };
+            const res: ClientResponse = await invoke('send_request', { req });
+            // Binary bodies stay base64, show the text when there is one
+            const { body: rawBody, body_text, ...rest } = res;
+            setResponse({ ...rest, body: body_text ?? `<${res.size} bytes, base64> ${rawBody}` });
         } catch (e: any) {
             setResponse({ error: e.toString() });
         } finally {
@@ -33,6 +33,18 @@ export const useTrafficSocket = () => {
                             status: undefined,
                         };
                         return [newRecord, ...prev]; // Prepend newest
+                    } else if (data.phase === 'blocked') {
+                        // Answered by a block rule, there is no separate response
+                        const newRecord: RequestRecord = {
+                            id: data.id,
+                            method: data.method,
+                            url: data.url,
+                            timestamp: Date.now(),
+                            status: data.status || undefined,
+                            duration: 0,
+                            blocked: true,
+                        };
+                        return [newRecord, ...prev];
                     } else if (data.phase === 'response') {
                         if (existingIndex !== -1) {
                             const updated = [...prev];
@@ -3,7 +3,15 @@ export interface ProxyEvent {
     method: string;
     url: string;
     status: number | null;
-    phase: 'request' | 'response';
+    phase: 'request' | 'response' | 'sse' | 'blocked';
+    sse_event?: SseEvent;
+}
+
+export interface SseEvent {
+    id: string | null;
+    event: string | null;
+    data: string;
+    retry: number | null;
 }
 
 export interface RequestRecord {
@@ -14,4 +22,236 @@ export interface RequestRecord {
     timestamp: number;
     duration?: number; // In ms (client side calc for now or update from BE later)
     size?: number;
+    blocked?: boolean;
+}
+
+export type RequestBody =
+    | { type: 'text'; content: string }
+    | { type: 'base64'; data: string }
+    | { type: 'file'; path: string }
+    | { type: 'form'; fields: { name: string; value: string }[] }
+    | {
+        type: 'multipart';
+        parts: {
+            name: string;
+            value?: string;
+            file_path?: string;
+            file_name?: string;
+            content_type?: string;
+        }[];
+    };
+
+export interface ClientOptions {
+    http_version?: 'auto' | 'http1' | 'http2' | 'h2c';
+    verify_tls?: boolean;
+    follow_redirects?: boolean;
+    max_redirects?: number;
+    connect_timeout_ms?: number | null;
+    timeout_ms?: number | null;
+    reuse_connections?: boolean;
+    use_cookie_jar?: boolean;
+}
+
+export type Auth =
+    | { type: 'basic'; username: string; password: string }
+    | { type: 'bearer'; token: string }
+    | { type: 'digest'; username: string; password: string }
+    | { type: 'api_key'; key: string; value: string; location?: 'header' | 'query' }
+    | {
+        type: 'aws_sig_v4';
+        access_key_id: string;
+        secret_access_key: string;
+        session_token?: string | null;
+        region: string;
+        service: string;
+    }
+    | ({ type: 'oauth2' } & OAuth2Config);
+
+export interface OAuth2Config {
+    grant_type: 'client_credentials' | 'authorization_code' | 'device_code' | 'refresh_token';
+    token_url: string;
+    authorization_url?: string | null;
+    device_authorization_url?: string | null;
+    client_id: string;
+    client_secret?: string | null;
+    scope?: string | null;
+    redirect_port?: number | null;
+    refresh_token?: string | null;
+}
+
+export interface OAuthFlowStart {
+    flow_id: string;
+    authorization_url: string | null;
+    redirect_uri: string | null;
+    user_code: string | null;
+    verification_uri: string | null;
+    verification_uri_complete: string | null;
+}
+
+export type OAuthFlowStatus =
+    | { state: 'pending' }
+    | { state: 'complete' }
+    | { state: 'failed'; error: string };
+
+export interface ClientRequest {
+    method: string;
+    url: string;
+    headers: Record<string, string>;
+    body: RequestBody | null;
+    options?: ClientOptions;
+    auth?: Auth | null;
+    // Scopes for {{name}} variables, globals always apply
+    collection_id?: string | null;
+    environment_id?: string | null;
+}
+
+export interface ClientResponse {
+    status: number;
+    headers: Record<string, string>;
+    body: string; // base64
+    body_text: string | null;
+    content_type: string | null;
+    size: number;
+    duration_ms: number;
+    url: string;
+    http_version: string;
+    redirects: { url: string; status: number; location: string }[];
+    request_id: string;
+}
+
+export interface Collection {
+    id: string;
+    name: string;
+    description: string | null;
+    sort_order: number;
+    created_at: number;
+    updated_at: number;
+}
+
+export interface Folder {
+    id: string;
+    collection_id: string;
+    parent_id: string | null;
+    name: string;
+    sort_order: number;
+    created_at: number;
+    updated_at: number;
+}
+
+export interface SavedRequest {
+    id: string;
+    collection_id: string;
+    folder_id: string | null;
+    name: string;
+    method: string;
+    url: string;
+    headers: string; // JSON object
+    body: string | null; // JSON RequestBody
+    auth: string | null; // JSON Auth
+    pre_request_script: string | null;
+    test_script: string | null;
+    sort_order: number;
+    created_at: number;
+    updated_at: number;
+}
+
+export interface CollectionTree {
+    collection: Collection;
+    folders: Folder[];
+    requests: SavedRequest[];
+}
+
+export interface Environment {
+    id: string;
+    name: string;
+    created_at: number;
+    updated_at: number;
+}
+
+export type VariableScope =
+    | { scope: 'global' }
+    | { scope: 'collection'; id: string }
+    | { scope: 'environment'; id: string };
+
+export interface Variable {
+    id: string;
+    scope: 'global' | 'collection' | 'environment';
+    scope_id: string | null;
+    key: string;
+    value: string; // "********" for secrets
+    secret: boolean;
+    enabled: boolean;
+}
+
+export interface Cookie {
+    id: string;
+    domain: string;
+    host_only: boolean;
+    path: string;
+    name: string;
+    value: string;
+    expires_at: number | null;
+    secure: boolean;
+    http_only: boolean;
+    same_site: string | null;
+    created_at: number;
+}
+
+export type CodeTarget =
+    | 'curl'
+    | 'rust_reqwest'
+    | 'python_requests'
+    | 'javascript_fetch'
+    | 'go_net_http'
+    | 'httpie'
+    | 'wget';
+
+export interface ProtoFile {
+    id: string;
+    name: string; // Import path, e.g. "helloworld/greeter.proto"
+    content: string;
+    added_at: number;
+}
+
+export interface GrpcMethod {
+    name: string;
+    input_type: string;
+    output_type: string;
+    client_streaming: boolean;
+    server_streaming: boolean;
+    input_example: unknown;
+}
+
+export interface GrpcService {
+    name: string;
+    file: string;
+    methods: GrpcMethod[];
+}
+
+export interface GrpcCatalog {
+    services: GrpcService[];
+    errors: string[];
+}
+
+export interface GrpcRequest {
+    url: string; // http:// for h2c, https:// for TLS
+    service: string;
+    method: string;
+    message: unknown; // An array of messages for client streaming methods
+    metadata?: Record<string, string>;
+    options?: ClientOptions;
+    auth?: Auth | null;
+    collection_id?: string | null;
+    environment_id?: string | null;
+}
+
+export interface GrpcResponse {
+    status: number;
+    status_name: string;
+    status_message: string | null;
+    headers: Record<string, string>;
+    trailers: Record<string, string>;
+    messages: unknown[];
+    duration_ms: number;
+    request_id: string;
 }